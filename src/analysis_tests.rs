@@ -0,0 +1,496 @@
+//! Checks of the estimators the analyze command reports against values worked out by hand.
+
+use rand::Rng;
+use rand_mt::Mt64;
+
+use crate::config::{Dynamics, RunConfig};
+use crate::data_collapse::{self, SurvivalCurve};
+use crate::file_utils::RunData;
+use crate::lifetime_distribution;
+use crate::lifetime_estimates::{self, IntervalMethod};
+use crate::run_merge;
+use crate::scaling_fit::{self, ScalingPoint};
+use crate::simulation::Lifetime;
+use crate::streaming_statistics::{LifetimeSummary, StreamingStatistics};
+use crate::survival::KaplanMeier;
+use crate::svg_plot::{self, Plot, Scale, Series};
+
+const TOLERANCE: f64 = 1e-12;
+
+/// Without censoring the curve is the fraction of lifetimes above each time and its mean is the sample mean
+#[test]
+fn kaplan_meier_without_censoring_is_the_empirical_survival() {
+    let lifetimes = [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0, 6.0];
+    let kaplan_meier = KaplanMeier::new(&lifetimes, &[]);
+
+    for point in &kaplan_meier.points {
+        let surviving = lifetimes.iter().filter(|lifetime| **lifetime > point.time).count() as f64;
+        assert!((point.survival - surviving / lifetimes.len() as f64).abs() < TOLERANCE);
+    }
+    let count = lifetimes.len() as f64;
+    let sample_mean = lifetimes.iter().sum::<f64>() / count;
+    let biased_variance = lifetimes.iter().map(|lifetime| (lifetime - sample_mean).powi(2)).sum::<f64>() / count;
+    let mean = kaplan_meier.mean();
+    assert!((mean.mean - sample_mean).abs() < TOLERANCE);
+    assert!((mean.standard_error - (biased_variance / count).sqrt()).abs() < TOLERANCE);
+    assert!(!mean.restricted);
+    assert_eq!(kaplan_meier.median(), Some(3.0));
+}
+
+/// Deaths at 1, 2, 2 and 4 with trials censored at 3 and 5
+#[test]
+fn kaplan_meier_with_censoring_matches_the_worked_example() {
+    let kaplan_meier = KaplanMeier::new(&[2.0, 1.0, 4.0, 2.0], &[5.0, 3.0]);
+
+    let times: Vec<f64> = kaplan_meier.points.iter().map(|point| point.time).collect();
+    let at_risk: Vec<u64> = kaplan_meier.points.iter().map(|point| point.at_risk).collect();
+    let survival: Vec<f64> = kaplan_meier.points.iter().map(|point| point.survival).collect();
+    assert_eq!(times, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert_eq!(at_risk, vec![6, 5, 3, 2, 1]);
+    for (estimate, expected) in survival.iter().zip([5.0 / 6.0, 0.5, 0.5, 0.25, 0.25]) {
+        assert!((estimate - expected).abs() < TOLERANCE);
+    }
+    // Greenwood: S(2)^2 (1 / (6 * 5) + 2 / (5 * 3))
+    assert!((kaplan_meier.points[1].standard_error - 0.5 * (1.0f64 / 6.0).sqrt()).abs() < TOLERANCE);
+
+    let mean = kaplan_meier.mean();
+    assert!((mean.mean - (1.0 + 5.0 / 6.0 + 0.5 + 0.5 + 0.25)).abs() < TOLERANCE);
+    assert!(mean.restricted);
+    assert_eq!(kaplan_meier.median(), Some(2.0));
+}
+
+/// Running statistics agree with the ones computed from every value, the quantiles to within the sketch accuracy
+#[test]
+fn streaming_statistics_match_the_stored_values() {
+    let mut rng = Mt64::new(5);
+    // lifetimes spread over several decades
+    let values: Vec<f64> = (0..20_000).map(|_| (10f64.powf(rng.gen_range(0.0..6.0))).ceil()).collect();
+    let mut statistics = StreamingStatistics::default();
+    for value in &values {
+        statistics.add(*value);
+    }
+
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (count - 1.0);
+    assert_eq!(statistics.count, values.len() as u64);
+    assert!((statistics.mean - mean).abs() < 1e-9 * mean);
+    assert!((statistics.variance() - variance).abs() < 1e-9 * variance);
+    assert_eq!(statistics.min, values.iter().copied().fold(f64::INFINITY, f64::min));
+    assert_eq!(statistics.max, values.iter().copied().fold(0.0, f64::max));
+
+    let mut sorted_values = values.clone();
+    sorted_values.sort_by(f64::total_cmp);
+    for quantile in [0.0, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 1.0] {
+        let exact = sorted_values[(quantile * (count - 1.0)).floor() as usize];
+        let estimate = statistics.quantiles.quantile(quantile).unwrap();
+        assert!((estimate - exact).abs() <= statistics.quantiles.relative_accuracy * exact * (1.0 + 1e-9), "quantile {quantile}: {estimate} against {exact}");
+    }
+
+    let bins_per_decade = statistics.histogram.bins_per_decade as f64;
+    for (bin, bin_count) in &statistics.histogram.counts {
+        let lower_edge = 10f64.powf(*bin as f64 / bins_per_decade);
+        let upper_edge = 10f64.powf((*bin + 1) as f64 / bins_per_decade);
+        let expected_count = values.iter().filter(|value| **value >= lower_edge && **value < upper_edge).count() as u64;
+        assert!(bin_count.abs_diff(expected_count) <= 1, "bin {bin}: {bin_count} against {expected_count}");
+    }
+    assert_eq!(statistics.histogram.counts.values().sum::<u64>(), values.len() as u64);
+}
+
+/// Censored lifetimes are counted apart, and the gillespie dynamics is summarised in time
+#[test]
+fn lifetime_summaries_keep_censored_trials_apart() {
+    let lifetimes = [
+        Lifetime { step_count: 12, time: Some(1.5), immortal: false, censored: false },
+        Lifetime { step_count: 40, time: Some(4.0), immortal: false, censored: true },
+        Lifetime { step_count: 20, time: Some(2.5), immortal: false, censored: false },
+    ];
+    let mut summary = LifetimeSummary::default();
+    for lifetime in &lifetimes {
+        summary.add(lifetime);
+    }
+    assert_eq!(summary.censored_trials, 1);
+    assert_eq!(summary.steps.count, 2);
+    assert_eq!(summary.steps.mean, 16.0);
+    assert_eq!(summary.lifetimes().mean, 2.0);
+    assert_eq!(summary.lifetimes().variance(), 0.5);
+}
+
+/// Points on tau = amplitude N^z exp(correction N^-omega), each with a one percent error
+fn scaling_points(amplitude: f64, z: f64, correction: f64, omega: f64) -> Vec<ScalingPoint> {
+    (8..=40).step_by(4).map(|chain_size| {
+        let mean_lifetime = amplitude * (chain_size as f64).powf(z) * (correction * (chain_size as f64).powf(-omega)).exp();
+        ScalingPoint { chain_size, mean_lifetime, standard_error: 0.01 * mean_lifetime }
+    }).collect()
+}
+
+/// An exact power law is fitted exactly, with the standard error of an unweighted straight line fit
+#[test]
+fn power_law_fit_recovers_z() {
+    let points = scaling_points(3.0, 1.5, 0.0, 1.0);
+    let fit = scaling_fit::fit_z(&points, None).unwrap();
+
+    assert!((fit.z - 1.5).abs() < 1e-9);
+    assert!((fit.amplitude - 3.0).abs() < 1e-9);
+    assert!(fit.reduced_chi_squared.unwrap() < 1e-12);
+    assert!(fit.jackknife_error.unwrap() < 1e-9);
+    // every ln tau has the error 0.01, so var(z) = 0.01^2 / sum (ln N - mean ln N)^2
+    let log_chain_sizes: Vec<f64> = points.iter().map(|point| (point.chain_size as f64).ln()).collect();
+    let mean_log_chain_size = log_chain_sizes.iter().sum::<f64>() / log_chain_sizes.len() as f64;
+    let spread: f64 = log_chain_sizes.iter().map(|log_chain_size| (log_chain_size - mean_log_chain_size).powi(2)).sum();
+    assert!((fit.standard_error - 0.01 / spread.sqrt()).abs() < 1e-12);
+    assert_eq!((fit.min_chain_size, fit.max_chain_size, fit.chain_sizes), (8, 40, points.len()));
+}
+
+/// Corrections to scaling bias the pure power law and are taken out by the corrected fit
+#[test]
+fn corrected_fit_recovers_z_under_corrections_to_scaling() {
+    let points = scaling_points(2.0, 2.0, -3.0, 1.0);
+
+    let corrected_fit = scaling_fit::fit_z(&points, Some(1.0)).unwrap();
+    assert!((corrected_fit.z - 2.0).abs() < 1e-9);
+    assert!((corrected_fit.correction_amplitude.unwrap() + 3.0).abs() < 1e-9);
+    assert!(corrected_fit.standard_error > 0.0);
+
+    let power_law_fit = scaling_fit::fit_z(&points, None).unwrap();
+    assert!(power_law_fit.z > 2.0 + 10.0 * power_law_fit.standard_error);
+    assert!(power_law_fit.reduced_chi_squared.unwrap() > 1.0);
+
+    assert!(scaling_fit::fit_z(&points[..2], Some(1.0)).is_none());
+    assert!(scaling_fit::fit_z(&points[..2], None).unwrap().jackknife_error.is_none());
+}
+
+/// Step counts are turned into sweeps of chain_size - 2 steps
+#[test]
+fn scaling_points_are_measured_in_sweeps() {
+    let mut run_data = RunData::new(&RunConfig::default(), 1);
+    run_data.runs.insert(6, vec![10, 30]);
+    run_data.runs.insert(8, vec![60]);
+    run_data.runs.insert(10, vec![40, 120]);
+
+    let points = scaling_fit::scaling_points(&run_data, 0, 8);
+    // a single lifetime has no standard error and chain size 10 is out of range
+    assert_eq!(points, vec![ScalingPoint { chain_size: 6, mean_lifetime: 5.0, standard_error: 2.5 }]);
+}
+
+/// A chain size whose lifetimes are all equal has no standard error and would get an infinite weight in the fit, so it
+/// is left out of the fit but still counts for the slope estimates of the z data file
+#[test]
+fn chain_sizes_without_a_standard_error_are_not_fitted() {
+    let mut run_data = RunData::new(&RunConfig::default(), 1);
+    // mean lifetimes in steps of 2 N^3, so z = 2 from the slopes
+    run_data.runs.insert(4, vec![128, 128]);
+    run_data.runs.insert(8, vec![1000, 1048]);
+    run_data.runs.insert(16, vec![8092, 8292]);
+
+    let points = scaling_fit::scaling_points(&run_data, 0, 16);
+    assert_eq!(points.iter().map(|point| point.chain_size).collect::<Vec<usize>>(), vec![8, 16]);
+    let fit = scaling_fit::fit_z(&points, None).unwrap();
+    assert!(fit.z.is_finite() && fit.standard_error.is_finite());
+
+    let (endpoint_z, average_z) = scaling_fit::slope_estimates(&run_data, 0, 16).unwrap();
+    assert!((endpoint_z - 2.0).abs() < TOLERANCE);
+    assert!((average_z - 2.0).abs() < TOLERANCE);
+    assert!(scaling_fit::slope_estimates(&run_data, 8, 8).is_none());
+}
+
+/// The quantiles of the standard normal distribution everyone remembers, on both sides and in the tails
+#[test]
+fn standard_normal_quantiles_match_the_tables() {
+    assert!((lifetime_estimates::standard_normal_quantile(0.975) - 1.959963985).abs() < 1e-8);
+    assert!((lifetime_estimates::standard_normal_quantile(0.025) + 1.959963985).abs() < 1e-8);
+    assert!((lifetime_estimates::standard_normal_quantile(0.995) - 2.575829304).abs() < 1e-8);
+    assert!(lifetime_estimates::standard_normal_quantile(0.5).abs() < TOLERANCE);
+}
+
+/// Percentiles interpolate linearly between the sorted values
+#[test]
+fn percentile_intervals_interpolate_between_order_statistics() {
+    let mut values: Vec<f64> = (0..=100).rev().map(|value| value as f64).collect();
+    let (lower, upper) = lifetime_estimates::percentile_interval(&mut values, 0.95);
+    assert!((lower - 2.5).abs() < TOLERANCE);
+    assert!((upper - 97.5).abs() < TOLERANCE);
+
+    let mut values = vec![1.0, 3.0];
+    let (lower, upper) = lifetime_estimates::percentile_interval(&mut values, 0.5);
+    assert!((lower - 1.5).abs() < TOLERANCE);
+    assert!((upper - 2.5).abs() < TOLERANCE);
+}
+
+/// With many trials the bootstrap interval of the mean is close to the normal one, and the same seed gives the same
+/// interval
+#[test]
+fn bootstrap_intervals_agree_with_the_normal_approximation_for_many_trials() {
+    let mut rng = Mt64::new(3);
+    let mut run_data = RunData::new(&RunConfig::default(), 1);
+    run_data.runs.insert(6, (0..4000).map(|_| rng.gen_range(1..200)).collect());
+
+    let bootstrap = lifetime_estimates::estimate_lifetimes("run", &run_data, 2000, 0.95, &mut Mt64::new(0));
+    let normal = lifetime_estimates::estimate_lifetimes("run", &run_data, 0, 0.95, &mut Mt64::new(0));
+    assert_eq!(bootstrap, lifetime_estimates::estimate_lifetimes("run", &run_data, 2000, 0.95, &mut Mt64::new(0)));
+    let (bootstrap, normal) = (&bootstrap[0], &normal[0]);
+    assert_eq!(bootstrap.interval_method, IntervalMethod::Bootstrap);
+    assert_eq!(normal.interval_method, IntervalMethod::Normal);
+    assert_eq!(bootstrap.trials, 4000);
+    assert!(bootstrap.mean_interval.0 < bootstrap.mean && bootstrap.mean < bootstrap.mean_interval.1);
+    let width = normal.mean_interval.1 - normal.mean_interval.0;
+    assert!((bootstrap.mean_interval.0 - normal.mean_interval.0).abs() < 0.1 * width);
+    assert!((bootstrap.mean_interval.1 - normal.mean_interval.1).abs() < 0.1 * width);
+    assert!((bootstrap.log_mean_standard_error / normal.log_mean_standard_error - 1.0).abs() < 0.1);
+}
+
+/// Geometric step counts die with the same probability at every step, so every hazard and the tail rate match it
+#[test]
+fn geometric_lifetimes_have_a_flat_hazard_and_their_decay_rate() {
+    let death_probability = 0.02;
+    let mut rng = Mt64::new(5);
+    let lifetimes: Vec<f64> = (0..20000).map(|_| {
+        let mut steps = 1.0;
+        while rng.gen::<f64>() >= death_probability {
+            steps += 1.0;
+        }
+        steps
+    }).collect();
+
+    let distribution = lifetime_distribution::lifetime_distribution(&lifetimes, &[], 10, true, 0.2);
+    assert_eq!(distribution.bins.iter().map(|bin| bin.deaths).sum::<u64>(), 20000);
+    // bins without a step count in them are left out
+    assert!(distribution.bins.iter().all(|bin| bin.upper.ceil() > bin.lower.ceil()));
+    assert_eq!(distribution.bins.last().unwrap().survival, 0.0);
+    for bin in distribution.bins.iter().filter(|bin| bin.deaths > 400) {
+        assert!((bin.hazard - death_probability).abs() < 4.0 * bin.hazard_standard_error);
+    }
+
+    let tail_fit = distribution.tail_fit.unwrap();
+    let rate = -(1.0 - death_probability).ln();
+    assert!((tail_fit.survival - 0.2).abs() < 0.01);
+    assert!((tail_fit.rate - rate).abs() < 4.0 * tail_fit.standard_error);
+    assert!(tail_fit.standard_error < 0.05 * rate);
+}
+
+/// Exponential lifetimes cut short at a fixed time keep the survival and decay rate of the full lifetimes
+#[test]
+fn censored_exponential_lifetimes_keep_their_decay_rate() {
+    let rate = 0.5;
+    let cap = 4.0;
+    let mut rng = Mt64::new(9);
+    let (lifetimes, censored_lifetimes): (Vec<f64>, Vec<f64>) = (0..20000).map(|_| -(1.0 - rng.gen::<f64>()).ln() / rate).partition(|lifetime| *lifetime <= cap);
+    let censored_lifetimes = vec![cap; censored_lifetimes.len()];
+
+    let distribution = lifetime_distribution::lifetime_distribution(&lifetimes, &censored_lifetimes, 5, false, 0.5);
+    assert_eq!(distribution.bins.iter().map(|bin| bin.deaths).sum::<u64>(), lifetimes.len() as u64);
+    for bin in &distribution.bins {
+        assert!((bin.survival - (-rate * bin.upper.min(cap)).exp()).abs() < 0.02);
+    }
+    let tail_fit = distribution.tail_fit.unwrap();
+    assert!((tail_fit.rate - rate).abs() < 4.0 * tail_fit.standard_error);
+}
+
+/// A summary bins the lifetimes like the lifetimes themselves, with survival counting the censored trials as alive
+#[test]
+fn summary_distributions_bin_like_the_stored_lifetimes() {
+    let mut summary = LifetimeSummary::default();
+    let mut lifetimes = Vec::new();
+    for step_count in [1, 2, 3, 5, 8, 13, 21, 34, 55, 89] {
+        summary.add(&Lifetime { step_count, time: None, immortal: false, censored: false });
+        lifetimes.push(step_count as f64);
+    }
+    summary.add(&Lifetime { step_count: 100, time: None, immortal: false, censored: true });
+
+    let from_summary = lifetime_distribution::summary_distribution(&summary, true);
+    let from_lifetimes = lifetime_distribution::lifetime_distribution(&lifetimes, &[100.0], 10, true, 0.2);
+    assert!(from_summary.tail_fit.is_none());
+    let occupied = |distribution: &lifetime_distribution::LifetimeDistribution| distribution.bins.iter()
+        .filter(|bin| bin.deaths > 0)
+        .map(|bin| (bin.lower, bin.deaths, bin.density, bin.survival))
+        .collect::<Vec<_>>();
+    let (from_summary_bins, from_lifetime_bins) = (occupied(&from_summary), occupied(&from_lifetimes));
+    assert_eq!(from_summary_bins.len(), from_lifetime_bins.len());
+    for (summary_bin, lifetime_bin) in from_summary_bins.iter().zip(&from_lifetime_bins) {
+        assert_eq!((summary_bin.0, summary_bin.1, summary_bin.2), (lifetime_bin.0, lifetime_bin.1, lifetime_bin.2));
+        assert!((summary_bin.3 - lifetime_bin.3).abs() < TOLERANCE);
+    }
+    assert!((from_summary.bins.last().unwrap().survival - 1.0 / 11.0).abs() < TOLERANCE);
+}
+
+/// Exponential survival curves of mean lifetime N^z (S/N)^kappa, sampled where the survival is between 0.05 and 0.95
+fn exponential_survival_curves(z: f64, sector_exponent: f64) -> Vec<SurvivalCurve> {
+    let mut curves = Vec::new();
+    for spin_sector in [1, 2, 3] {
+        for chain_size in [8, 12, 16, 20] {
+            let mean_lifetime = (chain_size as f64).powf(z) * (spin_sector as f64 / chain_size as f64).powf(sector_exponent);
+            let rescaled_lifetimes: Vec<f64> = (0..40).map(|point| 0.05 * (60f64.ln() * point as f64 / 39.0).exp()).collect();
+            curves.push(SurvivalCurve {
+                spin_sector,
+                chain_size,
+                log_lifetimes: rescaled_lifetimes.iter().map(|lifetime| (lifetime * mean_lifetime).ln()).collect(),
+                survival: rescaled_lifetimes.iter().map(|lifetime| (-lifetime).exp()).collect(),
+                standard_errors: vec![0.001; rescaled_lifetimes.len()],
+            });
+        }
+    }
+    curves
+}
+
+/// The collapse finds the exponents the curves were made with, from a start well away from them
+#[test]
+fn collapse_recovers_z_and_the_sector_exponent() {
+    let curves = exponential_survival_curves(3.0, -0.5);
+    let (quality, overlapping_points) = data_collapse::collapse_quality(&curves, 3.0, -0.5);
+    assert!(quality < 0.01);
+    // every point lies within the other curves, up to rounding at the ends
+    assert!(overlapping_points >= curves.len() * 39);
+    assert!(data_collapse::collapse_quality(&curves, 2.9, -0.5).0 > 10.0);
+
+    let collapse = data_collapse::optimize_collapse(&curves, 2.5, Some(0.0));
+    assert!((collapse.z - 3.0).abs() < 1e-3);
+    assert!((collapse.sector_exponent.unwrap() + 0.5).abs() < 1e-3);
+    assert!(collapse.z_error.unwrap() < 0.05);
+    assert!(collapse.sector_exponent_error.unwrap() < 0.05);
+
+    let spin_sector_one: Vec<SurvivalCurve> = curves.into_iter().filter(|curve| curve.spin_sector == 1).collect();
+    let collapse = data_collapse::optimize_collapse(&spin_sector_one, 2.5, None);
+    // within one spin sector (S/N)^kappa only changes the power of N
+    assert!((collapse.z - 3.5).abs() < 1e-3);
+    assert!(collapse.sector_exponent.is_none());
+}
+
+/// Step counts are turned into sweeps and the survival is cut to the requested range
+#[test]
+fn survival_curves_are_measured_in_sweeps() {
+    let mut run_data = RunData::new(&RunConfig::default(), 2);
+    run_data.runs.insert(6, vec![16, 4, 12, 8]);
+
+    let curve = data_collapse::survival_curve(&run_data, 2, 6, 0.05, 0.95, 50).unwrap();
+    let log_lifetimes: Vec<f64> = [1.0f64, 2.0, 3.0].iter().map(|sweeps| sweeps.ln()).collect();
+    assert_eq!(curve.log_lifetimes, log_lifetimes);
+    assert_eq!(curve.survival, vec![0.75, 0.5, 0.25]);
+    assert!(curve.standard_errors.iter().all(|standard_error| *standard_error > 0.0));
+    assert_eq!(data_collapse::survival_curve(&run_data, 2, 6, 0.05, 0.95, 2).unwrap().survival, vec![0.75, 0.25]);
+}
+
+/// Linear ticks step by 1, 2 or 5 times a power of 10 and logarithmic ones sit at 1, 2 and 5 times every decade
+#[test]
+fn plot_ticks_fall_on_round_numbers() {
+    let labels = |ticks: Vec<(f64, String)>| ticks.into_iter().map(|(_, label)| label).collect::<Vec<String>>();
+    assert_eq!(labels(svg_plot::linear_ticks(-0.3, 10.4)), ["0", "2", "4", "6", "8", "10"]);
+    assert_eq!(labels(svg_plot::linear_ticks(0.01, 0.07)), ["0.01", "0.02", "0.03", "0.04", "0.05", "0.06", "0.07"]);
+    assert_eq!(labels(svg_plot::log_ticks(-0.1, 2.1)), ["1", "2", "5", "10", "20", "50", "100"]);
+    assert_eq!(labels(svg_plot::log_ticks(-1.0, 6.0)), ["0.1", "1", "10", "100", "1000", "10000", "1e5", "1e6"]);
+}
+
+/// Every point gets a marker and an error bar, and points a logarithmic axis cannot show are left out
+#[test]
+fn plots_draw_every_point_they_can_show() {
+    let mut plot = Plot::new("lifetimes <N>", "chain size", "mean lifetime", Scale::Log, Scale::Log);
+    plot.add_series(Series::markers("simulated", vec![(8.0, 10.0), (12.0, 40.0), (16.0, 0.0)], vec![1.0, 20.0, 1.0]));
+    plot.add_series(Series::line("fit", vec![(8.0, 10.0), (16.0, 80.0)]));
+    let svg = plot.to_svg();
+
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert!(svg.contains("lifetimes &lt;N&gt;"));
+    // two markers and the legend marker
+    assert_eq!(svg.matches("<circle").count(), 3);
+    assert_eq!(svg.matches("<path").count(), 2);
+    assert_eq!(svg.matches("<polyline").count(), 1);
+}
+
+/// Merging streaming statistics gives the statistics of both streams, as if one had seen every value
+#[test]
+fn merged_streaming_statistics_match_a_single_stream() {
+    let mut rng = Mt64::new(11);
+    let values: Vec<f64> = (0..1000).map(|_| rng.gen_range(1.0..500.0)).collect();
+    let mut all = StreamingStatistics::default();
+    let mut first = StreamingStatistics::default();
+    let mut second = StreamingStatistics::default();
+    for (index, value) in values.iter().enumerate() {
+        all.add(*value);
+        if index < 300 { first.add(*value) } else { second.add(*value) }
+    }
+
+    first.merge(&second);
+    assert_eq!(first.count, all.count);
+    assert!((first.mean - all.mean).abs() < 1e-9 * all.mean);
+    assert!((first.variance() - all.variance()).abs() < 1e-9 * all.variance());
+    assert_eq!((first.min, first.max), (all.min, all.max));
+    assert_eq!(first.histogram, all.histogram);
+    assert_eq!(first.quantiles, all.quantiles);
+}
+
+fn run_data_with_seed(seed: u64, runs: Vec<(usize, Vec<u128>)>) -> RunData {
+    let mut config = RunConfig::default();
+    config.rng.seed = Some(seed);
+    let mut run_data = RunData::new(&config, 1);
+    run_data.runs.extend(runs);
+    run_data
+}
+
+/// Lifetimes of the chain sizes two files share are concatenated and every file becomes a source of the merged one
+#[test]
+fn merging_concatenates_lifetimes_and_records_sources() {
+    let first = run_data_with_seed(1, vec![(6, vec![3, 4]), (8, vec![10])]);
+    let mut second = run_data_with_seed(2, vec![(8, vec![20, 30]), (10, vec![50])]);
+    second.immortal_trials.insert(8, 2);
+    let inputs = vec![("a.json".to_string(), first), ("b.json".to_string(), second)];
+
+    let (merged, warnings) = run_merge::merge_runs(&inputs, false, None).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(merged.runs[&8], vec![10, 20, 30]);
+    assert_eq!(merged.trial_counts().into_iter().collect::<Vec<_>>(), vec![(6, 2), (8, 5), (10, 1)]);
+    let config = merged.config.as_ref().unwrap();
+    assert_eq!((config.min_chain_size, config.max_chain_size), (6, 10));
+    assert_eq!(merged.sources.iter().map(|source| (source.file_name.as_str(), source.master_seed)).collect::<Vec<_>>(), vec![("a.json", Some(1)), ("b.json", Some(2))]);
+
+    // merging the merged file again keeps the original sources and notices the repeated trials
+    let inputs = vec![("merged.json".to_string(), merged.clone()), ("b.json".to_string(), inputs[1].1.clone())];
+    let message = run_merge::merge_runs(&inputs, false, None).unwrap_err();
+    assert!(message.contains("master seed 2 repeats the trials of b.json"));
+
+    // the trial seeds also depend on the chain size, so a master seed repeated for other chain sizes draws other trials
+    let inputs = vec![("a.json".to_string(), run_data_with_seed(1, vec![(6, vec![3])])), ("c.json".to_string(), run_data_with_seed(1, vec![(8, vec![10])]))];
+    let (merged, warnings) = run_merge::merge_runs(&inputs, false, None).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(merged.trial_counts().into_iter().collect::<Vec<_>>(), vec![(6, 1), (8, 1)]);
+}
+
+/// Files that sample other lifetimes are refused unless mismatches are allowed, and then recorded in their source
+#[test]
+fn merging_refuses_mismatched_configs_unless_allowed() {
+    let first = run_data_with_seed(1, vec![(6, vec![3, 4])]);
+    let mut other_dynamics = run_data_with_seed(2, vec![(6, vec![5])]);
+    other_dynamics.config.as_mut().unwrap().dynamics = Dynamics::NFoldWay;
+    let mut other_spin_sector = run_data_with_seed(3, vec![(6, vec![5])]);
+    other_spin_sector.spin_sector = Some(2);
+
+    let inputs = vec![("a.json".to_string(), first.clone()), ("b.json".to_string(), other_dynamics)];
+    assert!(run_merge::merge_runs(&inputs, false, None).unwrap_err().contains("dynamics NFoldWay instead of RandomIndex"));
+    let (merged, warnings) = run_merge::merge_runs(&inputs, true, None).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(merged.runs[&6], vec![3, 4, 5]);
+    assert!(merged.sources[0].mismatches.is_empty());
+    assert_eq!(merged.sources[1].mismatches, vec!["dynamics NFoldWay instead of RandomIndex".to_string()]);
+
+    // another spin sector is never merged
+    let inputs = vec![("a.json".to_string(), first), ("c.json".to_string(), other_spin_sector)];
+    assert!(run_merge::merge_runs(&inputs, true, None).is_err());
+}
+
+/// A file of lifetimes merged with a summary is folded into the summary
+#[test]
+fn merging_with_a_summary_folds_the_lifetimes_into_it() {
+    let mut summarized = run_data_with_seed(1, Vec::new());
+    let mut summary = LifetimeSummary::default();
+    for step_count in [2, 4] {
+        summary.add(&Lifetime { step_count, time: None, immortal: false, censored: false });
+    }
+    summarized.summaries.insert(6, summary);
+    let mut stored = run_data_with_seed(2, vec![(6, vec![6, 8])]);
+    stored.censored_runs.insert(6, vec![100]);
+
+    let inputs = vec![("a.json".to_string(), summarized), ("b.json".to_string(), stored)];
+    let (merged, _) = run_merge::merge_runs(&inputs, false, None).unwrap();
+    assert!(merged.runs.is_empty() && merged.censored_runs.is_empty());
+    let summary = &merged.summaries[&6];
+    assert_eq!((summary.steps.count, summary.censored_trials), (4, 1));
+    assert!((summary.steps.mean - 5.0).abs() < TOLERANCE);
+}
@@ -0,0 +1,688 @@
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+
+use crate::config::{self, Dynamics, ExcitedBondConfig, LifetimeStorage, OutputFormat, RngKind, RunConfig, SnapshotTrigger, TimeUnit, TrajectoryFormat, UpdateScheme};
+use crate::file_utils::{self, Checkpoint};
+use crate::lifetime_estimates::EstimateFormat;
+use crate::observables::ObservableKind;
+use crate::site_placer::SitePlacerKind;
+
+/// Command line interface for running and inspecting Fredkin chain lifetime simulations
+#[derive(Debug, Parser)]
+#[command(name = "fredkin_chain", version, about = "Lifetime simulations of excited Fredkin spin chains")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Evolve excited chains until they die and record the number of steps each one lived
+    Simulate(Box<SimulateArgs>),
+    /// Summarize the lifetimes stored in one or more run files
+    Analyze(AnalyzeArgs),
+    /// Generate a single excited chain and print it
+    Inspect(InspectArgs),
+    /// List every configuration of a chain along with the probability the sampler generates it with
+    Enumerate(EnumerateArgs),
+    /// Solve for the exact mean lifetime of small chains and compare it with simulated lifetimes
+    Exact(ExactArgs),
+    /// Fit the dynamical exponent z of the mean lifetime, tau ~ N^z in sweeps, and store it in the z data file
+    FitZ(FitZArgs),
+    /// Collapse the survival curves of every chain size and spin sector onto one function of t / (N^z (S/N)^kappa),
+    /// optimizing the exponents
+    Collapse(CollapseArgs),
+    /// Draw SVG plots of the mean lifetime against chain size, and against spin sector at a fixed chain size
+    Plot(PlotArgs),
+    /// Merge run files of the same spin sector, from several machines or runs, into a single run file
+    Merge(MergeArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct SimulateArgs {
+    /// TOML or JSON file describing the run. Flags given on the command line override its values
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Directory the run_ss_*.json files are written to [default: ./data/runs]
+    #[arg(long)]
+    pub storage_directory: Option<String>,
+
+    /// Number of trials run for every chain size [default: 1000]
+    #[arg(long)]
+    pub trials: Option<u64>,
+
+    /// Smallest chain size to simulate. Raised to the smallest chain that fits the excited bonds when it is too small [default: 0]
+    #[arg(long)]
+    pub min_chain_size: Option<usize>,
+
+    /// Largest chain size to simulate
+    #[arg(long)]
+    pub max_chain_size: Option<usize>,
+
+    /// Amount the chain size grows by between simulated sizes [default: 2]
+    #[arg(long)]
+    pub chain_size_step: Option<usize>,
+
+    /// First spin sector (number of up-canted bonds) to simulate [default: 1]
+    #[arg(long)]
+    pub min_spin_sector: Option<usize>,
+
+    /// Last spin sector to simulate. Defaults to --min-spin-sector
+    #[arg(long)]
+    pub max_spin_sector: Option<usize>,
+
+    /// Number of down-canted bonds placed in every chain [default: 0]
+    #[arg(long)]
+    pub down_cant: Option<usize>,
+
+    /// Number of mismatch bonds placed in every chain [default: 0]
+    #[arg(long)]
+    pub mismatch: Option<usize>,
+
+    /// Strategy that chooses the sites of the excited bonds in the initial chains [default: k-beach]
+    #[arg(long, value_enum)]
+    pub site_placer: Option<SitePlacerKind>,
+
+    /// Engine that evolves the chains until they die [default: random-index]
+    #[arg(long, value_enum)]
+    pub dynamics: Option<Dynamics>,
+
+    /// Order the random-index dynamics attempts the triples in [default: random-sequential]
+    #[arg(long, value_enum)]
+    pub update_scheme: Option<UpdateScheme>,
+
+    /// Stop trials still alive after this many steps, moves for the gillespie dynamics, and store them as censored. 0 never stops a trial [default: 0]
+    #[arg(long)]
+    pub max_steps: Option<u64>,
+
+    /// Unit the lifetimes of the gillespie dynamics are recorded in [default: sweeps]
+    #[arg(long, value_enum)]
+    pub time_unit: Option<TimeUnit>,
+
+    /// Energy scale J of the Fredkin Hamiltonian, sets the rate of the moves in physical time [default: 1]
+    #[arg(long)]
+    pub coupling: Option<f64>,
+
+    /// Keep every trial's lifetime, or only running statistics of them that stay the same size however many trials run [default: runs]
+    #[arg(long, value_enum)]
+    pub lifetime_storage: Option<LifetimeStorage>,
+
+    /// Record the chains while they evolve, taking a snapshot every --trajectory-interval steps or moves [default: off]
+    #[arg(long, value_enum)]
+    pub trajectory: Option<SnapshotTrigger>,
+
+    /// Number of steps or moves between trajectory snapshots [default: 1]
+    #[arg(long)]
+    pub trajectory_interval: Option<u64>,
+
+    /// Format the trajectory files are written in [default: json-lines]
+    #[arg(long, value_enum)]
+    pub trajectory_format: Option<TrajectoryFormat>,
+
+    /// Quantities measured at every observation time and averaged over the trials alive at that time
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub observables: Vec<ObservableKind>,
+
+    /// Comma separated, increasing times the observables are measured at. Steps, or the time unit of the gillespie dynamics
+    #[arg(long, value_delimiter = ',')]
+    pub observation_times: Vec<f64>,
+
+    /// Re-label the excited sites after every step so the chain state is always a valid decorated Dyck word
+    #[arg(long)]
+    pub track_bond_identity: bool,
+
+    /// Number of trials between checkpoints within a chain size. 0 only checkpoints after each chain size [default: 0]
+    #[arg(long)]
+    pub checkpoint_interval: Option<u64>,
+
+    /// Continue the interrupted run whose checkpoint is in the storage directory.
+    /// The run uses the config saved in the checkpoint, only --threads may be changed and any other flag that changes the run is refused
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Number of worker threads the trials are spread over. 0 uses every available core [default: 0]
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Master seed of the run. Every trial is seeded from it. A random seed is drawn when none is given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Random number generator driving the run [default: mt64]
+    #[arg(long, value_enum)]
+    pub rng: Option<RngKind>,
+
+    /// Format the run files are written in [default: json]
+    #[arg(long, value_enum)]
+    pub output_format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Args)]
+pub struct AnalyzeArgs {
+    /// Run files produced by the simulate command
+    #[arg(required = true)]
+    pub run_files: Vec<String>,
+
+    /// Also print the mean and standard error of every observable at every observation time
+    #[arg(long)]
+    pub observables: bool,
+
+    /// Also print the Kaplan-Meier survival curve of every chain size
+    #[arg(long)]
+    pub survival: bool,
+
+    /// Also print the log-binned histogram, survival and hazard of the lifetimes of every chain size, with the decay
+    /// rate of their exponential tail
+    #[arg(long)]
+    pub distribution: bool,
+
+    /// Number of histogram bins per factor of 10 of the lifetimes. Summaries keep the binning they were run with
+    #[arg(long, default_value_t = 10)]
+    pub bins_per_decade: u32,
+
+    /// The tail fit starts where the survival drops to this fraction of the trials
+    #[arg(long, default_value_t = 0.2)]
+    pub tail_fraction: f64,
+
+    /// Format of the lifetime estimates. csv and json only write the estimates
+    #[arg(long, value_enum, default_value_t = EstimateFormat::Text)]
+    pub format: EstimateFormat,
+
+    /// File the csv or json estimates are written to instead of standard output
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Number of bootstrap resamples of the trials of every chain size. 0 uses the normal approximation instead
+    #[arg(long, default_value_t = 1000)]
+    pub bootstrap_samples: usize,
+
+    /// Probability the confidence intervals are meant to cover
+    #[arg(long, default_value_t = 0.95)]
+    pub confidence_level: f64,
+
+    /// Seed of the bootstrap resampling, so repeated analyses give the same intervals
+    #[arg(long, default_value_t = 0)]
+    pub bootstrap_seed: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    /// Size of the generated chain
+    #[arg(long)]
+    pub chain_size: usize,
+
+    /// Number of up-canted bonds placed in the chain
+    #[arg(long, default_value_t = 1)]
+    pub spin_sector: usize,
+
+    /// Number of down-canted bonds placed in the chain
+    #[arg(long, default_value_t = 0)]
+    pub down_cant: usize,
+
+    /// Number of mismatch bonds placed in the chain
+    #[arg(long, default_value_t = 0)]
+    pub mismatch: usize,
+
+    /// Master seed of a simulate run. Generates the chain of --trial instead of a random one
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Trial of the simulate run to reproduce, counted from 0 within the chain size
+    #[arg(long, default_value_t = 0, requires = "seed")]
+    pub trial: u64,
+
+    /// Random number generator the simulate run used
+    #[arg(long, value_enum, default_value_t = RngKind::Mt64)]
+    pub rng: RngKind,
+
+    /// Strategy that chooses the sites of the excited bonds, the one the simulate run used
+    #[arg(long, value_enum, default_value_t = SitePlacerKind::KBeach)]
+    pub site_placer: SitePlacerKind,
+
+    /// Engine that evolves the chain, the one the simulate run used
+    #[arg(long, value_enum, default_value_t = Dynamics::RandomIndex)]
+    pub dynamics: Dynamics,
+
+    /// Order the random-index dynamics attempts the triples in, the one the simulate run used
+    #[arg(long, value_enum, default_value_t = UpdateScheme::RandomSequential)]
+    pub update_scheme: UpdateScheme,
+
+    /// Evolve the chain until it dies and print its lifetime
+    #[arg(long)]
+    pub evolve: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct EnumerateArgs {
+    /// Size of the enumerated chains
+    #[arg(long)]
+    pub chain_size: usize,
+
+    /// Number of up-canted bonds placed in each chain. With no excited bonds at all the plain Dyck words are listed
+    #[arg(long, default_value_t = 1)]
+    pub spin_sector: usize,
+
+    /// Number of down-canted bonds placed in the chain
+    #[arg(long, default_value_t = 0)]
+    pub down_cant: usize,
+
+    /// Number of mismatch bonds placed in the chain
+    #[arg(long, default_value_t = 0)]
+    pub mismatch: usize,
+
+    /// Number of chains to draw from the sampler and count next to every configuration
+    #[arg(long)]
+    pub samples: Option<u64>,
+
+    /// Strategy that chooses the sites of the excited bonds. Its probability of every configuration is listed when it is
+    /// known, and the sampled chains are drawn with it
+    #[arg(long, value_enum, default_value_t = SitePlacerKind::KBeach)]
+    pub site_placer: SitePlacerKind,
+}
+
+#[derive(Debug, Args)]
+pub struct ExactArgs {
+    /// Smallest chain size to solve. Raised to the smallest chain that fits the excited bonds when it is too small
+    #[arg(long, default_value_t = 0)]
+    pub min_chain_size: usize,
+
+    /// Largest chain size to solve
+    #[arg(long)]
+    pub max_chain_size: usize,
+
+    /// Number of up-canted bonds placed in each chain
+    #[arg(long, default_value_t = 1)]
+    pub spin_sector: usize,
+
+    /// Number of down-canted bonds placed in each chain
+    #[arg(long, default_value_t = 0)]
+    pub down_cant: usize,
+
+    /// Number of mismatch bonds placed in each chain
+    #[arg(long, default_value_t = 0)]
+    pub mismatch: usize,
+
+    /// Strategy that chose the sites of the excited bonds of the initial chains, k-beach or uniform
+    #[arg(long, value_enum, default_value_t = SitePlacerKind::KBeach)]
+    pub site_placer: SitePlacerKind,
+
+    /// Run file of the same spin sector to compare the exact lifetimes against, made with the random-sequential update
+    /// scheme and the same site placer
+    #[arg(long)]
+    pub run_file: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct FitZArgs {
+    /// Run files produced by the simulate command, one spin sector each
+    #[arg(required = true)]
+    pub run_files: Vec<String>,
+
+    /// Smallest chain size included in the fit
+    #[arg(long, default_value_t = 0)]
+    pub min_chain_size: usize,
+
+    /// Largest chain size included in the fit [default: the largest one in the run file]
+    #[arg(long)]
+    pub max_chain_size: Option<usize>,
+
+    /// Also fit with a correction to scaling, tau = A N^z exp(b N^-omega), for this omega
+    #[arg(long)]
+    pub correction_exponent: Option<f64>,
+
+    /// Spin sector of run files older than the spin sector field of the run data
+    #[arg(long)]
+    pub spin_sector: Option<usize>,
+
+    /// File the z of every spin sector is stored in. Spin sectors already in it and not fitted again are kept
+    #[arg(long, default_value = "./data/z_data.txt")]
+    pub z_data_file: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CollapseArgs {
+    /// Run files produced by the simulate command, any number of spin sectors
+    #[arg(required = true)]
+    pub run_files: Vec<String>,
+
+    /// Smallest chain size included in the collapse
+    #[arg(long, default_value_t = 0)]
+    pub min_chain_size: usize,
+
+    /// Largest chain size included in the collapse [default: the largest one in the run files]
+    #[arg(long)]
+    pub max_chain_size: Option<usize>,
+
+    /// z the optimization starts from
+    #[arg(long, default_value_t = 3.0)]
+    pub initial_z: f64,
+
+    /// Also optimize the sector exponent kappa, starting from this value. Needs run files of two spin sectors or more
+    #[arg(long)]
+    pub sector_exponent: Option<f64>,
+
+    /// Survival curves are only compared where the survival is at least this fraction, the tails being noisy
+    #[arg(long, default_value_t = 0.05)]
+    pub min_survival: f64,
+
+    /// Survival curves are only compared where the survival is at most this fraction, below the first few deaths
+    #[arg(long, default_value_t = 0.95)]
+    pub max_survival: f64,
+
+    /// Largest number of points kept of every survival curve
+    #[arg(long, default_value_t = 50)]
+    pub points_per_curve: usize,
+
+    /// Also print the rescaled survival curves at the optimum
+    #[arg(long)]
+    pub curves: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PlotArgs {
+    /// Run files produced by the simulate command, one spin sector each
+    #[arg(required = true)]
+    pub run_files: Vec<String>,
+
+    /// Directory the svg files are written to, created when it does not exist
+    #[arg(long, default_value = "./data/plots")]
+    pub plot_directory: String,
+
+    /// Smallest chain size plotted and fitted
+    #[arg(long, default_value_t = 0)]
+    pub min_chain_size: usize,
+
+    /// Largest chain size plotted and fitted [default: the largest one in the run files]
+    #[arg(long)]
+    pub max_chain_size: Option<usize>,
+
+    /// Also plot the mean lifetime against spin sector for this chain size, over every run file that has it
+    #[arg(long)]
+    pub chain_size: Option<usize>,
+
+    /// Spin sector of run files older than the spin sector field of the run data
+    #[arg(long)]
+    pub spin_sector: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// Run files produced by the simulate command or merged before, all of the same spin sector
+    #[arg(required = true)]
+    pub run_files: Vec<String>,
+
+    /// File the merged run data is written to
+    #[arg(long)]
+    pub output: String,
+
+    /// Merge files whose config differs from the first file in a way that changes the lifetimes, or that repeat a
+    /// seed, instead of refusing them. The differences are recorded in the sources of the merged file
+    #[arg(long)]
+    pub allow_mismatch: bool,
+
+    /// Spin sector of run files older than the spin sector field of the run data
+    #[arg(long)]
+    pub spin_sector: Option<usize>,
+}
+
+impl SimulateArgs {
+    /// Builds the run configuration by loading the config file, if one was given, and applying the flags on top of it
+    pub fn to_run_config(&self) -> Result<RunConfig, String> {
+        let mut config = match &self.config {
+            Some(file_name) => config::load_config(file_name)?,
+            None => RunConfig::default(),
+        };
+
+        if let Some(storage_directory) = &self.storage_directory {
+            config.storage_directory = storage_directory.clone();
+        }
+        if let Some(trials) = self.trials {
+            config.trials = trials;
+        }
+        if let Some(min_chain_size) = self.min_chain_size {
+            config.min_chain_size = min_chain_size;
+        }
+        if let Some(max_chain_size) = self.max_chain_size {
+            config.max_chain_size = max_chain_size;
+        }
+        if let Some(chain_size_step) = self.chain_size_step {
+            config.chain_size_step = chain_size_step;
+        }
+        if let Some(min_spin_sector) = self.min_spin_sector {
+            config.min_spin_sector = min_spin_sector;
+            if self.max_spin_sector.is_none() && config.max_spin_sector < min_spin_sector {
+                config.max_spin_sector = min_spin_sector;
+            }
+        }
+        if let Some(max_spin_sector) = self.max_spin_sector {
+            config.max_spin_sector = max_spin_sector;
+        }
+        if let Some(down_cant) = self.down_cant {
+            config.excited_bond_map.down_cant = down_cant;
+        }
+        if let Some(mismatch) = self.mismatch {
+            config.excited_bond_map.mismatch = mismatch;
+        }
+        if let Some(site_placer) = self.site_placer {
+            config.site_placer = site_placer;
+        }
+        if let Some(dynamics) = self.dynamics {
+            config.dynamics = dynamics;
+        }
+        if let Some(update_scheme) = self.update_scheme {
+            config.update_scheme = update_scheme;
+        }
+        if let Some(max_steps) = self.max_steps {
+            config.max_steps = max_steps;
+        }
+        if let Some(time_unit) = self.time_unit {
+            config.continuous_time.time_unit = time_unit;
+        }
+        if let Some(coupling) = self.coupling {
+            config.continuous_time.coupling = coupling;
+        }
+        if let Some(lifetime_storage) = self.lifetime_storage {
+            config.lifetime_storage = lifetime_storage;
+        }
+        if let Some(snapshot) = self.trajectory {
+            config.trajectory.snapshot = snapshot;
+        }
+        if let Some(interval) = self.trajectory_interval {
+            config.trajectory.interval = interval;
+        }
+        if let Some(format) = self.trajectory_format {
+            config.trajectory.format = format;
+        }
+        if !self.observables.is_empty() {
+            config.observables.quantities = self.observables.clone();
+        }
+        if !self.observation_times.is_empty() {
+            config.observables.times = self.observation_times.clone();
+        }
+        if self.track_bond_identity {
+            config.track_bond_identity = true;
+        }
+        if let Some(checkpoint_interval) = self.checkpoint_interval {
+            config.checkpoint_interval = checkpoint_interval;
+        }
+        if let Some(threads) = self.threads {
+            config.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            config.rng.seed = Some(seed);
+        }
+        if let Some(rng) = self.rng {
+            config.rng.kind = rng;
+        }
+        if let Some(output_format) = self.output_format {
+            config.output_format = output_format;
+        }
+
+        Ok(config)
+    }
+
+    /// The flags given that change the run, every flag but --config, --storage-directory, --resume and --threads
+    fn run_changing_flags(&self) -> Vec<&'static str> {
+        let flags = [
+            ("--trials", self.trials.is_some()),
+            ("--min-chain-size", self.min_chain_size.is_some()),
+            ("--max-chain-size", self.max_chain_size.is_some()),
+            ("--chain-size-step", self.chain_size_step.is_some()),
+            ("--min-spin-sector", self.min_spin_sector.is_some()),
+            ("--max-spin-sector", self.max_spin_sector.is_some()),
+            ("--down-cant", self.down_cant.is_some()),
+            ("--mismatch", self.mismatch.is_some()),
+            ("--site-placer", self.site_placer.is_some()),
+            ("--dynamics", self.dynamics.is_some()),
+            ("--update-scheme", self.update_scheme.is_some()),
+            ("--max-steps", self.max_steps.is_some()),
+            ("--time-unit", self.time_unit.is_some()),
+            ("--coupling", self.coupling.is_some()),
+            ("--lifetime-storage", self.lifetime_storage.is_some()),
+            ("--trajectory", self.trajectory.is_some()),
+            ("--trajectory-interval", self.trajectory_interval.is_some()),
+            ("--trajectory-format", self.trajectory_format.is_some()),
+            ("--observables", !self.observables.is_empty()),
+            ("--observation-times", !self.observation_times.is_empty()),
+            ("--track-bond-identity", self.track_bond_identity),
+            ("--checkpoint-interval", self.checkpoint_interval.is_some()),
+            ("--seed", self.seed.is_some()),
+            ("--rng", self.rng.is_some()),
+            ("--output-format", self.output_format.is_some()),
+        ];
+        flags.into_iter().filter(|(_, given)| *given).map(|(flag, _)| flag).collect()
+    }
+}
+
+/// Parses the command line and checks the arguments clap cannot validate on its own.
+/// Exits with a usage error naming the offending argument when a value is invalid.
+pub fn parse() -> Cli {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Simulate(_) => {}
+        Command::Analyze(AnalyzeArgs { format, output, confidence_level, bins_per_decade, tail_fraction, .. }) => {
+            if *bins_per_decade == 0 {
+                exit_with_error("--bins-per-decade", "must be at least 1");
+            }
+            if !(*tail_fraction > 0.0 && *tail_fraction < 1.0) {
+                exit_with_error("--tail-fraction", &format!("({}) must be between 0 and 1", tail_fraction));
+            }
+            if !(*confidence_level > 0.0 && *confidence_level < 1.0) {
+                exit_with_error("--confidence-level", &format!("({}) must be between 0 and 1", confidence_level));
+            }
+            if output.is_some() && *format == EstimateFormat::Text {
+                exit_with_error("--output", "needs --format csv or json");
+            }
+        }
+        Command::Collapse(CollapseArgs { min_survival, max_survival, points_per_curve, .. }) => {
+            if !(0.0..1.0).contains(min_survival) {
+                exit_with_error("--min-survival", &format!("({}) must be between 0 and 1", min_survival));
+            }
+            if !(*max_survival > *min_survival && *max_survival <= 1.0) {
+                exit_with_error("--max-survival", &format!("({}) must be between --min-survival and 1", max_survival));
+            }
+            if *points_per_curve < 2 {
+                exit_with_error("--points-per-curve", "must be at least 2");
+            }
+        }
+        Command::Plot(_) | Command::Merge(_) => {}
+        Command::FitZ(FitZArgs { correction_exponent, .. }) => {
+            if correction_exponent.is_some_and(|omega| !(omega > 0.0 && omega.is_finite())) {
+                exit_with_error("--correction-exponent", &format!("({}) must be positive", correction_exponent.unwrap()));
+            }
+        }
+        Command::Inspect(InspectArgs { chain_size, spin_sector, down_cant, mismatch, .. }) => {
+            let excited_bonds = ExcitedBondConfig { up_cant: *spin_sector, down_cant: *down_cant, mismatch: *mismatch };
+            if *spin_sector == 0 && *down_cant == 0 && *mismatch == 0 {
+                exit_with_error("--spin-sector", "must be at least 1 when there are no down-canted or mismatch bonds since every excited chain needs an excited bond");
+            }
+            validate_chain_size(*chain_size, &excited_bonds);
+        }
+        Command::Enumerate(EnumerateArgs { chain_size, spin_sector, down_cant, mismatch, samples, .. }) => {
+            let excited_bonds = ExcitedBondConfig { up_cant: *spin_sector, down_cant: *down_cant, mismatch: *mismatch };
+            if *spin_sector == 0 && *down_cant == 0 && *mismatch == 0 {
+                if samples.is_some() {
+                    exit_with_error("--samples", "needs at least one excited bond since only excited chains can be sampled");
+                }
+                if !chain_size.is_multiple_of(2) {
+                    exit_with_error("--chain-size", &format!("({}) must be even", chain_size));
+                }
+            } else {
+                validate_chain_size(*chain_size, &excited_bonds);
+            }
+        }
+        Command::Exact(ExactArgs { min_chain_size, max_chain_size, spin_sector, down_cant, mismatch, .. }) => {
+            let excited_bonds = ExcitedBondConfig { up_cant: *spin_sector, down_cant: *down_cant, mismatch: *mismatch };
+            if *spin_sector == 0 && *down_cant == 0 && *mismatch == 0 {
+                exit_with_error("--spin-sector", "must be at least 1 when there are no down-canted or mismatch bonds since every excited chain needs an excited bond");
+            }
+            if !min_chain_size.is_multiple_of(2) {
+                exit_with_error("--min-chain-size", &format!("({}) must be even", min_chain_size));
+            }
+            if !max_chain_size.is_multiple_of(2) || *max_chain_size < excited_bonds.minimum_chain_size() {
+                exit_with_error("--max-chain-size", &format!("({}) must be even and at least {} to hold the excited bonds", max_chain_size, excited_bonds.minimum_chain_size()));
+            }
+        }
+    }
+
+    cli
+}
+
+/// Resolves the run configuration of the simulate command. Exits with a usage error naming the
+/// offending flag or config field when the config cannot be loaded or describes an invalid run.
+pub fn simulate_config(args: &SimulateArgs) -> RunConfig {
+    let config = match args.to_run_config() {
+        Ok(config) => config,
+        Err(message) => exit_with_error("--config", &message),
+    };
+
+    if let Err((field, message)) = config.validate() {
+        exit_with_error(&format!("--{}", field.replace('_', "-")), &message);
+    }
+
+    config
+}
+
+/// Loads the checkpoint the simulate command should resume from. The checkpoint is looked up in the
+/// storage directory given on the command line or in the config file. Exits with a usage error when
+/// there is no checkpoint to resume, or when a flag that changes the run is given since the run continues with the
+/// config saved in the checkpoint.
+pub fn resume_checkpoint(args: &SimulateArgs) -> Checkpoint {
+    if let Some(flag) = args.run_changing_flags().first() {
+        exit_with_error(flag, "cannot be changed when resuming, the run continues with the config saved in its checkpoint. Only --threads may be given with --resume");
+    }
+
+    let config = match args.to_run_config() {
+        Ok(config) => config,
+        Err(message) => exit_with_error("--config", &message),
+    };
+
+    let checkpoint_file = file_utils::checkpoint_file_name(&config.storage_directory);
+    let mut checkpoint = match file_utils::load_checkpoint(&checkpoint_file) {
+        Ok(checkpoint) => checkpoint,
+        Err(message) => exit_with_error("--resume", &message),
+    };
+
+    if let Some(threads) = args.threads {
+        checkpoint.config.threads = threads;
+    }
+
+    checkpoint
+}
+
+fn validate_chain_size(chain_size: usize, excited_bonds: &ExcitedBondConfig) {
+    if !chain_size.is_multiple_of(2) {
+        exit_with_error("--chain-size", &format!("({}) must be even", chain_size));
+    }
+    let smallest_chain_size = excited_bonds.minimum_chain_size();
+    if chain_size < smallest_chain_size {
+        exit_with_error("--chain-size", &format!("({}) must be at least {} to hold the excited bonds", chain_size, smallest_chain_size));
+    }
+}
+
+pub fn exit_with_error(argument: &str, message: &str) -> ! {
+    Cli::command()
+        .error(ErrorKind::ValueValidation, format!("invalid value for '{}': {}", argument, message))
+        .exit()
+}
@@ -0,0 +1,362 @@
+use std::{collections::HashMap, fs, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::observables::ObservableKind;
+use crate::site::BondKind;
+use crate::site_placer::SitePlacerKind;
+use crate::spin_chain;
+
+/// Everything needed to reproduce a simulation campaign. A run can be described entirely by a
+/// TOML or JSON file with these fields, and the config is echoed into every RunData file it produces.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    /// Directory the run_ss_*.json files are written to
+    pub storage_directory: String,
+    /// Number of trials run for every chain size
+    pub trials: u64,
+    /// Smallest chain size to simulate. Raised to the smallest chain that fits the excited bonds of a spin sector
+    pub min_chain_size: usize,
+    /// Largest chain size to simulate
+    pub max_chain_size: usize,
+    /// Amount the chain size grows by between simulated sizes
+    pub chain_size_step: usize,
+    /// First spin sector of the sweep. The spin sector is the number of up-canted bonds
+    pub min_spin_sector: usize,
+    /// Last spin sector of the sweep
+    pub max_spin_sector: usize,
+    /// The excited bonds placed in every chain. up_cant is replaced by the spin sector being simulated
+    pub excited_bond_map: ExcitedBondConfig,
+    /// The strategy that chooses the sites of the excited bonds in the initial chains
+    pub site_placer: SitePlacerKind,
+    /// How the chains are evolved until they die
+    pub dynamics: Dynamics,
+    /// The clock of the gillespie dynamics
+    pub continuous_time: ContinuousTimeConfig,
+    /// The order the random-index dynamics attempts the triples in
+    pub update_scheme: UpdateScheme,
+    /// Steps a trial may take before it is stopped and stored as a censored lifetime, moves for the gillespie dynamics.
+    /// 0 lets every trial run until it dies
+    pub max_steps: u64,
+    /// Whether the run files keep the lifetime of every trial or only running statistics of them
+    pub lifetime_storage: LifetimeStorage,
+    /// Snapshots of the chains taken while they evolve
+    pub trajectory: TrajectoryConfig,
+    /// Quantities measured at fixed times while the chains evolve and averaged over the trials
+    pub observables: ObservableConfig,
+    /// Re-label the excited sites after every step so the chain is always a valid decorated Dyck word.
+    /// Does not change the dynamics, only how the instantaneous state can be read
+    pub track_bond_identity: bool,
+    /// Number of trials between checkpoints within a chain size. 0 only checkpoints after each chain size
+    pub checkpoint_interval: u64,
+    /// Number of worker threads the trials of a chain size are spread over. 0 uses every available core
+    pub threads: usize,
+    pub rng: RngConfig,
+    pub output_format: OutputFormat,
+}
+
+/// Number of bonds of each excitation type. Maps onto the BondKind keys of excited_bond_map
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExcitedBondConfig {
+    pub up_cant: usize,
+    pub down_cant: usize,
+    pub mismatch: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RngConfig {
+    pub kind: RngKind,
+    /// Seed for the generator. A random seed is drawn when none is given
+    pub seed: Option<u64>,
+}
+
+/// The random number generators a run can be driven by
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RngKind {
+    /// 64 bit Mersenne Twister (Mt19937GenRand64)
+    #[default]
+    Mt64,
+    /// 32 bit Mersenne Twister (Mt19937GenRand32)
+    Mt,
+    /// The rand crate's StdRng
+    Std,
+}
+
+/// The engines that evolve a chain until it dies. The discrete ones count lifetimes in steps of the random-index dynamics
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Dynamics {
+    /// Pick a random triple every step and attempt its Fredkin move (evolve_chain)
+    #[default]
+    RandomIndex,
+    /// Rejection-free kinetic Monte Carlo, only ever picks triples whose move changes the chain and skips the idle steps in between
+    NFoldWay,
+    /// Continuous-time kinetic Monte Carlo, every triple fires at a Poisson rate and lifetimes are recorded as times
+    Gillespie,
+}
+
+/// The orders the random-index dynamics can attempt the triples of the chain in.
+/// Each attempted triple counts as one step, a sweep attempts every one of the chain_size - 2 triples once.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateScheme {
+    /// Every step picks a triple uniformly at random
+    #[default]
+    RandomSequential,
+    /// Sweeps through the triples from left to right
+    OrderedSweep,
+    /// Sweeps through the triples in a new random order every sweep
+    RandomPermutation,
+    /// Sweeps through the three sublattices of non-overlapping triples (i mod 3) one after the other, updating each in parallel
+    Checkerboard,
+}
+
+/// The time unit and rate of the continuous-time dynamics
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContinuousTimeConfig {
+    pub time_unit: TimeUnit,
+    /// The energy scale J of the Fredkin Hamiltonian H = J sum_i P_i, only used for physical time
+    pub coupling: f64,
+}
+
+/// The units continuous-time lifetimes are reported in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    /// Every triple fires at rate 1, so a unit of time is one sweep of chain_size - 2 attempted moves
+    #[default]
+    Sweeps,
+    /// Units of hbar/J. Every move fires at rate J/2, which makes the generator of the bulk moves equal to H
+    Physical,
+}
+
+/// When and how the chains are recorded while they evolve
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrajectoryConfig {
+    pub snapshot: SnapshotTrigger,
+    /// Number of steps or moves between snapshots
+    pub interval: u64,
+    pub format: TrajectoryFormat,
+}
+
+/// What a snapshot of the chain is taken after. The starting chain and the last chain before death are always recorded
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotTrigger {
+    /// No trajectories are recorded
+    #[default]
+    Off,
+    /// Every interval steps, idle steps included
+    Steps,
+    /// Every interval moves that changed the chain
+    Moves,
+}
+
+/// How trajectory files are written to disk
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TrajectoryFormat {
+    /// One JSON object per snapshot and line
+    #[default]
+    JsonLines,
+    /// One BSON document per snapshot, the chain as site codes
+    Bson,
+}
+
+/// The observables measured along every trajectory and the times they are measured at
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObservableConfig {
+    pub quantities: Vec<ObservableKind>,
+    /// Observation times in increasing order, in steps or in the time unit of the gillespie dynamics
+    pub times: Vec<f64>,
+}
+
+/// How the lifetimes of the trials are kept in RunData
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LifetimeStorage {
+    /// The lifetime of every trial, the run file grows with the number of trials
+    #[default]
+    Runs,
+    /// Mean, variance, extremes, a log-binned histogram and a quantile sketch of the lifetimes of every chain size.
+    /// The run file stays the same size however many trials run. Not available with max_steps, whose censored trials it would only count
+    Summary,
+}
+
+/// How RunData files are written to disk
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Compact JSON on a single line
+    #[default]
+    Json,
+    /// Indented, human readable JSON
+    JsonPretty,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            storage_directory: "./data/runs".to_string(),
+            trials: 1000,
+            min_chain_size: 0,
+            max_chain_size: 0,
+            chain_size_step: 2,
+            min_spin_sector: 1,
+            max_spin_sector: 1,
+            excited_bond_map: ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 },
+            site_placer: SitePlacerKind::default(),
+            dynamics: Dynamics::default(),
+            continuous_time: ContinuousTimeConfig::default(),
+            update_scheme: UpdateScheme::default(),
+            max_steps: 0,
+            lifetime_storage: LifetimeStorage::default(),
+            trajectory: TrajectoryConfig::default(),
+            observables: ObservableConfig::default(),
+            track_bond_identity: false,
+            checkpoint_interval: 0,
+            threads: 0,
+            rng: RngConfig::default(),
+            output_format: OutputFormat::default(),
+        }
+    }
+}
+
+impl Default for ContinuousTimeConfig {
+    fn default() -> Self {
+        ContinuousTimeConfig { time_unit: TimeUnit::default(), coupling: 1.0 }
+    }
+}
+
+impl Default for TrajectoryConfig {
+    fn default() -> Self {
+        TrajectoryConfig { snapshot: SnapshotTrigger::default(), interval: 1, format: TrajectoryFormat::default() }
+    }
+}
+
+impl RunConfig {
+    /// The step a trial is stopped at when it is still alive, None when trials run until they die
+    pub fn step_cap(&self) -> Option<u128> {
+        match self.max_steps {
+            0 => None,
+            max_steps => Some(max_steps as u128),
+        }
+    }
+}
+
+impl ContinuousTimeConfig {
+    /// The rate every triple attempts its Fredkin move at, in the configured time unit
+    pub fn move_rate(&self) -> f64 {
+        match self.time_unit {
+            TimeUnit::Sweeps => 1.0,
+            TimeUnit::Physical => self.coupling / 2.0,
+        }
+    }
+}
+
+impl ExcitedBondConfig {
+    /// The bonds of a chain in the given spin sector, keeping the down-canted and mismatch counts
+    pub fn with_spin_sector(self, spin_sector: usize) -> Self {
+        ExcitedBondConfig { up_cant: spin_sector, ..self }
+    }
+
+    /// The smallest chain that can hold all of the excited bonds. Every bond occupies 2 sites
+    /// and the sites reserved at the edges of the chain are never excited.
+    pub fn minimum_chain_size(&self) -> usize {
+        let (left_reserved, right_reserved) = spin_chain::reserved_edge_sites(self.up_cant, self.down_cant, self.mismatch);
+        (2 * (self.up_cant + self.down_cant + self.mismatch)) + left_reserved + right_reserved
+    }
+
+    /// Converts the bond counts into the excited_bond_map expected by SpinChain::new_excited
+    pub fn to_map(self) -> HashMap<BondKind, usize> {
+        let mut excited_bond_map:HashMap<BondKind, usize> = HashMap::<BondKind, usize>::new();
+        excited_bond_map.insert(BondKind::UpCant, self.up_cant);
+        excited_bond_map.insert(BondKind::DownCant, self.down_cant);
+        excited_bond_map.insert(BondKind::Mismatch, self.mismatch);
+        excited_bond_map
+    }
+}
+
+
+impl RunConfig {
+    /// Checks that the config describes a sweep that can be run.
+    /// On failure returns the name of the offending field along with the reason it is invalid.
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.trials == 0 {
+            return Err(("trials", "must be at least 1".to_string()));
+        }
+        if self.min_spin_sector == 0 && self.excited_bond_map.down_cant == 0 && self.excited_bond_map.mismatch == 0 {
+            return Err(("min_spin_sector", "must be at least 1 when there are no down-canted or mismatch bonds since every excited chain needs an excited bond".to_string()));
+        }
+        if self.max_spin_sector < self.min_spin_sector {
+            return Err(("max_spin_sector", format!("({}) must not be smaller than min_spin_sector ({})", self.max_spin_sector, self.min_spin_sector)));
+        }
+        if !self.min_chain_size.is_multiple_of(2) {
+            return Err(("min_chain_size", format!("({}) must be even", self.min_chain_size)));
+        }
+        if self.chain_size_step == 0 || !self.chain_size_step.is_multiple_of(2) {
+            return Err(("chain_size_step", format!("({}) must be a positive even number", self.chain_size_step)));
+        }
+        if self.max_chain_size == 0 {
+            return Err(("max_chain_size", "is required".to_string()));
+        }
+        if !(self.continuous_time.coupling > 0.0 && self.continuous_time.coupling.is_finite()) {
+            return Err(("coupling", format!("({}) must be a positive number", self.continuous_time.coupling)));
+        }
+        if self.update_scheme != UpdateScheme::RandomSequential && self.dynamics != Dynamics::RandomIndex {
+            return Err(("update_scheme", format!("{:?} needs the random_index dynamics, the others always update random-sequentially", self.update_scheme)));
+        }
+        if self.trajectory.interval == 0 {
+            return Err(("trajectory_interval", "must be at least 1".to_string()));
+        }
+        if self.trajectory.snapshot == SnapshotTrigger::Steps && self.dynamics == Dynamics::Gillespie {
+            return Err(("trajectory", "the gillespie dynamics has no steps, take snapshots every few moves instead".to_string()));
+        }
+        if !self.observables.quantities.is_empty() && self.observables.times.is_empty() {
+            return Err(("observation_times", "are required to measure observables".to_string()));
+        }
+        if self.observables.times.iter().any(|time| !(*time >= 0.0 && time.is_finite())) || self.observables.times.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(("observation_times", format!("({:?}) must be non-negative and strictly increasing", self.observables.times)));
+        }
+        if self.lifetime_storage == LifetimeStorage::Summary && self.max_steps > 0 {
+            return Err(("lifetime_storage", format!("summary keeps no lifetimes for the trials cut short by max_steps ({}), so its mean lifetime would only be a lower bound. Store the runs instead", self.max_steps)));
+        }
+        if let Some(observable) = self.observables.quantities.iter().find(|observable| observable.reads_bond_labels()).filter(|_| !self.track_bond_identity) {
+            return Err(("observables", format!("{:?} needs track_bond_identity, without it the excited bond labels are left behind on the sites the moves swap them onto", observable)));
+        }
+        let smallest_chain_size = self.excited_bond_map.with_spin_sector(self.max_spin_sector).minimum_chain_size();
+        if self.max_chain_size < smallest_chain_size {
+            return Err(("max_chain_size", format!("({}) must be at least {} to hold the excited bonds of spin sector {}", self.max_chain_size, smallest_chain_size, self.max_spin_sector)));
+        }
+        Ok(())
+    }
+}
+
+impl RunConfig {
+    /// The number of worker threads to run trials on, resolving 0 to the number of available cores
+    pub fn worker_threads(&self) -> usize {
+        if self.threads == 0 {
+            thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1)
+        } else {
+            self.threads
+        }
+    }
+}
+
+/// Loads a run configuration from a .toml or .json file. Fields missing from the file keep their default values.
+pub fn load_config(file_name: &str) -> Result<RunConfig, String> {
+    let contents = fs::read_to_string(file_name).map_err(|error| format!("could not read {}: {}", file_name, error))?;
+
+    if file_name.ends_with(".json") {
+        serde_json::from_str(&contents).map_err(|error| format!("could not parse {}: {}", file_name, error))
+    } else {
+        toml::from_str(&contents).map_err(|error| format!("could not parse {}: {}", file_name, error))
+    }
+}
@@ -0,0 +1,220 @@
+use crate::file_utils::RunData;
+use crate::lifetime_distribution;
+use crate::survival::KaplanMeier;
+
+/// Largest number of Nelder-Mead iterations of a collapse
+const MAXIMUM_ITERATIONS: usize = 1000;
+/// The simplex is considered converged once every vertex is this close to the best one, in the exponents and the quality
+const CONVERGENCE_TOLERANCE: f64 = 1e-8;
+/// Exponents further than this from the optimum are not searched for the error width of the collapse
+const MAXIMUM_ERROR_WIDTH: f64 = 10.0;
+
+/// The survival curve of the lifetimes of one chain size and spin sector, sorted by lifetime. Lifetimes are in sweeps
+/// of chain_size - 2 steps, or in the time unit of the gillespie dynamics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurvivalCurve {
+    pub spin_sector: usize,
+    pub chain_size: usize,
+    pub log_lifetimes: Vec<f64>,
+    pub survival: Vec<f64>,
+    pub standard_errors: Vec<f64>,
+}
+
+/// The exponents that best collapse the survival curves onto a single function of t / (N^z (S/N)^kappa)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collapse {
+    pub z: f64,
+    // Distance from z the quality rises by 1 at, the other exponent held at its optimum. None when it never does
+    pub z_error: Option<f64>,
+    // kappa, None when only z was optimized
+    pub sector_exponent: Option<f64>,
+    pub sector_exponent_error: Option<f64>,
+    pub quality: f64,
+    // Number of curve points that overlap with another curve at the optimum, the terms of the quality
+    pub overlapping_points: usize,
+}
+
+/// The Kaplan-Meier survival curve of a chain size of a run file, between the given survival fractions and thinned to
+/// at most max_points points evenly spread in survival. Summaries use the survival at the bin edges of their histogram,
+/// with binomial standard errors. None when fewer than two points are left.
+pub fn survival_curve(run_data: &RunData, spin_sector: usize, chain_size: usize, min_survival: f64, max_survival: f64, max_points: usize) -> Option<SurvivalCurve> {
+    let lifetimes_per_sweep = if run_data.is_continuous_time() { 1.0 } else { (chain_size - 2) as f64 };
+    let points: Vec<(f64, f64, f64)> = match run_data.summaries.get(&chain_size) {
+        Some(summary) => {
+            let trials = (summary.lifetimes().count + summary.censored_trials) as f64;
+            lifetime_distribution::summary_distribution(summary, !run_data.is_continuous_time()).bins.iter()
+                .map(|bin| (bin.upper, bin.survival, (bin.survival * (1.0 - bin.survival) / trials).sqrt()))
+                .collect()
+        }
+        None => {
+            let (lifetimes, censored_lifetimes) = run_data.lifetime_samples(chain_size);
+            KaplanMeier::new(&lifetimes, &censored_lifetimes).points.iter()
+                .map(|point| (point.time, point.survival, point.standard_error))
+                .collect()
+        }
+    };
+    let points: Vec<(f64, f64, f64)> = points.into_iter()
+        .filter(|(lifetime, survival, standard_error)| *lifetime > 0.0 && (min_survival..=max_survival).contains(survival) && *standard_error > 0.0)
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let stride = points.len().div_ceil(max_points.max(1));
+    let points: Vec<&(f64, f64, f64)> = points.iter().step_by(stride).collect();
+    Some(SurvivalCurve {
+        spin_sector,
+        chain_size,
+        log_lifetimes: points.iter().map(|(lifetime, _, _)| (lifetime / lifetimes_per_sweep).ln()).collect(),
+        survival: points.iter().map(|(_, survival, _)| *survival).collect(),
+        standard_errors: points.iter().map(|(_, _, standard_error)| *standard_error).collect(),
+    })
+}
+
+/// ln(t / (N^z (S/N)^kappa)) at every point of the curve. The sector ratio is left out for kappa = 0, so spin sector 0
+/// can be collapsed in z alone.
+pub fn rescaled_log_lifetimes(curve: &SurvivalCurve, z: f64, sector_exponent: f64) -> Vec<f64> {
+    let log_chain_size = (curve.chain_size as f64).ln();
+    let mut shift = z * log_chain_size;
+    if sector_exponent != 0.0 {
+        shift += sector_exponent * ((curve.spin_sector as f64).ln() - log_chain_size);
+    }
+    curve.log_lifetimes.iter().map(|log_lifetime| log_lifetime - shift).collect()
+}
+
+/// The quality of the collapse of Houdayer and Hartmann, Phys. Rev. B 70, 014418 (2004), along with the number of
+/// points it averages over. Every point is compared with the weighted mean of the other curves interpolated at its
+/// rescaled lifetime, in units of their combined standard error, so a perfect collapse has a quality of about 1.
+/// Infinite when no two curves overlap.
+pub fn collapse_quality(curves: &[SurvivalCurve], z: f64, sector_exponent: f64) -> (f64, usize) {
+    let rescaled: Vec<Vec<f64>> = curves.iter().map(|curve| rescaled_log_lifetimes(curve, z, sector_exponent)).collect();
+    let mut sum = 0.0;
+    let mut overlapping_points = 0;
+    for (curve_index, curve) in curves.iter().enumerate() {
+        for (point, log_lifetime) in rescaled[curve_index].iter().enumerate() {
+            let mut weight_sum = 0.0;
+            let mut weighted_survival = 0.0;
+            for (other_index, other_curve) in curves.iter().enumerate() {
+                let other_log_lifetimes = &rescaled[other_index];
+                if other_index == curve_index || *log_lifetime < other_log_lifetimes[0] || *log_lifetime > *other_log_lifetimes.last().unwrap() {
+                    continue;
+                }
+                let upper = other_log_lifetimes.partition_point(|other| other < log_lifetime).clamp(1, other_log_lifetimes.len() - 1);
+                let fraction = (log_lifetime - other_log_lifetimes[upper - 1]) / (other_log_lifetimes[upper] - other_log_lifetimes[upper - 1]);
+                let interpolate = |values: &[f64]| values[upper - 1] + fraction * (values[upper] - values[upper - 1]);
+                let weight = interpolate(&other_curve.standard_errors).powi(-2);
+                weight_sum += weight;
+                weighted_survival += weight * interpolate(&other_curve.survival);
+            }
+            if weight_sum == 0.0 {
+                continue;
+            }
+            let master_survival = weighted_survival / weight_sum;
+            sum += (curve.survival[point] - master_survival).powi(2) / (curve.standard_errors[point].powi(2) + 1.0 / weight_sum);
+            overlapping_points += 1;
+        }
+    }
+    if overlapping_points == 0 {
+        return (f64::INFINITY, 0);
+    }
+    (sum / overlapping_points as f64, overlapping_points)
+}
+
+/// Minimizes the collapse quality over z, and over kappa too when an initial sector exponent is given, with the
+/// Nelder-Mead simplex method started from the initial exponents. The errors are the distances from the optimum at
+/// which the quality has risen by 1, the convention of Houdayer and Hartmann.
+pub fn optimize_collapse(curves: &[SurvivalCurve], initial_z: f64, initial_sector_exponent: Option<f64>) -> Collapse {
+    let quality = |exponents: &[f64]| collapse_quality(curves, exponents[0], exponents.get(1).copied().unwrap_or(0.0)).0;
+    let mut initial_exponents = vec![initial_z];
+    initial_exponents.extend(initial_sector_exponent);
+    let optimum = nelder_mead(&quality, initial_exponents);
+
+    let (best_quality, overlapping_points) = collapse_quality(curves, optimum[0], optimum.get(1).copied().unwrap_or(0.0));
+    Collapse {
+        z: optimum[0],
+        z_error: error_width(&quality, &optimum, 0, best_quality),
+        sector_exponent: optimum.get(1).copied(),
+        sector_exponent_error: (optimum.len() > 1).then(|| error_width(&quality, &optimum, 1, best_quality)).flatten(),
+        quality: best_quality,
+        overlapping_points,
+    }
+}
+
+/// Minimizes the function with the Nelder-Mead simplex method, starting from a simplex of steps of 0.1 around the
+/// initial point
+fn nelder_mead<F: Fn(&[f64]) -> f64>(function: &F, initial_point: Vec<f64>) -> Vec<f64> {
+    let dimension = initial_point.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=dimension)
+        .map(|vertex| {
+            let mut point = initial_point.clone();
+            if vertex > 0 {
+                point[vertex - 1] += 0.1;
+            }
+            let value = function(&point);
+            (point, value)
+        })
+        .collect();
+    let combine = |a: &[f64], b: &[f64], coefficient: f64| -> Vec<f64> { a.iter().zip(b).map(|(a, b)| a + coefficient * (b - a)).collect() };
+
+    for _ in 0..MAXIMUM_ITERATIONS {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, worst) = (&simplex[0], &simplex[dimension]);
+        let spread = simplex.iter().map(|(point, _)| point.iter().zip(&best.0).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max)).fold(0.0, f64::max);
+        if spread < CONVERGENCE_TOLERANCE && (worst.1 - best.1).abs() < CONVERGENCE_TOLERANCE {
+            break;
+        }
+
+        let centroid: Vec<f64> = (0..dimension).map(|axis| simplex[..dimension].iter().map(|(point, _)| point[axis]).sum::<f64>() / dimension as f64).collect();
+        let reflected = combine(&centroid, &simplex[dimension].0, -1.0);
+        let reflected_value = function(&reflected);
+        if reflected_value < simplex[0].1 {
+            let expanded = combine(&centroid, &simplex[dimension].0, -2.0);
+            let expanded_value = function(&expanded);
+            simplex[dimension] = if expanded_value < reflected_value { (expanded, expanded_value) } else { (reflected, reflected_value) };
+        } else if reflected_value < simplex[dimension - 1].1 {
+            simplex[dimension] = (reflected, reflected_value);
+        } else {
+            let contracted = combine(&centroid, &simplex[dimension].0, 0.5);
+            let contracted_value = function(&contracted);
+            if contracted_value < simplex[dimension].1 {
+                simplex[dimension] = (contracted, contracted_value);
+            } else {
+                // shrink towards the best vertex
+                let best_point = simplex[0].0.clone();
+                for (point, value) in simplex.iter_mut().skip(1) {
+                    *point = combine(&best_point, point, 0.5);
+                    *value = function(point);
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    simplex.swap_remove(0).0
+}
+
+/// The mean distance along one exponent from the optimum to where the function reaches its minimum plus 1, on both
+/// sides. None when one side stays below that within MAXIMUM_ERROR_WIDTH.
+fn error_width<F: Fn(&[f64]) -> f64>(function: &F, optimum: &[f64], axis: usize, minimum: f64) -> Option<f64> {
+    let value_at = |offset: f64| {
+        let mut point = optimum.to_vec();
+        point[axis] += offset;
+        function(&point)
+    };
+    let mut widths = Vec::new();
+    for direction in [-1.0, 1.0] {
+        let mut inside = 0.0;
+        let mut outside = 0.01;
+        while value_at(direction * outside) < minimum + 1.0 {
+            inside = outside;
+            outside *= 2.0;
+            if outside > MAXIMUM_ERROR_WIDTH {
+                return None;
+            }
+        }
+        for _ in 0..50 {
+            let middle = (inside + outside) / 2.0;
+            if value_at(direction * middle) < minimum + 1.0 { inside = middle } else { outside = middle }
+        }
+        widths.push((inside + outside) / 2.0);
+    }
+    Some(widths.iter().sum::<f64>() / 2.0)
+}
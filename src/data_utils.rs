@@ -1,271 +1,347 @@
-// use std::collections::{BTreeMap, HashMap};
-
-// use plotlib::{page::Page, repr::Plot, style::{PointMarker, PointStyle}, view::ContinuousView};
-// use plotters::{chart::{ChartBuilder, LabelAreaPosition}, prelude::{BitMapBackend, Circle, IntoDrawingArea}, series::LineSeries, style::{BLUE, RED, WHITE}};
-
-// use crate::file_utils::{self, Run, RunData, ZData};
-
-// pub fn generate_lifetime_plot_single_ss(runs_map: &BTreeMap<usize, Vec<Run>>, spin_sector: &usize) {
-
-//     let mut data: ZData = file_utils::load_data("./data/z_data.txt".to_string());
-
-//     println!("generating plot");
-//     let mut plot_data: Vec<(f64, f64)> = Vec::new();
-
-//     for chain_size in runs_map.keys() {
-//         let mut step_total: u128 = 0;
-//         let runs = runs_map.get(chain_size).unwrap();
-//         let total_entries = runs.len() as f64;
-//         for run in runs {
-//             step_total += run.step_count;
-//         }
-//         let  step_total_conversion= step_total as f64;
-//         let average_step_count = step_total_conversion/total_entries;
-//         let chain_size_conversion = *chain_size as f64;
-//         plot_data.push((chain_size_conversion, average_step_count));
-//       }
-//     //   let plot: Plot = Plot::new(plot_data).point_style(PointStyle::new().marker(PointMarker::Square).colour("#DD3355"));
-
-//     //   let v = ContinuousView::new().add(plot).x_range(0.0, 32.0).y_range(0.0, 10000.0);
-//     //   Page::single(&v).save("chains.svg").unwrap();
-
-//     let lifetime_file_path = format!("data/plots/lifetime_{}.png", spin_sector);
-
-//     let lifetime_drawing = BitMapBackend::new(lifetime_file_path.as_str(), (1920, 1080))
-//         .into_drawing_area();
-//     lifetime_drawing.fill(&WHITE).unwrap();
-
-//     let final_entry = plot_data.last().unwrap();
-//     let max_x = final_entry.0 + 2.0;
-//     let max_y = final_entry.1 + 2.0;
-
-
-//     let mut lifetime_ctx = ChartBuilder::on(&lifetime_drawing)
-//         .set_label_area_size(LabelAreaPosition::Left, 60)
-//         .set_label_area_size(LabelAreaPosition::Bottom, 60)
-//         .caption("Fredkin Chain Lifetimes", ("sans-serif", 40))
-//         .build_cartesian_2d(0.0..max_x, 0f64..max_y)
-//         .unwrap();
-
-//     lifetime_ctx.configure_mesh().draw().unwrap();
-
-//     lifetime_ctx.draw_series(plot_data.iter().map(|point| Circle::new(*point, 5, &BLUE))).unwrap();
-
-//     let lifetime_log_file_path = format!("data/plots/lifetime_log_{}.png", spin_sector);
-
-//     let log_lifetime_drawing = BitMapBackend::new(lifetime_log_file_path.as_str(), (1920, 1080))
-//     .into_drawing_area();
-//     log_lifetime_drawing.fill(&WHITE).unwrap();
-
-//     let max_x_log = max_x.ln() + 1.0;
-//     let max_y_log = max_y.ln() + 1.0;
-
-//     let mut lifetime_log_ctx = ChartBuilder::on(&log_lifetime_drawing)
-//         .set_label_area_size(LabelAreaPosition::Left, 60)
-//         .set_label_area_size(LabelAreaPosition::Bottom, 60)
-//         .caption("Fredkin Chain Lifetimes", ("sans-serif", 40))
-//         .build_cartesian_2d(0.9f64..max_x_log, 1.5f64..max_y_log)
-//         .unwrap();
-
-//     lifetime_log_ctx.configure_mesh().draw().unwrap();
-
-//     lifetime_log_ctx.draw_series(plot_data.iter().map(|point| Circle::new((point.0.ln(), point.1.ln()), 5, &BLUE))).unwrap();
-
-//     // for point in &plot_data {
-//     //     let x = point.0;
-//     //     let y = point.1;
-//     //     println!("({x}, {y})");
-//     // }
-
-//     let mut x_2 = plot_data.get(0).unwrap().0.ln();
-//     let mut y_2 = plot_data.get(0).unwrap().1.ln();
-//     let mut x_1 = plot_data.get(0).unwrap().0.ln();
-//     let mut y_1 = plot_data.get(0).unwrap().1.ln();
-//     for point in &plot_data {
-//         let x = point.0.ln();
-//         let y = point.1.ln();
-//         if x > x_2 {
-//             x_2 = x;
-//             y_2 = y;
-//         }
-//         else if x < x_1 {
-//             x_1 = x;
-//             y_1 = y;
-
-//         }
-//         // println!("({x}, {y})");
-//     }
-
-//     println!("x2: {x_2}, y2: {y_2} and x1: {x_1}, y1: {y_1}");
-//     let delta_x = x_2 - x_1;
-//     let delta_y = y_2 - y_1;
-//     let z = delta_y/delta_x - 1.0;
-
-//     println!("z: {z}");
-
-//     println!("Average");
-
-//     let mut sum_slope = 0.0;
-
-//     for i in 0..plot_data.len() - 1 {
-//         let x_2 = plot_data.get_mut(i + 1).unwrap().0;
-//         let x_1 = plot_data.get_mut(i).unwrap().0;
-
-//         let y_2 = plot_data.get_mut(i + 1).unwrap().1;
-//         let y_1 = plot_data.get_mut(i).unwrap().1;
-
-//         let delta_x = x_2.ln() - x_1.ln();
-//         let delta_y = y_2.ln() - y_1.ln();
-
-//         sum_slope += delta_y/delta_x;
-//     } 
-
-//     let average_z = sum_slope/(plot_data.len() as f64 - 1.0) - 1.0;
-
-//     println!("average z: {average_z}");
-
-//     // let mut z_data = HashMap::new();
-//     let mut zs = Vec::new();
-//     zs.push(z);
-//     zs.push(average_z);
-//     // z_data.insert(*spin_sector, zs);
-
-//     data.z_data.insert(*spin_sector, zs);
-//     // let data = ZData{z_data};
-
-//     file_utils::save_data("./data/z_data.txt".to_string(), &data);
-
-//     // ctx.draw_series(
-//     //     plot_data.iter().map(|point| Circle::new(*point, 5, &BLUE))
-//     // ).unwrap();
-
-
-//     // root_drawing_area.fill(&WHITE).unwrap();
-
-//     // let mut chart = ChartBuilder::on(&root_drawing_area)
-//     //     .build_cartesian_2d(-3.14..3.14, -1.2..1.2)
-//     //     .unwrap();
-
-//     // chart.draw_series(LineSeries::new(
-//     //     (-314..314).map(|x| x as f64 / 100.0).map(|x| (x, x.sin())),
-//     //     &RED
-//     // )).unwrap();
-
-// }
-
-// pub fn generate_lifetime_plot_mutliple_ss_single_chainsize_log(min_spin_sector: usize, max_spin_sector: usize, chain_size:usize) {
-
-//     let mut average_vec: Vec<(f64,f64)> = Vec::new();
-
-//     let mut max_y: f64 = 0.0;
-
-//     for i in min_spin_sector..=max_spin_sector {
-//         let file_name = format!("./data/runs/run_ss_{}.json", i);
-//         let run_data:RunData = file_utils::load_data(file_name);
-
-//         let run = run_data.runs.get(&chain_size).unwrap();
-
-//         let mut sum = 0;
-//         let mut total_number_of_runs:u128 = 0;
-//         for step_count in run {
-
-//             sum+=step_count;
-//             total_number_of_runs+=1;
-
-//         }
-//         let sum_conversion = sum as f64;
-//         let total_number_of_runs_conversion = total_number_of_runs as f64;
-//         let average = sum_conversion/total_number_of_runs_conversion;
-//         if average > max_y {
-//             max_y = average;
-//         }
-
-//         println!("average for ss {}: {}", i, average);
-//         average_vec.push((i as f64,average));
-//     }
-
-//     let spin_sector_lifetimes = format!("data/plots/ss_{}_cs_{}_log.png", max_spin_sector, chain_size);
-    
-//     let spin_sector_lifetimes_drawing = BitMapBackend::new(spin_sector_lifetimes.as_str(), (1920, 1080))
-//     .into_drawing_area();
-//     spin_sector_lifetimes_drawing.fill(&WHITE).unwrap();
-
-//     // let max_x_log = max_x.ln() + 1.0;
-//     // max_y  = max_y + 1000.0;
-//     let max_y_log = max_y.ln() + 1.0;
-//     let max_x_log = (max_spin_sector as f64).ln() + 1.0;
-
-//     let mut spin_sector_lifetime_ctx = ChartBuilder::on(&spin_sector_lifetimes_drawing)
-//         .set_label_area_size(LabelAreaPosition::Left, 60)
-//         .set_label_area_size(LabelAreaPosition::Bottom, 60)
-//         .caption("Fredkin Chain Lifetimes", ("sans-serif", 40))
-//         .build_cartesian_2d(0.0f64..max_x_log as f64 + 1.0, 0.0f64..max_y_log)
-//         .unwrap();
-
-//         spin_sector_lifetime_ctx.configure_mesh().draw().unwrap();
-
-//         spin_sector_lifetime_ctx.draw_series(average_vec.iter().map(|point| Circle::new((point.0.ln(), point.1.ln()), 5, &BLUE))).unwrap();
-
-
-// }
-
-// pub fn generate_lifetime_plot_mutliple_ss_single_chainsize(min_spin_sector: usize, max_spin_sector: usize, chain_size:usize) {
-
-//     let mut average_vec: Vec<(f64,f64)> = Vec::new();
-
-//     let mut max_y: f64 = 0.0;
-//     let mut min_y: f64 = 0.0;
-
-//     for i in min_spin_sector..=max_spin_sector {
-//         let file_name = format!("./data/runs/run_ss_{}.json", i);
-//         let run_data:RunData = file_utils::load_data(file_name);
-
-//         let run = run_data.runs.get(&chain_size).unwrap();
-
-//         let mut sum = 0;
-//         let mut total_number_of_runs:u128 = 0;
-//         for step_count in run {
-
-//             sum+=step_count;
-//             total_number_of_runs+=1;
-
-//         }
-//         let sum_conversion = sum as f64;
-//         let total_number_of_runs_conversion = total_number_of_runs as f64;
-//         let average = sum_conversion/total_number_of_runs_conversion;
-//         if i == min_spin_sector {
-//             min_y = average;
-//         } else if average < min_y {
-//             min_y = average;
-//         } 
-        
-//         if average > max_y {
-//             max_y = average;
-//         }
-
-//         println!("average for ss {}: {}", i, average);
-//         average_vec.push((i as f64,average));
-//     }
-
-//     let spin_sector_lifetimes = format!("data/plots/ss_{}_cs_{}.png", max_spin_sector, chain_size);
-    
-//     let spin_sector_lifetimes_drawing = BitMapBackend::new(spin_sector_lifetimes.as_str(), (1920, 1080))
-//     .into_drawing_area();
-//     spin_sector_lifetimes_drawing.fill(&WHITE).unwrap();
-
-//     let max_x = max_spin_sector as f64 + 1.0;
-//     let min_x = min_spin_sector as f64;
-
-//     let mut spin_sector_lifetime_ctx = ChartBuilder::on(&spin_sector_lifetimes_drawing)
-//         .set_label_area_size(LabelAreaPosition::Left, 60)
-//         .set_label_area_size(LabelAreaPosition::Bottom, 60)
-//         .caption("Fredkin Chain Lifetimes", ("sans-serif", 40))
-//         .build_cartesian_2d(min_x..max_x, 950f64..max_y)
-//         .unwrap();
-
-//         spin_sector_lifetime_ctx.configure_mesh().draw().unwrap();
-
-//         spin_sector_lifetime_ctx.draw_series(average_vec.iter().map(|point| Circle::new((point.0, point.1), 5, &BLUE))).unwrap();
-
-
-// }
\ No newline at end of file
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use rand_mt::Mt64;
+
+use crate::cli::{self, AnalyzeArgs, CollapseArgs, FitZArgs, MergeArgs, PlotArgs};
+use crate::data_collapse::{self, SurvivalCurve};
+use crate::file_utils::{self, RunData, ZData};
+use crate::lifetime_distribution::{self, LifetimeDistribution};
+use crate::lifetime_estimates::{self, EstimateFormat, LifetimeEstimate};
+use crate::run_merge;
+use crate::scaling_fit::{self, ScalingPoint, ZFit};
+use crate::survival::KaplanMeier;
+use crate::svg_plot::{Plot, Scale, Series};
+
+/// Estimates the mean lifetime of every chain size stored in the given run files, with bootstrap confidence intervals.
+/// The text format prints them along with the rest of the summary, csv and json only write the estimates.
+/// Runs with censored trials get the Kaplan-Meier estimate of the mean lifetime, which accounts for them.
+pub fn print_lifetime_summary(args: &AnalyzeArgs) {
+    let mut rng = Mt64::new(args.bootstrap_seed);
+    let mut all_estimates: Vec<LifetimeEstimate> = Vec::new();
+    for run_file in &args.run_files {
+        let run_data: RunData = file_utils::load_data(run_file.clone());
+        let estimates = lifetime_estimates::estimate_lifetimes(run_file, &run_data, args.bootstrap_samples, args.confidence_level, &mut rng);
+        if args.format != EstimateFormat::Text {
+            all_estimates.extend(estimates);
+            continue;
+        }
+        println!("{run_file}");
+        if let Some(config) = &run_data.config {
+            println!("dynamics: {:?}, update scheme: {:?}", config.dynamics, config.update_scheme);
+        }
+        for (chain_size, immortal_trials) in &run_data.immortal_trials {
+            println!("chain size {chain_size}: {immortal_trials} trials never die and are left out");
+        }
+        for (chain_size, censored_runs) in &run_data.censored_runs {
+            println!("chain size {chain_size}: {} trials reached the step cap and are kept as censored lifetimes", censored_runs.len());
+        }
+        if args.observables {
+            print_observable_curves(&run_data);
+        }
+        if !run_data.summaries.is_empty() {
+            if args.survival {
+                println!("no survival curves, the run only kept running statistics of the lifetimes");
+            }
+            print_lifetime_statistics(&run_data);
+        } else if !run_data.censored_runs.is_empty() || args.survival {
+            print_kaplan_meier_estimates(&run_data, args.survival);
+        }
+        if args.distribution {
+            print_lifetime_distributions(&run_data, args.bins_per_decade, args.tail_fraction);
+        }
+        if let Some(estimate) = estimates.first() {
+            println!("lifetimes in {}, {} confidence intervals from {:?}", estimate.unit, args.confidence_level, estimate.interval_method);
+        }
+        println!("chain size, trials, censored, mean lifetime, standard error, median, mean lower, mean upper, log mean, log mean standard error, log mean lower, log mean upper");
+        for estimate in &estimates {
+            println!("{}", estimate_row(estimate));
+        }
+    }
+
+    let contents = match args.format {
+        EstimateFormat::Text => return,
+        EstimateFormat::Csv => {
+            let mut contents = "run file, spin sector, unit, interval method, chain size, trials, censored, mean lifetime, standard error, median, mean lower, mean upper, log mean, log mean standard error, log mean lower, log mean upper\n".to_string();
+            for estimate in &all_estimates {
+                let spin_sector = estimate.spin_sector.map_or(String::new(), |spin_sector| spin_sector.to_string());
+                contents += &format!("{}, {}, {}, {:?}, {}\n", estimate.run_file, spin_sector, estimate.unit, estimate.interval_method, estimate_row(estimate));
+            }
+            contents
+        }
+        EstimateFormat::Json => serde_json::to_string_pretty(&all_estimates).unwrap() + "\n",
+    };
+    match &args.output {
+        Some(output) => fs::write(output, contents).unwrap(),
+        None => print!("{contents}"),
+    }
+}
+
+/// The numbers of an estimate from the chain size on, comma separated
+fn estimate_row(estimate: &LifetimeEstimate) -> String {
+    let median = estimate.median.map_or("none".to_string(), |median| median.to_string());
+    format!("{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}", estimate.chain_size, estimate.trials, estimate.censored, estimate.mean, estimate.standard_error,
+        median, estimate.mean_interval.0, estimate.mean_interval.1, estimate.log_mean, estimate.log_mean_standard_error, estimate.log_mean_interval.0, estimate.log_mean_interval.1)
+}
+
+/// Fits z to the mean lifetimes of every run file, prints the fits and stores them in the z data file under the spin
+/// sector of the run file, next to the endpoint and average slope estimates of z. The power law fit is always made, the
+/// fit with corrections to scaling when an omega is given.
+pub fn fit_dynamical_exponents(args: &FitZArgs) {
+    let mut z_data: ZData = if Path::new(&args.z_data_file).exists() { file_utils::load_data(args.z_data_file.clone()) } else { ZData::default() };
+    for run_file in &args.run_files {
+        let run_data: RunData = file_utils::load_data(run_file.clone());
+        let spin_sector = run_data.spin_sector.or(args.spin_sector)
+            .unwrap_or_else(|| panic!("{run_file} does not record its spin sector, pass it with --spin-sector"));
+        let points = scaling_fit::scaling_points(&run_data, args.min_chain_size, args.max_chain_size.unwrap_or(usize::MAX));
+        println!("{run_file}, spin sector {spin_sector}");
+        println!("chain size, mean lifetime in sweeps, standard error");
+        for point in &points {
+            println!("{}, {}, {}", point.chain_size, point.mean_lifetime, point.standard_error);
+        }
+
+        if let Some((endpoint_z, average_z)) = scaling_fit::slope_estimates(&run_data, args.min_chain_size, args.max_chain_size.unwrap_or(usize::MAX)) {
+            println!("endpoint z: {endpoint_z}, average z: {average_z}");
+            z_data.z_data.insert(spin_sector, vec![endpoint_z, average_z]);
+        }
+        let fits: Vec<ZFit> = [None, args.correction_exponent].into_iter().enumerate()
+            .filter(|(index, correction_exponent)| *index == 0 || correction_exponent.is_some())
+            .filter_map(|(_, correction_exponent)| scaling_fit::fit_z(&points, correction_exponent))
+            .collect();
+        if fits.is_empty() {
+            println!("too few chain sizes to fit z");
+            continue;
+        }
+        let optional = |value: Option<f64>| value.map_or("none".to_string(), |value| value.to_string());
+        println!("omega, z, standard error, jackknife error, amplitude, correction amplitude, reduced chi squared, chain sizes");
+        for fit in &fits {
+            println!("{}, {}, {}, {}, {}, {}, {}, {}", optional(fit.correction_exponent), fit.z, fit.standard_error, optional(fit.jackknife_error),
+                fit.amplitude, optional(fit.correction_amplitude), optional(fit.reduced_chi_squared), fit.chain_sizes);
+        }
+
+        z_data.fits.insert(spin_sector, fits);
+    }
+    file_utils::save_data(args.z_data_file.clone(), &z_data);
+}
+
+/// Collapses the survival curves of every chain size of every run file, prints the optimal exponents and, when asked
+/// for, the rescaled curves. Lifetimes are in sweeps, or in time for the gillespie dynamics, and every run file has to
+/// measure them the same way.
+pub fn collapse_survival_curves(args: &CollapseArgs) {
+    let mut curves: Vec<SurvivalCurve> = Vec::new();
+    let mut continuous_time = None;
+    for run_file in &args.run_files {
+        let run_data: RunData = file_utils::load_data(run_file.clone());
+        let spin_sector = run_data.spin_sector.unwrap_or_else(|| panic!("{run_file} does not record its spin sector"));
+        if *continuous_time.get_or_insert(run_data.is_continuous_time()) != run_data.is_continuous_time() {
+            panic!("{run_file} measures lifetimes differently from the run files before it, gillespie runs cannot be collapsed with step counts");
+        }
+        let max_chain_size = args.max_chain_size.unwrap_or(usize::MAX);
+        curves.extend(run_data.chain_sizes().into_iter()
+            .filter(|chain_size| (args.min_chain_size..=max_chain_size).contains(chain_size))
+            .filter_map(|chain_size| data_collapse::survival_curve(&run_data, spin_sector, chain_size, args.min_survival, args.max_survival, args.points_per_curve)));
+    }
+    if curves.len() < 2 {
+        println!("too few survival curves to collapse");
+        return;
+    }
+    let spin_sectors: BTreeSet<usize> = curves.iter().map(|curve| curve.spin_sector).collect();
+    if args.sector_exponent.is_some() && (spin_sectors.len() < 2 || spin_sectors.contains(&0)) {
+        panic!("the sector exponent needs curves of two nonzero spin sectors or more, the run files have spin sectors {spin_sectors:?}");
+    }
+
+    let collapse = data_collapse::optimize_collapse(&curves, args.initial_z, args.sector_exponent);
+    let optional = |value: Option<f64>| value.map_or("none".to_string(), |value| value.to_string());
+    println!("{} survival curves, spin sectors {:?}", curves.len(), spin_sectors);
+    println!("z, error, sector exponent, error, quality, overlapping points");
+    println!("{}, {}, {}, {}, {}, {}", collapse.z, optional(collapse.z_error), optional(collapse.sector_exponent), optional(collapse.sector_exponent_error),
+        collapse.quality, collapse.overlapping_points);
+
+    if args.curves {
+        for curve in &curves {
+            println!("spin sector {}, chain size {}", curve.spin_sector, curve.chain_size);
+            println!("rescaled lifetime, survival, standard error");
+            let rescaled_log_lifetimes = data_collapse::rescaled_log_lifetimes(curve, collapse.z, collapse.sector_exponent.unwrap_or(0.0));
+            for (point, log_lifetime) in rescaled_log_lifetimes.iter().enumerate() {
+                println!("{}, {}, {}", log_lifetime.exp(), curve.survival[point], curve.standard_errors[point]);
+            }
+        }
+    }
+}
+
+/// Draws the mean lifetime in sweeps against chain size for every run file, on linear and log-log axes with the power
+/// law fit of z, and against spin sector when a chain size is given. Prints the name of every file it writes.
+pub fn plot_lifetimes(args: &PlotArgs) {
+    fs::create_dir_all(&args.plot_directory).unwrap();
+    let mut spin_sector_points: Vec<(usize, ScalingPoint)> = Vec::new();
+    for run_file in &args.run_files {
+        let run_data: RunData = file_utils::load_data(run_file.clone());
+        let spin_sector = run_data.spin_sector.or(args.spin_sector)
+            .unwrap_or_else(|| panic!("{run_file} does not record its spin sector, pass it with --spin-sector"));
+        let points = scaling_fit::scaling_points(&run_data, args.min_chain_size, args.max_chain_size.unwrap_or(usize::MAX));
+        if let Some(point) = args.chain_size.and_then(|chain_size| points.iter().find(|point| point.chain_size == chain_size)) {
+            spin_sector_points.push((spin_sector, *point));
+        }
+        if points.is_empty() {
+            println!("{run_file} has no chain sizes with two different lifetimes or more to plot");
+            continue;
+        }
+
+        let fit_line = scaling_fit::fit_z(&points, None).map(|fit| {
+            let (first, last) = (points[0].chain_size as f64, points[points.len() - 1].chain_size as f64);
+            let line: Vec<(f64, f64)> = (0..=100).map(|index| {
+                let chain_size = first * (last / first).powf(index as f64 / 100.0);
+                (chain_size, fit.amplitude * chain_size.powf(fit.z))
+            }).collect();
+            Series::line(&format!("fit, z = {:.3} ± {:.3}", fit.z, fit.standard_error), line)
+        });
+        for (scale, suffix) in [(Scale::Linear, ""), (Scale::Log, "log_")] {
+            let mut plot = Plot::new(&format!("Fredkin chain lifetimes, spin sector {spin_sector}"), "chain size", "mean lifetime (sweeps)", scale, scale);
+            plot.add_series(Series::markers("simulated", points.iter().map(|point| (point.chain_size as f64, point.mean_lifetime)).collect(),
+                points.iter().map(|point| point.standard_error).collect()));
+            plot.add_series(fit_line.clone().unwrap_or_else(|| Series::line("too few chain sizes to fit z", Vec::new())));
+            let file_name = format!("{}/lifetime_{}{}.svg", args.plot_directory, suffix, spin_sector);
+            plot.save(&file_name);
+            println!("{file_name}");
+        }
+    }
+
+    let Some(chain_size) = args.chain_size else {
+        return;
+    };
+    if spin_sector_points.is_empty() {
+        println!("no run file has chain size {chain_size}");
+        return;
+    }
+    spin_sector_points.sort_by_key(|(spin_sector, _)| *spin_sector);
+    let (min_spin_sector, max_spin_sector) = (spin_sector_points[0].0, spin_sector_points[spin_sector_points.len() - 1].0);
+    for (scale, suffix) in [(Scale::Linear, ""), (Scale::Log, "_log")] {
+        let mut plot = Plot::new(&format!("Fredkin chain lifetimes, chain size {chain_size}"), "spin sector", "mean lifetime (sweeps)", scale, scale);
+        plot.add_series(Series::markers("simulated", spin_sector_points.iter().map(|(spin_sector, point)| (*spin_sector as f64, point.mean_lifetime)).collect(),
+            spin_sector_points.iter().map(|(_, point)| point.standard_error).collect()));
+        let file_name = format!("{}/ss_{}_{}_cs_{}{}.svg", args.plot_directory, min_spin_sector, max_spin_sector, chain_size, suffix);
+        plot.save(&file_name);
+        println!("{file_name}");
+    }
+}
+
+/// Merges the run files into the output file and prints the trials of every chain size each file brought. Files that
+/// cannot be merged stop the command with an error naming them, before anything is written.
+pub fn merge_run_files(args: &MergeArgs) {
+    let inputs: Vec<(String, RunData)> = args.run_files.iter().map(|run_file| (run_file.clone(), file_utils::load_data(run_file.clone()))).collect();
+    let (merged, warnings) = match run_merge::merge_runs(&inputs, args.allow_mismatch, args.spin_sector) {
+        Ok(merged) => merged,
+        Err(message) => cli::exit_with_error("<RUN_FILES>", &message),
+    };
+    let output_format = merged.config.as_ref().map(|config| config.output_format).unwrap_or_default();
+    file_utils::save_data_formatted(args.output.clone(), &merged, output_format);
+    for warning in &warnings {
+        println!("mismatch merged anyway, {warning}");
+    }
+
+    println!("source, master seed, chain size, trials");
+    for source in &merged.sources {
+        let master_seed = source.master_seed.map_or("none".to_string(), |master_seed| master_seed.to_string());
+        for (chain_size, trials) in &source.trials {
+            println!("{}, {}, {}, {}", source.file_name, master_seed, chain_size, trials);
+        }
+    }
+    println!("merged, , chain size, trials");
+    for (chain_size, trials) in merged.trial_counts() {
+        println!("{}, , {}, {}", args.output, chain_size, trials);
+    }
+}
+
+/// Prints the running statistics of the lifetimes of every chain size, in time for the gillespie dynamics and in steps
+/// otherwise. The quantiles come from the quantile sketch and are accurate to its relative accuracy.
+fn print_lifetime_statistics(run_data: &RunData) {
+    println!("chain size, trials, censored, mean lifetime, standard error, min, 10th percentile, median, 90th percentile, max");
+    for (chain_size, summary) in &run_data.summaries {
+        let lifetimes = summary.lifetimes();
+        let quantile = |quantile: f64| lifetimes.quantiles.quantile(quantile).map_or("none".to_string(), |value| value.to_string());
+        println!("{}, {}, {}, {}, {}, {}, {}, {}, {}, {}", chain_size, lifetimes.count + summary.censored_trials, summary.censored_trials,
+            lifetimes.mean, lifetimes.standard_error(), lifetimes.min, quantile(0.1), quantile(0.5), quantile(0.9), lifetimes.max);
+    }
+}
+
+/// Prints the Kaplan-Meier mean and median lifetime of every chain size, the survival curves too when asked for.
+/// A mean is restricted when the longest lifetime of its chain size was censored and is then a lower bound.
+fn print_kaplan_meier_estimates(run_data: &RunData, print_curves: bool) {
+    let mut estimates = Vec::new();
+    for chain_size in run_data.chain_sizes() {
+        let (lifetimes, censored_lifetimes) = run_data.lifetime_samples(chain_size);
+        let kaplan_meier = KaplanMeier::new(&lifetimes, &censored_lifetimes);
+        if print_curves {
+            println!("chain size {chain_size} survival");
+            println!("time, at risk, deaths, censored, survival, standard error");
+            for point in &kaplan_meier.points {
+                println!("{}, {}, {}, {}, {}, {}", point.time, point.at_risk, point.deaths, point.censored, point.survival, point.standard_error);
+            }
+        }
+        estimates.push((chain_size, lifetimes.len() + censored_lifetimes.len(), censored_lifetimes.len(), kaplan_meier));
+    }
+    println!("chain size, trials, censored, kaplan-meier mean lifetime, standard error, restricted, median lifetime");
+    for (chain_size, trials, censored, kaplan_meier) in estimates {
+        let mean = kaplan_meier.mean();
+        let median = kaplan_meier.median().map_or("none".to_string(), |median| median.to_string());
+        println!("{}, {}, {}, {}, {}, {}, {}", chain_size, trials, censored, mean.mean, mean.standard_error, mean.restricted, median);
+    }
+}
+
+/// Prints the histogram, survival and hazard of the lifetimes of every chain size in logarithmic bins, followed by the
+/// decay rates of the exponential tails, in time for the gillespie dynamics and per step otherwise.
+fn print_lifetime_distributions(run_data: &RunData, bins_per_decade: u32, tail_fraction: f64) {
+    let discrete = !run_data.is_continuous_time();
+    let distributions: Vec<(usize, LifetimeDistribution)> = run_data.chain_sizes().into_iter()
+        .map(|chain_size| {
+            let distribution = match run_data.summaries.get(&chain_size) {
+                Some(summary) => lifetime_distribution::summary_distribution(summary, discrete),
+                None => {
+                    let (lifetimes, censored_lifetimes) = run_data.lifetime_samples(chain_size);
+                    lifetime_distribution::lifetime_distribution(&lifetimes, &censored_lifetimes, bins_per_decade, discrete, tail_fraction)
+                }
+            };
+            (chain_size, distribution)
+        })
+        .collect();
+
+    for (chain_size, distribution) in &distributions {
+        println!("chain size {chain_size} distribution");
+        println!("lower, upper, deaths, density, survival, hazard, hazard standard error");
+        for bin in &distribution.bins {
+            println!("{}, {}, {}, {}, {}, {}, {}", bin.lower, bin.upper, bin.deaths, bin.density, bin.survival, bin.hazard, bin.hazard_standard_error);
+        }
+    }
+    if !run_data.summaries.is_empty() {
+        println!("no tail fits, the run only kept running statistics of the lifetimes");
+        return;
+    }
+    println!("chain size, tail start, tail survival, tail deaths, decay rate, standard error, decay time");
+    for (chain_size, distribution) in &distributions {
+        match &distribution.tail_fit {
+            Some(fit) => println!("{}, {}, {}, {}, {}, {}, {}", chain_size, fit.start, fit.survival, fit.deaths, fit.rate, fit.standard_error, 1.0 / fit.rate),
+            None => println!("{chain_size}, none, , , , , "),
+        }
+    }
+}
+
+/// Prints the mean of every observable value at every observation time, the values separated by spaces,
+/// followed by their standard errors. The means only average over the trials still alive at that time.
+fn print_observable_curves(run_data: &RunData) {
+    for (chain_size, curves) in &run_data.observables {
+        for observable in curves.sums.keys() {
+            println!("chain size {chain_size}, {observable:?}");
+            println!("time, survivors, means, standard errors");
+            let means = curves.means(*observable);
+            let standard_errors = curves.standard_errors(*observable);
+            for (time_index, time) in curves.times.iter().enumerate() {
+                println!("{}, {}, {}, {}", time, curves.survivors[time_index], join_values(&means[time_index]), join_values(&standard_errors[time_index]));
+            }
+        }
+    }
+}
+
+fn join_values(values: &[f64]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" ")
+}
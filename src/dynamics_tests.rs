@@ -0,0 +1,446 @@
+//! Statistical checks that the rejection-free dynamics of n_fold_way and gillespie give the lifetimes of the random-index
+//! dynamics. Mean lifetimes are compared against the exact solver and whole lifetime distributions against the random-index
+//! dynamics with a two sample Kolmogorov-Smirnov test. Every test runs from a fixed seed.
+
+use std::fs;
+
+use rand_mt::Mt64;
+
+use crate::config::{ContinuousTimeConfig, Dynamics, ExcitedBondConfig, LifetimeStorage, ObservableConfig, RunConfig, SnapshotTrigger, TimeUnit, TrajectoryConfig, UpdateScheme};
+use crate::exact_solver;
+use crate::n_fold_way::{self, ActiveMoves};
+use crate::observables::{Observable, ObservableCurves, ObservableKind, ObservableSampler};
+use crate::recorder::TrialRecording;
+use crate::simulation::{self, Lifetime};
+use crate::site::Site;
+use crate::site_placer::SitePlacerKind;
+use crate::spin_chain::SpinChain;
+use crate::trajectory::{TrajectoryRecorder, TrajectorySnapshot, TrajectoryWriter};
+use crate::update_scheme::UpdateSchedule;
+use crate::enumeration;
+use crate::{evolve_chain, CHAIN_SIZE};
+
+/// Number of standard errors a sample mean may sit away from the exact mean
+const MEAN_CRITICAL_Z: f64 = 4.0;
+/// Kolmogorov-Smirnov constant for a p value of 0.001
+const KS_CRITICAL_CONSTANT: f64 = 1.95;
+
+fn simulated_lifetimes(config: &RunConfig, chain_size: usize, trials: usize, seed: u64) -> Vec<Lifetime> {
+    let excited_bond_map = config.excited_bond_map.to_map();
+    let mut rng = Mt64::new(seed);
+    (0..trials).map(|_| simulation::run_trial(config, &excited_bond_map, chain_size, &mut rng)).collect()
+}
+
+fn simulated_step_counts(dynamics: Dynamics, excited_bonds: ExcitedBondConfig, chain_size: usize, trials: usize, seed: u64) -> Vec<u128> {
+    let config = RunConfig { dynamics, excited_bond_map: excited_bonds, ..RunConfig::default() };
+    simulated_lifetimes(&config, chain_size, trials, seed).iter().map(|lifetime| lifetime.step_count).collect()
+}
+
+/// The sample mean and its standard error
+fn mean_and_standard_error(samples: &[f64]) -> (f64, f64) {
+    let count = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+    let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (count - 1.0);
+    (mean, (variance / count).sqrt())
+}
+
+/// The largest distance between the empirical distribution functions of two samples
+fn two_sample_ks_distance(first: &[u128], second: &[u128]) -> f64 {
+    let mut first = first.to_vec();
+    let mut second = second.to_vec();
+    first.sort_unstable();
+    second.sort_unstable();
+
+    let (mut first_index, mut second_index) = (0, 0);
+    let mut largest_distance: f64 = 0.0;
+    while first_index < first.len() && second_index < second.len() {
+        let value = first[first_index].min(second[second_index]);
+        while first_index < first.len() && first[first_index] == value {
+            first_index += 1;
+        }
+        while second_index < second.len() && second[second_index] == value {
+            second_index += 1;
+        }
+        let distance = (first_index as f64 / first.len() as f64 - second_index as f64 / second.len() as f64).abs();
+        largest_distance = largest_distance.max(distance);
+    }
+    largest_distance
+}
+
+fn assert_n_fold_way_matches_exact_mean(excited_bonds: ExcitedBondConfig, chain_size: usize, seed: u64) {
+    let exact_lifetime = exact_solver::exact_mean_lifetime(&excited_bonds, chain_size, SitePlacerKind::KBeach).unwrap().mean_lifetime;
+    let lifetimes: Vec<f64> = simulated_step_counts(Dynamics::NFoldWay, excited_bonds, chain_size, 20000, seed).iter().map(|steps| *steps as f64).collect();
+    let (mean, standard_error) = mean_and_standard_error(&lifetimes);
+    assert!((mean - exact_lifetime).abs() < MEAN_CRITICAL_Z * standard_error,
+        "chain size {chain_size}: n-fold way mean {mean} +- {standard_error}, exact {exact_lifetime}");
+}
+
+#[test]
+fn n_fold_way_matches_exact_mean_lifetime() {
+    assert_n_fold_way_matches_exact_mean(ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 8, 1);
+    assert_n_fold_way_matches_exact_mean(ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 12, 2);
+    assert_n_fold_way_matches_exact_mean(ExcitedBondConfig { up_cant: 0, down_cant: 1, mismatch: 1 }, 10, 3);
+}
+
+#[test]
+fn exact_solver_refuses_chains_with_too_many_states() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 };
+    let (argument, _) = exact_solver::exact_mean_lifetime(&excited_bonds, 18, SitePlacerKind::KBeach).err().unwrap();
+    assert_eq!(argument, "max_chain_size");
+}
+
+/// Runs whose lifetimes the exact solution does not describe are told apart from the ones it does
+#[test]
+fn exact_solver_refuses_runs_it_does_not_describe() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 };
+    for dynamics in [Dynamics::RandomIndex, Dynamics::NFoldWay, Dynamics::Gillespie] {
+        assert!(exact_solver::run_mismatches(&RunConfig { dynamics, ..RunConfig::default() }, &excited_bonds, SitePlacerKind::KBeach).is_empty());
+    }
+    let mismatched_configs = [
+        RunConfig { update_scheme: UpdateScheme::OrderedSweep, ..RunConfig::default() },
+        RunConfig { site_placer: SitePlacerKind::Uniform, ..RunConfig::default() },
+        RunConfig { excited_bond_map: ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 }, ..RunConfig::default() },
+    ];
+    for config in &mismatched_configs {
+        assert_eq!(exact_solver::run_mismatches(config, &excited_bonds, SitePlacerKind::KBeach).len(), 1);
+    }
+    let (argument, _) = exact_solver::exact_mean_lifetime(&excited_bonds, 8, SitePlacerKind::Rejection).err().unwrap();
+    assert_eq!(argument, "site_placer");
+}
+
+#[test]
+fn n_fold_way_and_random_index_lifetimes_agree() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 };
+    let trials = 5000;
+    let random_index = simulated_step_counts(Dynamics::RandomIndex, excited_bonds, 14, trials, 4);
+    let n_fold_way = simulated_step_counts(Dynamics::NFoldWay, excited_bonds, 14, trials, 5);
+
+    let distance = two_sample_ks_distance(&random_index, &n_fold_way);
+    let critical_distance = KS_CRITICAL_CONSTANT * (2.0 / trials as f64).sqrt();
+    assert!(distance < critical_distance, "KS distance {distance} above {critical_distance}");
+}
+
+/// Every step of the random-index dynamics attempts one of the chain_size - 2 triples, and in continuous time every triple
+/// attempts move_rate moves per unit of time, so the exact mean time is the exact mean step count over (chain_size - 2) * move_rate
+#[test]
+fn gillespie_matches_exact_mean_time() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 1 };
+    let chain_size = 10;
+    let exact_step_count = exact_solver::exact_mean_lifetime(&excited_bonds, chain_size, SitePlacerKind::KBeach).unwrap().mean_lifetime;
+
+    for (time_unit, coupling, seed) in [(TimeUnit::Sweeps, 1.0, 8), (TimeUnit::Physical, 3.0, 9)] {
+        let continuous_time = ContinuousTimeConfig { time_unit, coupling };
+        let config = RunConfig { dynamics: Dynamics::Gillespie, excited_bond_map: excited_bonds, continuous_time, ..RunConfig::default() };
+        let times: Vec<f64> = simulated_lifetimes(&config, chain_size, 20000, seed).iter().map(|lifetime| lifetime.time.unwrap()).collect();
+
+        let exact_time = exact_step_count / ((chain_size - 2) as f64 * continuous_time.move_rate());
+        let (mean, standard_error) = mean_and_standard_error(&times);
+        assert!((mean - exact_time).abs() < MEAN_CRITICAL_Z * standard_error, "{time_unit:?}: gillespie mean {mean} +- {standard_error}, exact {exact_time}");
+    }
+}
+
+#[test]
+fn active_moves_follow_the_chain() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 1 };
+    let chain_size = 30;
+    let mut rng = Mt64::new(6);
+
+    for _ in 0..50 {
+        let mut spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited(&excited_bonds.to_map(), chain_size, &mut rng);
+        let mut active_moves = ActiveMoves::new(&spin_chain.chain, chain_size);
+        loop {
+            let active_count = (0..chain_size - 2).filter(|index| n_fold_way::is_active_move(&spin_chain.chain, *index)).count();
+            assert_eq!(active_moves.len(), active_count);
+
+            let random_index = active_moves.choose(&mut rng);
+            assert!(n_fold_way::is_active_move(&spin_chain.chain, random_index));
+            let swapped_index = n_fold_way::swapped_site_index(&spin_chain.chain, random_index);
+            let chain_before_move = spin_chain.chain.clone();
+            let mut evolved_chain = spin_chain.chain.clone();
+            let is_alive = evolve_chain(&mut evolved_chain, random_index, chain_size);
+            assert_eq!(active_moves.make_move(&mut spin_chain.chain, random_index, chain_size), is_alive);
+            if !is_alive {
+                break;
+            }
+            // the move gives the chain of evolve_chain, changing only the two swapped sites
+            assert_eq!(spin_chain.chain, evolved_chain);
+            let changed_sites: Vec<usize> = (0..chain_size).filter(|index| spin_chain.chain[*index] != chain_before_move[*index]).collect();
+            assert_eq!(changed_sites, vec![swapped_index, swapped_index + 1]);
+        }
+    }
+}
+
+/// The sites of a chain written as the brackets print_chain prints
+fn chain_of(brackets: &str) -> Vec<Site> {
+    brackets.chars().map(|bracket| match bracket {
+        '(' => Site::Up,
+        ')' => Site::Down,
+        '[' => Site::UpCant,
+        ']' => Site::DownCant,
+        '}' => Site::MismatchDown,
+        '{' => Site::MismatchUp,
+        _ => panic!("{bracket} is not a site"),
+    }).collect()
+}
+
+/// Pins the edge rules of evolve_chain: ()) at the left edge kills the chain like (() at the right edge, and
+/// )() at the left edge swaps like ()( at the right edge does
+#[test]
+fn unmatched_spins_die_at_either_edge() {
+    // (chain, random index, chain after the step, alive)
+    let steps = [
+        ("()]]()", 0, "()]]()", false),
+        ("]()]()", 1, "])(]()", true),
+        ("]](())", 0, "]](())", true),
+        ("()}{()", 0, "()}{()", false),
+        ("}(){()", 0, "(}){()", true),
+        ("}{)(()", 3, "}{)(()", false),
+        ("}{)()(", 3, "}{)(()", true),
+    ];
+    for (brackets, random_index, evolved_brackets, alive) in steps {
+        let mut chain = chain_of(brackets);
+        let chain_size = chain.len();
+        assert_eq!(evolve_chain(&mut chain, random_index, chain_size), alive, "{brackets} at {random_index}");
+        if alive {
+            assert_eq!(chain, chain_of(evolved_brackets), "{brackets} at {random_index}");
+        }
+    }
+}
+
+#[test]
+fn geometric_waiting_times_have_the_geometric_mean() {
+    let mut rng = Mt64::new(7);
+    let number_of_triples = 100;
+    let waiting_times = n_fold_way::WaitingTimes::new(number_of_triples);
+    // looked up, looked up and searched beyond the table in about 0.3% of the draws, and found by a logarithm
+    for number_of_active in [60, 30, 5] {
+        let probability = number_of_active as f64 / number_of_triples as f64;
+        let draws: Vec<(u128, usize)> = (0..50000).map(|_| waiting_times.draw(number_of_active, &mut rng)).collect();
+        let tries: Vec<f64> = draws.iter().map(|(tries, _)| *tries as f64).collect();
+        let (mean, standard_error) = mean_and_standard_error(&tries);
+        assert!((mean - 1.0 / probability).abs() < MEAN_CRITICAL_Z * standard_error, "probability {probability}: mean {mean} +- {standard_error}");
+
+        let single_try_fraction = tries.iter().filter(|tries| **tries == 1.0).count() as f64 / tries.len() as f64;
+        let single_try_error = (probability * (1.0 - probability) / tries.len() as f64).sqrt();
+        assert!((single_try_fraction - probability).abs() < MEAN_CRITICAL_Z * single_try_error);
+
+        // the triple that moves is uniform over the active ones, whether it moved at the first try or later
+        for single_try in [true, false] {
+            let choices: Vec<f64> = draws.iter().filter(|(tries, _)| (*tries == 1) == single_try).map(|(_, choice)| *choice as f64).collect();
+            assert!(choices.iter().all(|choice| *choice < number_of_active as f64));
+            let (mean, standard_error) = mean_and_standard_error(&choices);
+            let expected = (number_of_active - 1) as f64 / 2.0;
+            assert!((mean - expected).abs() < MEAN_CRITICAL_Z * standard_error, "probability {probability}, single try {single_try}: mean choice {mean} +- {standard_error}");
+        }
+    }
+    assert_eq!(n_fold_way::WaitingTimes::new(1).draw(1, &mut rng), (1, 0));
+}
+
+#[test]
+fn sweeps_attempt_every_triple_once() {
+    let chain_size = 14;
+    let mut rng = Mt64::new(10);
+    for update_scheme in [UpdateScheme::OrderedSweep, UpdateScheme::RandomPermutation, UpdateScheme::Checkerboard] {
+        let mut update_schedule = UpdateSchedule::new(update_scheme, chain_size);
+        for _ in 0..5 {
+            let mut sweep: Vec<usize> = (0..chain_size - 2).map(|_| update_schedule.next_index(chain_size, &mut rng)).collect();
+            if update_scheme == UpdateScheme::Checkerboard {
+                // the triples of a sublattice come one after the other and do not overlap
+                assert!(sweep.windows(2).all(|pair| pair[1] == pair[0] + 3 || pair[1] < pair[0]), "{sweep:?}");
+            }
+            sweep.sort_unstable();
+            assert_eq!(sweep, (0..chain_size - 2).collect::<Vec<usize>>(), "{update_scheme:?}");
+        }
+    }
+}
+
+/// Starts the deterministic schemes from every configuration and checks the test run_trial stops immortal chains with:
+/// the chains that return to their starting spins are the ones that never die
+#[test]
+fn deterministic_sweeps_detect_chains_that_never_die() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 };
+    let chain_size = 12;
+    for update_scheme in [UpdateScheme::OrderedSweep, UpdateScheme::Checkerboard] {
+        let config = RunConfig { update_scheme, ..RunConfig::default() };
+        let mut immortal_chains = 0;
+        for configuration in enumeration::excited_configurations(&excited_bonds, chain_size) {
+            if deterministic_lifetime(&config, &configuration, chain_size, 1000 * (chain_size - 2)).is_none() {
+                immortal_chains += 1;
+            }
+        }
+        // about a third of these chains cycle forever under either scheme
+        assert!(immortal_chains > 0, "{update_scheme:?}");
+    }
+}
+
+/// Evolves the configuration with the deterministic update scheme for at most max_steps and returns its lifetime,
+/// or None when the chain is still alive
+fn deterministic_lifetime(config: &RunConfig, configuration: &[Site], chain_size: usize, max_steps: usize) -> Option<usize> {
+    let mut chain = configuration.to_vec();
+    let mut update_schedule = UpdateSchedule::new(config.update_scheme, chain_size);
+    let mut rng = Mt64::new(0);
+    let mut returned_to_start = false;
+    for step in 1..=max_steps {
+        let index = update_schedule.next_index(chain_size, &mut rng);
+        if !evolve_chain(&mut chain, index, chain_size) {
+            assert!(!returned_to_start, "a chain that returned to its starting spins died");
+            return Some(step);
+        }
+        if update_schedule.completed_deterministic_sweep() && chain.iter().zip(configuration).all(|(site, start)| site.spin() == start.spin()) {
+            returned_to_start = true;
+        }
+    }
+    assert!(returned_to_start, "a chain that never dies did not return to its starting spins");
+    None
+}
+
+/// Snapshots every few steps land on the same steps whether the idle steps are simulated or skipped
+#[test]
+fn trajectories_are_recorded_every_interval() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 };
+    let chain_size = 12;
+    let interval = 3;
+    let mut rng = Mt64::new(11);
+
+    for (dynamics, snapshot) in [(Dynamics::RandomIndex, SnapshotTrigger::Steps), (Dynamics::NFoldWay, SnapshotTrigger::Steps), (Dynamics::Gillespie, SnapshotTrigger::Moves)] {
+        let trajectory = TrajectoryConfig { snapshot, interval, ..TrajectoryConfig::default() };
+        let config = RunConfig { dynamics, excited_bond_map: excited_bonds, trajectory, ..RunConfig::default() };
+        for trial in 0..20 {
+            let mut recorder = TrajectoryRecorder::new(&trajectory, trial, 0);
+            let lifetime = simulation::run_recorded_trial(&config, &excited_bonds.to_map(), chain_size, &mut recorder, &mut rng);
+
+            // every multiple of the interval the chain lived through, then the last chain before death
+            let mut expected_steps: Vec<u64> = (0..lifetime.step_count as u64).step_by(interval as usize).collect();
+            expected_steps.push(lifetime.step_count as u64);
+            let steps: Vec<u64> = recorder.snapshots.iter().map(|snapshot| snapshot.step).collect();
+            assert_eq!(steps, expected_steps, "{dynamics:?}");
+
+            // moves only swap sites, so every snapshot holds the sites of the starting chain
+            let mut starting_sites: Vec<i8> = recorder.snapshots[0].chain.iter().map(|site| i8::from(*site)).collect();
+            starting_sites.sort_unstable();
+            for snapshot in &recorder.snapshots {
+                let mut sites: Vec<i8> = snapshot.chain.iter().map(|site| i8::from(*site)).collect();
+                sites.sort_unstable();
+                assert_eq!(sites, starting_sites);
+                assert_eq!(snapshot.trial, trial);
+            }
+        }
+    }
+}
+
+/// A fresh run truncates the trajectory file and a resumed one cuts it back to the checkpoint, so the trials written after
+/// the checkpoint by the interrupted run do not show up twice
+#[test]
+fn resumed_runs_write_every_trajectory_once() {
+    let storage_directory = std::env::temp_dir().join(format!("fredkin_trajectories_{}", std::process::id()));
+    fs::create_dir_all(&storage_directory).unwrap();
+    let storage_directory = storage_directory.to_str().unwrap();
+    let trajectory = TrajectoryConfig { snapshot: SnapshotTrigger::Moves, ..TrajectoryConfig::default() };
+    let snapshot = |trial: u64| TrajectorySnapshot { trial, seed: trial, step: 0, moves: 0, time: None, chain: vec![Site::Up, Site::Down] };
+    let written_trials = || -> Vec<u64> {
+        let contents = fs::read_to_string(format!("{}/trajectories_ss_1_cs_4.jsonl", storage_directory)).unwrap();
+        contents.lines().map(|line| serde_json::from_str::<TrajectorySnapshot>(line).unwrap().trial).collect()
+    };
+
+    for _ in 0..2 {
+        let mut trajectory_writer = TrajectoryWriter::new(&trajectory, storage_directory, 1, 4, 0, None);
+        for trial in 0..3 {
+            trajectory_writer.write_trajectory(&[snapshot(trial)]);
+        }
+    }
+    assert_eq!(written_trials(), vec![0, 1, 2]);
+
+    let mut trajectory_writer = TrajectoryWriter::new(&trajectory, storage_directory, 1, 4, 0, None);
+    trajectory_writer.write_trajectory(&[snapshot(0), snapshot(1)]);
+    let checkpoint_length = trajectory_writer.length();
+    // the trial the run was interrupted in
+    trajectory_writer.write_trajectory(&[snapshot(2)]);
+
+    let mut trajectory_writer = TrajectoryWriter::new(&trajectory, storage_directory, 1, 4, 2, Some(checkpoint_length));
+    trajectory_writer.write_trajectory(&[snapshot(2), snapshot(3)]);
+    assert_eq!(written_trials(), vec![0, 1, 2, 3]);
+
+    fs::remove_dir_all(storage_directory).unwrap();
+}
+
+/// Observables are measured on the chain the trajectory holds at each observation time, for the times the chain lives to.
+/// The excited sites are those of the re-labelled chain, which the labels only follow with track_bond_identity.
+#[test]
+fn observables_measure_the_chain_alive_at_each_time() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 };
+    let chain_size = 12;
+    let quantities = vec![ObservableKind::HeightProfile, ObservableKind::ExcitedSitePositions, ObservableKind::ActiveMoves, ObservableKind::RightmostExcitedDistance];
+    let observable_config = ObservableConfig { quantities: quantities.clone(), times: vec![0.0, 1.0, 2.5, 7.0, 20.0, 60.0, 200.0] };
+    let mut rng = Mt64::new(13);
+
+    for (dynamics, snapshot) in [(Dynamics::RandomIndex, SnapshotTrigger::Steps), (Dynamics::NFoldWay, SnapshotTrigger::Steps), (Dynamics::Gillespie, SnapshotTrigger::Moves)] {
+        let trajectory = TrajectoryConfig { snapshot, ..TrajectoryConfig::default() };
+        let config = RunConfig { dynamics, excited_bond_map: excited_bonds, trajectory, observables: observable_config.clone(), track_bond_identity: true, max_chain_size: chain_size, ..RunConfig::default() };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(RunConfig { track_bond_identity: false, ..config.clone() }.validate().unwrap_err().0, "observables");
+        let mut curves = ObservableCurves::new(&observable_config);
+        let mut death_times = Vec::new();
+        for trial in 0..20 {
+            let mut recording = TrialRecording { trajectory: TrajectoryRecorder::new(&trajectory, trial, 0), observables: ObservableSampler::new(&observable_config) };
+            let lifetime = simulation::run_recorded_trial(&config, &excited_bonds.to_map(), chain_size, &mut recording, &mut rng);
+            let death_time = lifetime.time.unwrap_or(lifetime.step_count as f64);
+            let measurements = &recording.observables.measurements;
+            assert_eq!(measurements.len(), observable_config.times.iter().filter(|time| **time < death_time).count(), "{dynamics:?}");
+
+            for (time, values) in observable_config.times.iter().zip(measurements) {
+                // the last snapshot at or before the observation time, leaving out the last chain before death
+                let snapshots = &recording.trajectory.snapshots[..recording.trajectory.snapshots.len() - 1];
+                let chain = match dynamics {
+                    Dynamics::Gillespie => &snapshots.iter().rev().find(|snapshot| snapshot.time.unwrap() <= *time).unwrap().chain,
+                    _ => &snapshots[time.floor() as usize].chain,
+                };
+                let mut relabelled_chain = SpinChain::<CHAIN_SIZE> { chain: chain.clone(), chain_hash: 0, number_of_mismatch_bonds: excited_bonds.mismatch };
+                relabelled_chain.relabel_excited_sites();
+                for (observable, observable_values) in quantities.iter().zip(values) {
+                    let mut expected_values = Vec::new();
+                    observable.measure(&relabelled_chain.chain, &mut expected_values);
+                    assert_eq!(observable_values, &expected_values, "{dynamics:?} {observable:?} at time {time}");
+                }
+            }
+            curves.add_trial(&quantities, measurements);
+            death_times.push(death_time);
+        }
+
+        let expected_survivors: Vec<u64> = observable_config.times.iter().map(|time| death_times.iter().filter(|death_time| *death_time > time).count() as u64).collect();
+        assert_eq!(curves.survivors, expected_survivors, "{dynamics:?}");
+        // one height before every site and one after the last
+        let starting_heights = &curves.means(ObservableKind::HeightProfile)[0];
+        assert_eq!(starting_heights.len(), chain_size + 1);
+        assert_eq!(starting_heights[0], 0.0);
+    }
+}
+
+/// A capped trial follows the uncapped one step for step and is censored exactly when the uncapped one outlives the cap
+#[test]
+fn step_cap_censors_the_lifetimes_it_cuts_short() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 };
+    let chain_size = 10;
+
+    // the gillespie dynamics counts moves, far fewer than steps
+    for (dynamics, max_steps) in [(Dynamics::RandomIndex, 40), (Dynamics::NFoldWay, 20), (Dynamics::Gillespie, 8)] {
+        let config = RunConfig { dynamics, excited_bond_map: excited_bonds, ..RunConfig::default() };
+        let capped_config = RunConfig { max_steps, ..config.clone() };
+        // a summary only counts the censored trials, which would bias its mean lifetime low
+        assert_eq!(RunConfig { max_chain_size: chain_size, ..capped_config.clone() }.validate(), Ok(()));
+        assert_eq!(RunConfig { max_chain_size: chain_size, lifetime_storage: LifetimeStorage::Summary, ..capped_config.clone() }.validate().unwrap_err().0, "lifetime_storage");
+        let mut censored_trials = 0;
+        for seed in 0..200 {
+            let lifetime = simulated_lifetimes(&config, chain_size, 1, seed)[0];
+            let capped_lifetime = simulated_lifetimes(&capped_config, chain_size, 1, seed)[0];
+            assert_eq!(capped_lifetime.censored, lifetime.step_count > max_steps as u128, "{dynamics:?}");
+            assert_eq!(capped_lifetime.step_count, lifetime.step_count.min(max_steps as u128), "{dynamics:?}");
+            if capped_lifetime.censored {
+                censored_trials += 1;
+                assert!(capped_lifetime.time <= lifetime.time);
+            } else {
+                assert_eq!(capped_lifetime.time, lifetime.time);
+            }
+        }
+        assert!(censored_trials > 0 && censored_trials < 200, "{dynamics:?}: {censored_trials} censored trials");
+    }
+}
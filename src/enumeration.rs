@@ -0,0 +1,192 @@
+use crate::config::ExcitedBondConfig;
+use crate::site::Site;
+use crate::spin_chain;
+
+/// Lists every Dyck word of the given length in lexicographic order, ( before )
+/// * length: the number of sites of the words, odd lengths have no Dyck words
+pub fn dyck_words(length: usize) -> Vec<Vec<Site>> {
+    let mut words: Vec<Vec<Site>> = Vec::new();
+    if length.is_multiple_of(2) {
+        extend_dyck_word(&mut words, &mut Vec::with_capacity(length), 0, length);
+    }
+    words
+}
+
+fn extend_dyck_word(words: &mut Vec<Vec<Site>>, word: &mut Vec<Site>, height: usize, length: usize) {
+    let remaining_sites = length - word.len();
+    if remaining_sites == 0 {
+        words.push(word.clone());
+        return;
+    }
+    if height < remaining_sites {
+        word.push(Site::Up);
+        extend_dyck_word(words, word, height + 1, length);
+        word.pop();
+    }
+    if height > 0 {
+        word.push(Site::Down);
+        extend_dyck_word(words, word, height - 1, length);
+        word.pop();
+    }
+}
+
+/// Lists every excited chain of the given size that new_excited can generate. Without any excited bonds
+/// these are the plain Dyck words of the chain.
+/// * excited_bonds: the excited bonds placed in every chain
+/// * chain_size: the size of the chains
+///
+/// The excited sites sit at alternating even and odd positions outside of the sites reserved by reserved_edge_sites,
+/// are labelled ]] ... }} ... {{ ... [[ from left to right and every interval between them holds a Dyck word.
+pub fn excited_configurations(excited_bonds: &ExcitedBondConfig, chain_size: usize) -> Vec<Vec<Site>> {
+    let number_of_bonds = excited_bonds.up_cant + excited_bonds.down_cant + excited_bonds.mismatch;
+    if number_of_bonds == 0 {
+        return dyck_words(chain_size);
+    }
+    if chain_size < excited_bonds.minimum_chain_size() || !chain_size.is_multiple_of(2) {
+        return Vec::new();
+    }
+
+    let sites: Vec<Site> = [
+        vec![Site::DownCant; 2 * excited_bonds.down_cant],
+        vec![Site::MismatchDown; excited_bonds.mismatch],
+        vec![Site::MismatchUp; excited_bonds.mismatch],
+        vec![Site::UpCant; 2 * excited_bonds.up_cant],
+    ].concat();
+
+    let mut configurations: Vec<Vec<Site>> = Vec::new();
+    for placement in excited_site_placements(excited_bonds, chain_size) {
+        // The Dyck word intervals are [0, first excited site), the gaps between excited sites and (last excited site, chain_size)
+        let mut interval_bounds: Vec<usize> = vec![0];
+        for index in &placement {
+            interval_bounds.push(*index);
+            interval_bounds.push(index + 1);
+        }
+        interval_bounds.push(chain_size);
+
+        let mut partial_chains: Vec<Vec<Site>> = vec![Vec::with_capacity(chain_size)];
+        for (interval_number, interval) in interval_bounds.chunks(2).enumerate() {
+            let words = dyck_words(interval[1] - interval[0]);
+            let mut next_partial_chains: Vec<Vec<Site>> = Vec::new();
+            for partial_chain in &partial_chains {
+                for word in &words {
+                    let mut next_chain = partial_chain.clone();
+                    next_chain.extend_from_slice(word);
+                    if let Some(site) = sites.get(interval_number) {
+                        next_chain.push(*site);
+                    }
+                    next_partial_chains.push(next_chain);
+                }
+            }
+            partial_chains = next_partial_chains;
+        }
+        configurations.append(&mut partial_chains);
+    }
+
+    configurations
+}
+
+/// Lists every set of excited sites a site placer may return: 2 * (number of bonds) sites at alternating even and odd
+/// positions outside of the sites reserved by reserved_edge_sites
+pub fn excited_site_placements(excited_bonds: &ExcitedBondConfig, chain_size: usize) -> Vec<Vec<usize>> {
+    let number_of_sites = 2 * (excited_bonds.up_cant + excited_bonds.down_cant + excited_bonds.mismatch);
+    let (left_reserved, right_reserved) = spin_chain::reserved_edge_sites(excited_bonds.up_cant, excited_bonds.down_cant, excited_bonds.mismatch);
+    let mut placements: Vec<Vec<usize>> = Vec::new();
+    if chain_size >= left_reserved + right_reserved {
+        extend_placement(&mut placements, &mut Vec::new(), left_reserved, number_of_sites, chain_size - right_reserved);
+    }
+    placements
+}
+
+/// Adds every way of placing the remaining excited sites at alternating even and odd positions in [start, end)
+fn extend_placement(placements: &mut Vec<Vec<usize>>, placement: &mut Vec<usize>, start: usize, remaining_sites: usize, end: usize) {
+    if remaining_sites == 0 {
+        placements.push(placement.clone());
+        return;
+    }
+    // the n-th excited site is even for even n and odd for odd n
+    let parity = placement.len() % 2;
+    let first_index = if start % 2 == parity { start } else { start + 1 };
+    // leave room for the sites still to be placed after this one
+    let mut index = first_index;
+    while index + remaining_sites <= end {
+        placement.push(index);
+        extend_placement(placements, placement, index + 1, remaining_sites - 1, end);
+        placement.pop();
+        index += 2;
+    }
+}
+
+/// The number of Dyck words with 2 * n sites
+pub fn catalan_number(n: usize) -> u128 {
+    binomial(2 * n, n) / (n as u128 + 1)
+}
+
+/// The number of words of the given length with the given number of unmatched sites. Dyck words fill the
+/// unmatched_sites + 1 intervals around the unmatched sites, so the count does not depend on which of the
+/// unmatched sites are up or down spins: (k + 1)/(n + 1) * binomial(n + 1, (n - k)/2)
+pub fn ballot_number(length: usize, unmatched_sites: usize) -> u128 {
+    if unmatched_sites > length || !(length - unmatched_sites).is_multiple_of(2) {
+        return 0;
+    }
+    (unmatched_sites as u128 + 1) * binomial(length + 1, (length - unmatched_sites) / 2) / (length as u128 + 1)
+}
+
+/// The number of chains excited_configurations lists, counted without listing them.
+/// A reserved left edge forbids an empty Dyck word in front of the first excited site and a reserved right edge an empty
+/// Dyck word behind the last one. Removing such an edge site leaves a word with one unmatched site less, so inclusion-exclusion
+/// over the two edges gives the count from ballot numbers.
+pub fn number_of_excited_configurations(excited_bonds: &ExcitedBondConfig, chain_size: usize) -> u128 {
+    let unmatched_sites = 2 * (excited_bonds.up_cant + excited_bonds.down_cant + excited_bonds.mismatch);
+    if unmatched_sites == 0 {
+        return if chain_size.is_multiple_of(2) { catalan_number(chain_size / 2) } else { 0 };
+    }
+    if chain_size < excited_bonds.minimum_chain_size() {
+        return 0;
+    }
+    let (left_reserved, right_reserved) = spin_chain::reserved_edge_sites(excited_bonds.up_cant, excited_bonds.down_cant, excited_bonds.mismatch);
+
+    let mut count = ballot_number(chain_size, unmatched_sites);
+    if left_reserved > 0 && right_reserved > 0 {
+        count += ballot_number(chain_size - 2, unmatched_sites - 2);
+    }
+    if left_reserved > 0 {
+        count -= ballot_number(chain_size - 1, unmatched_sites - 1);
+    }
+    if right_reserved > 0 {
+        count -= ballot_number(chain_size - 1, unmatched_sites - 1);
+    }
+    count
+}
+
+fn binomial(n: usize, k: usize) -> u128 {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 0..k {
+        // result * (n - i) is divisible by i + 1 since result is binomial(n, i) at this point
+        result = result * (n - i) as u128 / (i as u128 + 1);
+    }
+    result
+}
+
+/// The logarithms ln(n!) for n = 0..=size. Lets the sampler weigh configurations of long chains whose counts do not fit in any integer
+pub fn log_factorials(size: usize) -> Vec<f64> {
+    let mut log_factorials = vec![0.0; size + 1];
+    for n in 1..=size {
+        log_factorials[n] = log_factorials[n - 1] + (n as f64).ln();
+    }
+    log_factorials
+}
+
+/// ln of ballot_number, negative infinity when there are no such words
+/// * log_factorials: the table from log_factorials, covering at least length + 1
+pub fn log_ballot_number(log_factorials: &[f64], length: usize, unmatched_sites: usize) -> f64 {
+    if unmatched_sites > length || !(length - unmatched_sites).is_multiple_of(2) {
+        return f64::NEG_INFINITY;
+    }
+    let k = (length - unmatched_sites) / 2;
+    let log_binomial = log_factorials[length + 1] - log_factorials[k] - log_factorials[length + 1 - k];
+    ((unmatched_sites + 1) as f64).ln() - ((length + 1) as f64).ln() + log_binomial
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use crate::config::{Dynamics, ExcitedBondConfig, RunConfig, UpdateScheme};
+use crate::site_placer::SitePlacerKind;
+use crate::site::{Site, Spin};
+use crate::spin_chain::SpinChain;
+use crate::{evolve_chain, CHAIN_SIZE};
+
+/// The largest number of living configurations the dense solver will take on.
+/// The fundamental matrix needs (states)^2 floats and (states)^3 operations, at this size about 50 MB and a second.
+/// Spin sector 1 reaches it past chain size 16, which has 2002 states.
+const MAXIMUM_TRANSIENT_STATES: usize = 2500;
+
+/// For every transient state and every triple, the index of the state the move leads to or None when it kills the chain
+type Transitions = Vec<Vec<Option<usize>>>;
+
+/// The exact lifetime of the excited chains of one chain size
+pub struct ExactLifetime {
+    // Number of distinct chains new_excited can generate
+    pub initial_configurations: usize,
+    // Number of living spin configurations reachable from the initial chains
+    pub transient_states: usize,
+    // Expected number of steps until death, averaged over the initial distribution of the site placer
+    pub mean_lifetime: f64,
+}
+
+/// Solves for the exact expected lifetime of the random-index dynamics of evolve_chain.
+/// * excited_bonds: the excited bonds placed in every chain
+/// * chain_size: the size of the chains
+/// * site_placer: the placer the initial chains are drawn with, one whose distribution is known
+///
+/// The dynamics only look at spins, so the living spin configurations reachable from the initial chains are the
+/// transient states of an absorbing Markov chain, and every move that kills the chain leads to the absorbing state.
+/// Every step picks one of the chain_size - 2 triples, so with Q the transition matrix between transient states the
+/// expected number of steps until death is t = (I - Q)^-1 1, the row sums of the fundamental matrix. The step that
+/// kills the chain is counted, just like run_trial counts it.
+///
+/// Fails with the argument to blame when the distribution of the site placer is not known, when the chain has more than
+/// MAXIMUM_TRANSIENT_STATES transient states, which is found out while they are enumerated, or when some state can never
+/// reach death.
+pub fn exact_mean_lifetime(excited_bonds: &ExcitedBondConfig, chain_size: usize, site_placer: SitePlacerKind) -> Result<ExactLifetime, (&'static str, String)> {
+    let initial_distribution = SpinChain::<CHAIN_SIZE>::excited_chain_distribution(&excited_bonds.to_map(), chain_size, site_placer)
+        .ok_or(("site_placer", format!("{:?} draws its chains with probabilities that are not known in closed form", site_placer)))?;
+
+    // Several labelled chains can share a spin configuration, their probabilities add up
+    let mut initial_state_probabilities: HashMap<Vec<Spin>, f64> = HashMap::new();
+    for (chain, probability) in &initial_distribution {
+        let spins: Vec<Spin> = chain.iter().map(|site| site.spin()).collect();
+        *initial_state_probabilities.entry(spins).or_insert(0.0) += probability;
+    }
+
+    let (states, transitions) = transient_states(initial_state_probabilities.keys().cloned().collect(), chain_size)
+        .ok_or(("max_chain_size", format!("chain size {} has more than {} transient states, the most the exact solver handles", chain_size, MAXIMUM_TRANSIENT_STATES)))?;
+    let number_of_states = states.len();
+
+    // (I - Q) t = 1
+    let move_probability = 1.0 / (chain_size - 2) as f64;
+    let mut matrix = vec![vec![0.0; number_of_states]; number_of_states];
+    for (state, state_transitions) in transitions.iter().enumerate() {
+        matrix[state][state] += 1.0;
+        for next_state in state_transitions.iter().flatten() {
+            matrix[state][*next_state] -= move_probability;
+        }
+    }
+    let expected_lifetimes = solve_linear_system(matrix, vec![1.0; number_of_states])
+        .ok_or(("max_chain_size", format!("chain size {} of spin sector {} with {} down-canted and {} mismatch bonds has states that can never reach death, the transition matrix is singular",
+            chain_size, excited_bonds.up_cant, excited_bonds.down_cant, excited_bonds.mismatch)))?;
+
+    let state_indices: HashMap<&Vec<Spin>, usize> = states.iter().enumerate().map(|(index, state)| (state, index)).collect();
+    let mean_lifetime = initial_state_probabilities.iter()
+        .map(|(state, probability)| probability * expected_lifetimes[state_indices[state]])
+        .sum();
+
+    Ok(ExactLifetime { initial_configurations: initial_distribution.len(), transient_states: number_of_states, mean_lifetime })
+}
+
+/// The ways a run was made differently from what the exact solver assumes: the random-sequential update scheme, initial
+/// chains of the same site placer and the same excited bonds. The dynamics themselves may differ, the n-fold way keeps the
+/// lifetime distribution of the random-index dynamics and gillespie lifetimes are compared in time.
+pub fn run_mismatches(config: &RunConfig, excited_bonds: &ExcitedBondConfig, site_placer: SitePlacerKind) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if config.update_scheme != UpdateScheme::RandomSequential && config.dynamics == Dynamics::RandomIndex {
+        mismatches.push(format!("update scheme {:?} instead of RandomSequential", config.update_scheme));
+    }
+    if config.site_placer != site_placer {
+        mismatches.push(format!("site placer {:?} instead of {:?}", config.site_placer, site_placer));
+    }
+    if config.excited_bond_map.down_cant != excited_bonds.down_cant || config.excited_bond_map.mismatch != excited_bonds.mismatch {
+        mismatches.push(format!("{} down-canted and {} mismatch bonds instead of {} and {}",
+            config.excited_bond_map.down_cant, config.excited_bond_map.mismatch, excited_bonds.down_cant, excited_bonds.mismatch));
+    }
+    mismatches
+}
+
+/// Finds every living spin configuration reachable from the initial states.
+/// Returns the states along with, for every state and every triple, the index of the state the move leads to
+/// or None when the move kills the chain. None once there are more than MAXIMUM_TRANSIENT_STATES states.
+fn transient_states(initial_states: Vec<Vec<Spin>>, chain_size: usize) -> Option<(Vec<Vec<Spin>>, Transitions)> {
+    let mut state_indices: HashMap<Vec<Spin>, usize> = HashMap::new();
+    let mut states: Vec<Vec<Spin>> = Vec::new();
+    for state in initial_states {
+        state_indices.insert(state.clone(), states.len());
+        states.push(state);
+    }
+
+    let mut transitions: Transitions = Vec::new();
+    let mut next_unexplored_state = 0;
+    while next_unexplored_state < states.len() {
+        if states.len() > MAXIMUM_TRANSIENT_STATES {
+            return None;
+        }
+        let mut state_transitions: Vec<Option<usize>> = Vec::new();
+        for random_index in 0..chain_size - 2 {
+            let mut chain: Vec<Site> = states[next_unexplored_state].iter().map(|spin| Site::from(*spin)).collect();
+            if evolve_chain(&mut chain, random_index, chain_size) {
+                let next_state: Vec<Spin> = chain.iter().map(|site| site.spin()).collect();
+                let next_index = match state_indices.get(&next_state) {
+                    Some(index) => *index,
+                    None => {
+                        state_indices.insert(next_state.clone(), states.len());
+                        states.push(next_state);
+                        states.len() - 1
+                    }
+                };
+                state_transitions.push(Some(next_index));
+            } else {
+                state_transitions.push(None);
+            }
+        }
+        transitions.push(state_transitions);
+        next_unexplored_state += 1;
+    }
+
+    Some((states, transitions))
+}
+
+/// Solves A x = b with Gaussian elimination and partial pivoting. None when A is singular.
+fn solve_linear_system(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let size = rhs.len();
+
+    for column in 0..size {
+        let pivot_row = (column..size)
+            .max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))
+            .unwrap();
+        if matrix[pivot_row][column] == 0.0 {
+            return None;
+        }
+        matrix.swap(column, pivot_row);
+        rhs.swap(column, pivot_row);
+
+        let (pivot_rows, remaining_rows) = matrix.split_at_mut(column + 1);
+        let pivot = &pivot_rows[column];
+        for (offset, row) in remaining_rows.iter_mut().enumerate() {
+            let factor = row[column] / pivot[column];
+            if factor == 0.0 {
+                continue;
+            }
+            for (entry, pivot_entry) in row[column..].iter_mut().zip(&pivot[column..]) {
+                *entry -= factor * pivot_entry;
+            }
+            rhs[column + 1 + offset] -= factor * rhs[column];
+        }
+    }
+
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let known: f64 = (row + 1..size).map(|column| matrix[row][column] * solution[column]).sum();
+        solution[row] = (rhs[row] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
@@ -1,30 +1,153 @@
-use std::{collections::{BTreeMap, HashMap}, fs::File, io::{Read, Write}};
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, fs::{self, File}, io::{Read, Write}};
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json;
 
+use crate::config::{OutputFormat, RunConfig};
+use crate::observables::ObservableCurves;
+use crate::scaling_fit::ZFit;
+use crate::streaming_statistics::LifetimeSummary;
+use crate::survival::KaplanMeier;
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
-pub struct Run {
-    pub step_count: u128
-}
 
-// Key Value Pair: <spin sector: <chain_length: Vec<Run>>
+// Key Value Pair: <chain_length: Vec<step count>>
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RunData {
-    pub runs: BTreeMap<usize, Vec<u128>>
+    pub runs: BTreeMap<usize, Vec<u128>>,
+    // Continuous-time lifetimes in the time unit of the config, only recorded by the gillespie dynamics.
+    // runs then holds the number of moves that fired.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub times: BTreeMap<usize, Vec<f64>>,
+    // Number of trials of every chain size that a deterministic update scheme never kills. They are left out of runs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub immortal_trials: BTreeMap<usize, u64>,
+    // Trials still alive at the step cap of the config, stored apart from runs as right-censored lifetimes:
+    // the chain lived for longer than the stored step count, and longer than the stored time for the gillespie dynamics
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub censored_runs: BTreeMap<usize, Vec<u128>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub censored_times: BTreeMap<usize, Vec<f64>>,
+    // Running statistics of the lifetimes of every chain size, kept instead of runs by the summary lifetime storage
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub summaries: BTreeMap<usize, LifetimeSummary>,
+    // Observables summed over the trials of every chain size, see the observables section of the config
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub observables: BTreeMap<usize, ObservableCurves>,
+    // The configuration the runs were produced with. Older run files do not have one.
+    #[serde(default)]
+    pub config: Option<RunConfig>,
+    // The seed every trial's random number generator was derived from
+    #[serde(default)]
+    pub master_seed: Option<u64>,
+    // The spin sector every chain of the file was excited in. Older run files only have it in their file name
+    #[serde(default)]
+    pub spin_sector: Option<usize>,
+    // The run files merged into this one, empty for a file written by a single run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<RunSource>,
+}
+
+/// One run file merged into another, see the merge command
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunSource {
+    pub file_name: String,
+    pub master_seed: Option<u64>,
+    // Number of trials the file held for every chain size, censored and immortal ones included
+    pub trials: BTreeMap<usize, u64>,
+    // Config parameters that differed from the first file merged, only merged anyway when mismatches were allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<String>,
 }
 
 impl RunData {
-    pub fn new() -> RunData {
-        let mut runs: BTreeMap<usize, Vec<u128>> = BTreeMap::new();
-        let run_data = RunData{runs};
-        run_data
+    pub fn new(config: &RunConfig, spin_sector: usize) -> RunData {
+        let runs: BTreeMap<usize, Vec<u128>> = BTreeMap::new();
+        RunData{runs, times: BTreeMap::new(), immortal_trials: BTreeMap::new(), censored_runs: BTreeMap::new(), censored_times: BTreeMap::new(), summaries: BTreeMap::new(), observables: BTreeMap::new(), config: Some(config.clone()), master_seed: config.rng.seed, spin_sector: Some(spin_sector), sources: Vec::new()}
+    }
+
+    /// Every chain size with lifetimes, censored ones included
+    pub fn chain_sizes(&self) -> BTreeSet<usize> {
+        self.runs.keys().chain(self.censored_runs.keys()).chain(self.summaries.keys()).copied().collect()
+    }
+
+    /// The lifetimes of the trials of a chain size that died and of the censored ones, in time for the gillespie dynamics
+    /// and in steps otherwise
+    pub fn lifetime_samples(&self, chain_size: usize) -> (Vec<f64>, Vec<f64>) {
+        if !self.times.is_empty() || !self.censored_times.is_empty() {
+            let lifetimes = self.times.get(&chain_size).cloned().unwrap_or_default();
+            let censored_lifetimes = self.censored_times.get(&chain_size).cloned().unwrap_or_default();
+            return (lifetimes, censored_lifetimes);
+        }
+        let to_f64 = |step_counts: Option<&Vec<u128>>| step_counts.map(|step_counts| step_counts.iter().map(|step_count| *step_count as f64).collect()).unwrap_or_default();
+        (to_f64(self.runs.get(&chain_size)), to_f64(self.censored_runs.get(&chain_size)))
+    }
+
+    /// Number of trials of every chain size, censored and immortal ones included
+    pub fn trial_counts(&self) -> BTreeMap<usize, u64> {
+        let mut trials: BTreeMap<usize, u64> = BTreeMap::new();
+        for (chain_size, runs) in self.runs.iter().chain(&self.censored_runs) {
+            *trials.entry(*chain_size).or_insert(0) += runs.len() as u64;
+        }
+        for (chain_size, summary) in &self.summaries {
+            *trials.entry(*chain_size).or_insert(0) += summary.lifetimes().count + summary.censored_trials;
+        }
+        for (chain_size, immortal_trials) in &self.immortal_trials {
+            *trials.entry(*chain_size).or_insert(0) += immortal_trials;
+        }
+        trials
+    }
+
+    /// Whether the lifetimes were measured in continuous time by the gillespie dynamics rather than in steps
+    pub fn is_continuous_time(&self) -> bool {
+        !self.times.is_empty() || self.summaries.values().any(|summary| summary.times.is_some())
+    }
+
+    /// The mean lifetime of a chain size and its standard error, in time for the gillespie dynamics and in steps otherwise.
+    /// The Kaplan-Meier mean when some trials were censored, the mean of the uncensored trials for summaries.
+    /// None with fewer than two lifetimes
+    pub fn mean_lifetime(&self, chain_size: usize) -> Option<(f64, f64)> {
+        if let Some(summary) = self.summaries.get(&chain_size) {
+            let lifetimes = summary.lifetimes();
+            return (lifetimes.count > 1).then(|| (lifetimes.mean, lifetimes.standard_error()));
+        }
+        let (lifetimes, censored_lifetimes) = self.lifetime_samples(chain_size);
+        if lifetimes.len() + censored_lifetimes.len() < 2 {
+            return None;
+        }
+        if !censored_lifetimes.is_empty() {
+            let mean = KaplanMeier::new(&lifetimes, &censored_lifetimes).mean();
+            return Some((mean.mean, mean.standard_error));
+        }
+        let number_of_trials = lifetimes.len() as f64;
+        let mean = lifetimes.iter().sum::<f64>() / number_of_trials;
+        let variance = lifetimes.iter().map(|lifetime| (lifetime - mean).powi(2)).sum::<f64>() / (number_of_trials - 1.0);
+        Some((mean, (variance / number_of_trials).sqrt()))
     }
 }
+/// Progress of an interrupted simulation. Since every trial is seeded from the master seed in the config
+/// and its own index, the next trial to run is all the random number generator state there is to save.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Checkpoint {
+    pub config: RunConfig,
+    // The spin sector being simulated when the checkpoint was written
+    pub spin_sector: usize,
+    pub next_chain_size: usize,
+    pub next_trial: u64,
+    // Every trial of the spin sector completed so far
+    pub run_data: RunData,
+    // Length in bytes of the trajectory file of next_chain_size, None when no trajectories are written or next_trial is 0.
+    // A resumed run cuts the file back to it so the trials after the checkpoint are not written twice
+    #[serde(default)]
+    pub trajectory_length: Option<u64>,
+}
+
+/// Dynamical exponents fitted by the fit-z command, keyed by spin sector
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ZData{
-    pub z_data: HashMap<usize, Vec<f64>>
+    // [endpoint z, average z] of scaling_fit::slope_estimates, the power law fit and its errors are the first of fits
+    pub z_data: HashMap<usize, Vec<f64>>,
+    // Every fit of the spin sector, with and without corrections to scaling
+    #[serde(default)]
+    pub fits: HashMap<usize, Vec<ZFit>>,
 }
 
 pub fn load_data<T: DeserializeOwned>(file_name: String) -> T {
@@ -41,4 +164,37 @@ pub fn save_data<T: Serialize>(file_name: String, data: &T) {
     let mut file = File::create(file_name).unwrap();
     let data = serde_json::to_string(data).unwrap();
     file.write_all(data.as_bytes()).unwrap();
-}
\ No newline at end of file
+}
+pub fn save_data_formatted<T: Serialize>(file_name: String, data: &T, output_format: OutputFormat) {
+    match output_format {
+        OutputFormat::Json => save_data(file_name, data),
+        OutputFormat::JsonPretty => {
+            let mut file = File::create(file_name).unwrap();
+            let data = serde_json::to_string_pretty(data).unwrap();
+            file.write_all(data.as_bytes()).unwrap();
+        }
+    }
+}
+
+pub fn checkpoint_file_name(storage_directory: &str) -> String {
+    format!("{}/checkpoint.json", storage_directory)
+}
+
+/// Writes the checkpoint to a temporary file first and then moves it into place,
+/// so a run killed while checkpointing still leaves the previous checkpoint intact.
+pub fn save_checkpoint(file_name: &str, checkpoint: &Checkpoint) {
+    let temporary_file_name = format!("{}.tmp", file_name);
+    save_data(temporary_file_name.clone(), checkpoint);
+    fs::rename(temporary_file_name, file_name).unwrap();
+}
+
+pub fn load_checkpoint(file_name: &str) -> Result<Checkpoint, String> {
+    let contents = fs::read(file_name).map_err(|error| format!("could not read checkpoint {}: {}", file_name, error))?;
+    serde_json::from_slice(&contents).map_err(|error| format!("could not parse checkpoint {}: {}", file_name, error))
+}
+
+pub fn remove_checkpoint(file_name: &str) {
+    if fs::metadata(file_name).is_ok() {
+        fs::remove_file(file_name).unwrap();
+    }
+}
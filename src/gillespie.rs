@@ -0,0 +1,57 @@
+use rand::Rng;
+
+use crate::n_fold_way::ActiveMoves;
+use crate::spin_chain::SpinChain;
+use crate::recorder::TrialRecorder;
+use crate::simulation::Lifetime;
+
+/// Evolves the chain in continuous time until it dies, every triple attempting its Fredkin move at the given rate.
+/// Returns the number of moves that fired, the fatal one included, along with the time the chain died at.
+/// * move_rate: the rate every triple fires at. A rate of 1 measures time in sweeps of chain_size - 2 attempted moves
+/// * max_moves: stops the chain after this many moves if it is still alive, its lifetime then being censored at the time
+///   of the last move
+/// * relabel_excited_sites: re-label the excited sites after every move, see SpinChain::relabel_excited_sites
+/// * recorder: follows the chain, a step being a move
+///
+/// Triples whose move does nothing never change the chain, so with a active triples the time until the next move is
+/// exponential with rate a * move_rate and the move that fires is uniform over the active triples.
+pub fn evolve_until_death<const N: usize, R: Rng>(spin_chain: &mut SpinChain<N>, chain_size: usize, move_rate: f64, max_moves: Option<u128>, relabel_excited_sites: bool, recorder: &mut impl TrialRecorder, rng: &mut R) -> Lifetime {
+    let mut active_moves = ActiveMoves::new(&spin_chain.chain, chain_size);
+
+    let mut move_count: u128 = 0;
+    let mut time = 0.0;
+    let mut is_alive = true;
+    recorder.record(0, 0, Some(0.0), &spin_chain.chain);
+
+    while is_alive {
+        // every chain that can die has an active triple, the one that kills it
+        let total_rate = active_moves.len() as f64 * move_rate;
+        let waiting_time = exponential_waiting_time(total_rate, rng);
+        recorder.record_idle_time(time, time + waiting_time, &spin_chain.chain);
+        time += waiting_time;
+        move_count += 1;
+
+        let random_index = active_moves.choose(rng);
+        is_alive = active_moves.make_move(&mut spin_chain.chain, random_index, chain_size);
+        if is_alive {
+            if relabel_excited_sites {
+                spin_chain.relabel_excited_sites();
+            }
+            recorder.record(move_count, move_count, Some(time), &spin_chain.chain);
+            if max_moves == Some(move_count) {
+                recorder.finish(move_count, move_count, Some(time), &spin_chain.chain);
+                return Lifetime { step_count: move_count, time: Some(time), immortal: false, censored: true };
+            }
+        }
+    }
+    // the fatal move did not change the chain
+    recorder.finish(move_count, move_count - 1, Some(time), &spin_chain.chain);
+    Lifetime { step_count: move_count, time: Some(time), immortal: false, censored: false }
+}
+
+/// Draws the time until the first event of a Poisson process with the given rate
+pub fn exponential_waiting_time<R: Rng>(rate: f64, rng: &mut R) -> f64 {
+    // 1 - u is in (0, 1] so the logarithm stays finite
+    let uniform: f64 = 1.0 - rng.gen_range(0.0f64..1f64);
+    -uniform.ln() / rate
+}
@@ -0,0 +1,155 @@
+use crate::streaming_statistics::{LifetimeSummary, LogHistogram};
+use crate::survival::KaplanMeier;
+
+/// One logarithmic bin of a lifetime distribution, holding the lifetimes in [lower, upper). Bin k of b bins per decade
+/// spans [10^(k/b), 10^((k+1)/b)), the binning of LogHistogram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistributionBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub deaths: u64,
+    // Deaths per trial per unit of lifetime, per step for step counts
+    pub density: f64,
+    // Fraction of the trials that outlived the bin, S(upper) = P(lifetime >= upper)
+    pub survival: f64,
+    // Deaths per unit of lifetime lived in the bin by the trials that reached it, per step for step counts
+    pub hazard: f64,
+    // Poisson standard error of the hazard, sqrt(deaths) over the exposure
+    pub hazard_standard_error: f64,
+}
+
+/// The fit of S(t) ~ exp(-rate t) to the lifetimes past the start of the tail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TailFit {
+    pub start: f64,
+    // Fraction of the trials that outlived the start of the tail
+    pub survival: f64,
+    // Deaths after the start of the tail, the number of lifetimes the rate rests on
+    pub deaths: u64,
+    // Decay rate of the survival per unit of lifetime, per step for step counts
+    pub rate: f64,
+    pub standard_error: f64,
+}
+
+/// The distribution of the lifetimes of one chain size
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifetimeDistribution {
+    pub bins: Vec<DistributionBin>,
+    // None for summaries, which do not keep the lifetimes the fit needs, and for tails with fewer than two deaths
+    pub tail_fit: Option<TailFit>,
+}
+
+/// The distribution of lifetimes kept trial by trial. Censored lifetimes count towards the survival and the time at
+/// risk but not the deaths, so they do not bias the hazard.
+/// * discrete: the lifetimes are step counts, densities and hazards are then per step and bins without an integer in
+///   them are left out
+/// * tail_fraction: the tail fit starts at the first lifetime the survival drops to this fraction at
+///
+/// For step counts the tail is geometric, with a probability p of dying at every step that is estimated as the deaths
+/// over the steps lived in the tail, and the rate is -ln(1 - p). Either way the rate estimates the slowest decay of the
+/// dynamics restricted to the living chains, the smallest eigenvalue of minus the generator for the gillespie dynamics
+/// and -ln of the largest eigenvalue of the transition matrix for the discrete ones, so it can be compared with the
+/// spectral gap.
+pub fn lifetime_distribution(lifetimes: &[f64], censored_lifetimes: &[f64], bins_per_decade: u32, discrete: bool, tail_fraction: f64) -> LifetimeDistribution {
+    let mut histogram = LogHistogram::new(bins_per_decade);
+    for lifetime in lifetimes {
+        histogram.add(*lifetime);
+    }
+    let kaplan_meier = KaplanMeier::new(lifetimes, censored_lifetimes);
+    let trials = (lifetimes.len() + censored_lifetimes.len()) as f64;
+
+    let positive_lifetimes = || lifetimes.iter().chain(censored_lifetimes).filter(|lifetime| **lifetime > 0.0);
+    let bin_of = |lifetime: &f64| (lifetime.log10() * bins_per_decade as f64).floor() as i32;
+    let (Some(first_bin), Some(last_bin)) = (positive_lifetimes().map(bin_of).min(), positive_lifetimes().map(bin_of).max()) else {
+        return LifetimeDistribution { bins: Vec::new(), tail_fit: None };
+    };
+
+    let bins = (first_bin..=last_bin)
+        .filter_map(|bin| {
+            let (lower, upper) = bin_edges(bin, bins_per_decade);
+            let width = bin_width(lower, upper, discrete);
+            if width == 0.0 {
+                return None;
+            }
+            let deaths = histogram.counts.get(&bin).copied().unwrap_or(0);
+            let exposure: f64 = lifetimes.iter().chain(censored_lifetimes).map(|lifetime| time_at_risk(*lifetime, lower, upper, discrete)).sum();
+            let hazard = if exposure > 0.0 { deaths as f64 / exposure } else { 0.0 };
+            let hazard_standard_error = if exposure > 0.0 { (deaths as f64).sqrt() / exposure } else { 0.0 };
+            Some(DistributionBin { lower, upper, deaths, density: deaths as f64 / (trials * width), survival: kaplan_meier.survival_before(upper), hazard, hazard_standard_error })
+        })
+        .collect();
+
+    let tail_fit = kaplan_meier.points.iter().find(|point| point.survival <= tail_fraction).and_then(|start| {
+        let deaths = lifetimes.iter().filter(|lifetime| **lifetime > start.time).count() as u64;
+        let exposure: f64 = lifetimes.iter().chain(censored_lifetimes).map(|lifetime| (lifetime - start.time).max(0.0)).sum();
+        if deaths < 2 {
+            return None;
+        }
+        let (rate, standard_error) = if discrete {
+            let death_probability = deaths as f64 / exposure;
+            if death_probability >= 1.0 {
+                return None;
+            }
+            (-(1.0 - death_probability).ln(), death_probability / (deaths as f64 * (1.0 - death_probability)).sqrt())
+        } else {
+            let rate = deaths as f64 / exposure;
+            (rate, rate / (deaths as f64).sqrt())
+        };
+        Some(TailFit { start: start.time, survival: start.survival, deaths, rate, standard_error })
+    });
+
+    LifetimeDistribution { bins, tail_fit }
+}
+
+/// The distribution of the lifetimes of a summary, in the bins of its histogram. Without the lifetimes themselves the
+/// hazard uses the actuarial estimate of the time at risk, every death of a bin living through half of it. Censored
+/// trials are taken to outlive every bin.
+pub fn summary_distribution(summary: &LifetimeSummary, discrete: bool) -> LifetimeDistribution {
+    let lifetimes = summary.lifetimes();
+    let histogram = &lifetimes.histogram;
+    let trials = (lifetimes.count + summary.censored_trials) as f64;
+    let mut at_risk = trials - histogram.non_positive as f64;
+    let mut bins = Vec::new();
+    if let (Some(first_bin), Some(last_bin)) = (histogram.counts.keys().next(), histogram.counts.keys().next_back()) {
+        for bin in *first_bin..=*last_bin {
+            let (lower, upper) = bin_edges(bin, histogram.bins_per_decade);
+            let width = bin_width(lower, upper, discrete);
+            let deaths = histogram.counts.get(&bin).copied().unwrap_or(0);
+            if width == 0.0 {
+                continue;
+            }
+            let exposure = width * (at_risk - deaths as f64 / 2.0);
+            at_risk -= deaths as f64;
+            bins.push(DistributionBin {
+                lower,
+                upper,
+                deaths,
+                density: deaths as f64 / (trials * width),
+                survival: at_risk / trials,
+                hazard: deaths as f64 / exposure,
+                hazard_standard_error: (deaths as f64).sqrt() / exposure,
+            });
+        }
+    }
+    LifetimeDistribution { bins, tail_fit: None }
+}
+
+fn bin_edges(bin: i32, bins_per_decade: u32) -> (f64, f64) {
+    let edge = |bin: i32| 10f64.powf(bin as f64 / bins_per_decade as f64);
+    (edge(bin), edge(bin + 1))
+}
+
+/// The length of [lower, upper), or the number of step counts in it
+fn bin_width(lower: f64, upper: f64, discrete: bool) -> f64 {
+    if discrete { upper.ceil() - lower.ceil() } else { upper - lower }
+}
+
+/// The part of [lower, upper) a trial with the given lifetime was alive for. A trial that died at step n was at risk
+/// at steps 1 to n.
+fn time_at_risk(lifetime: f64, lower: f64, upper: f64, discrete: bool) -> f64 {
+    if discrete {
+        (upper.ceil().min(lifetime + 1.0) - lower.ceil()).max(0.0)
+    } else {
+        (upper.min(lifetime) - lower).max(0.0)
+    }
+}
@@ -0,0 +1,182 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::file_utils::RunData;
+use crate::survival::KaplanMeier;
+
+/// How the analyze command writes the lifetime estimates
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateFormat {
+    /// A table per run file along with the rest of the summary
+    #[default]
+    Text,
+    /// One comma separated table of every run file, with a header line
+    Csv,
+    /// A JSON array of the estimates of every run file
+    Json,
+}
+
+/// How the confidence intervals of an estimate were found
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntervalMethod {
+    /// Percentiles of the estimate over resamples of the trials
+    Bootstrap,
+    /// The normal approximation around the standard error, for summary runs that do not keep the trials
+    Normal,
+}
+
+/// The mean lifetime of one chain size with its uncertainties, in time for the gillespie dynamics and in steps otherwise
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LifetimeEstimate {
+    pub run_file: String,
+    pub spin_sector: Option<usize>,
+    pub chain_size: usize,
+    // "steps", or the time unit of the gillespie dynamics
+    pub unit: String,
+    pub trials: u64,
+    pub censored: u64,
+    // The Kaplan-Meier mean and median when some trials were censored
+    pub mean: f64,
+    pub standard_error: f64,
+    pub median: Option<f64>,
+    pub mean_interval: (f64, f64),
+    pub log_mean: f64,
+    pub log_mean_standard_error: f64,
+    pub log_mean_interval: (f64, f64),
+    pub interval_method: IntervalMethod,
+}
+
+/// Estimates the mean lifetime of every chain size of a run file. Lifetimes kept trial by trial get percentile bootstrap
+/// intervals, resampling the trials of the chain size with replacement, while summaries fall back on the normal
+/// approximation. Chain sizes with fewer than two trials are left out.
+/// * bootstrap_samples: number of resamples, 0 uses the normal approximation for every chain size
+/// * confidence_level: the probability every interval is meant to cover, between 0 and 1
+pub fn estimate_lifetimes<R: Rng>(run_file: &str, run_data: &RunData, bootstrap_samples: usize, confidence_level: f64, rng: &mut R) -> Vec<LifetimeEstimate> {
+    let unit = match &run_data.config {
+        Some(config) if run_data.is_continuous_time() => format!("{:?}", config.continuous_time.time_unit).to_lowercase(),
+        _ if run_data.is_continuous_time() => "time".to_string(),
+        _ => "steps".to_string(),
+    };
+    let mut estimates = Vec::new();
+    for chain_size in run_data.chain_sizes() {
+        let Some((mean, standard_error)) = run_data.mean_lifetime(chain_size) else {
+            continue;
+        };
+        let (trials, censored, median, bootstrap_means) = match run_data.summaries.get(&chain_size) {
+            Some(summary) => {
+                let lifetimes = summary.lifetimes();
+                (lifetimes.count + summary.censored_trials, summary.censored_trials, lifetimes.quantiles.quantile(0.5), Vec::new())
+            }
+            None => {
+                let (lifetimes, censored_lifetimes) = run_data.lifetime_samples(chain_size);
+                let median = if censored_lifetimes.is_empty() { median(&lifetimes) } else { KaplanMeier::new(&lifetimes, &censored_lifetimes).median() };
+                let bootstrap_means: Vec<f64> = (0..bootstrap_samples).map(|_| resampled_mean(&lifetimes, &censored_lifetimes, rng)).collect();
+                ((lifetimes.len() + censored_lifetimes.len()) as u64, censored_lifetimes.len() as u64, median, bootstrap_means)
+            }
+        };
+
+        let (mean_interval, log_mean_standard_error, log_mean_interval, interval_method) = if bootstrap_means.is_empty() {
+            // delta method for the logarithm
+            let critical_value = standard_normal_quantile(0.5 + confidence_level / 2.0);
+            let log_mean_standard_error = standard_error / mean;
+            let mean_interval = (mean - critical_value * standard_error, mean + critical_value * standard_error);
+            let log_mean_interval = (mean.ln() - critical_value * log_mean_standard_error, mean.ln() + critical_value * log_mean_standard_error);
+            (mean_interval, log_mean_standard_error, log_mean_interval, IntervalMethod::Normal)
+        } else {
+            let mut bootstrap_means = bootstrap_means;
+            let mut log_means: Vec<f64> = bootstrap_means.iter().map(|mean| mean.ln()).collect();
+            let count = log_means.len() as f64;
+            let mean_log_mean = log_means.iter().sum::<f64>() / count;
+            let log_mean_standard_error = (log_means.iter().map(|log_mean| (log_mean - mean_log_mean).powi(2)).sum::<f64>() / (count - 1.0).max(1.0)).sqrt();
+            (percentile_interval(&mut bootstrap_means, confidence_level), log_mean_standard_error, percentile_interval(&mut log_means, confidence_level), IntervalMethod::Bootstrap)
+        };
+        let estimate = LifetimeEstimate {
+            run_file: run_file.to_string(),
+            spin_sector: run_data.spin_sector,
+            chain_size,
+            unit: unit.clone(),
+            trials,
+            censored,
+            mean,
+            standard_error,
+            median,
+            mean_interval,
+            log_mean: mean.ln(),
+            log_mean_standard_error,
+            log_mean_interval,
+            interval_method,
+        };
+        estimates.push(estimate);
+    }
+    estimates
+}
+
+/// The mean of a resample of the trials, the Kaplan-Meier mean when some of them were censored
+fn resampled_mean<R: Rng>(lifetimes: &[f64], censored_lifetimes: &[f64], rng: &mut R) -> f64 {
+    let trials = lifetimes.len() + censored_lifetimes.len();
+    if censored_lifetimes.is_empty() {
+        return (0..trials).map(|_| lifetimes[rng.gen_range(0..trials)]).sum::<f64>() / trials as f64;
+    }
+    let mut resampled_lifetimes = Vec::new();
+    let mut resampled_censored_lifetimes = Vec::new();
+    for _ in 0..trials {
+        let trial = rng.gen_range(0..trials);
+        match lifetimes.get(trial) {
+            Some(lifetime) => resampled_lifetimes.push(*lifetime),
+            None => resampled_censored_lifetimes.push(censored_lifetimes[trial - lifetimes.len()]),
+        }
+    }
+    KaplanMeier::new(&resampled_lifetimes, &resampled_censored_lifetimes).mean().mean
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted_values = values.to_vec();
+    sorted_values.sort_by(f64::total_cmp);
+    let middle = sorted_values.len() / 2;
+    if sorted_values.len().is_multiple_of(2) {
+        Some((sorted_values[middle - 1] + sorted_values[middle]) / 2.0)
+    } else {
+        Some(sorted_values[middle])
+    }
+}
+
+/// The central interval holding the confidence level of the values, by linear interpolation between order statistics
+pub fn percentile_interval(values: &mut [f64], confidence_level: f64) -> (f64, f64) {
+    values.sort_by(f64::total_cmp);
+    let percentile = |quantile: f64| {
+        let position = quantile * (values.len() - 1) as f64;
+        let lower = position.floor() as usize;
+        let upper = position.ceil() as usize;
+        values[lower] + (values[upper] - values[lower]) * (position - lower as f64)
+    };
+    (percentile((1.0 - confidence_level) / 2.0), percentile((1.0 + confidence_level) / 2.0))
+}
+
+/// The inverse of the standard normal distribution function, by Acklam's rational approximation (relative error below
+/// 1.2e-9)
+pub fn standard_normal_quantile(probability: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2, 1.38357751867269e2, -3.066479806614716e1, 2.506628277459239];
+    const B: [f64; 5] = [-5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2, 6.680131188771972e1, -1.328068155288572e1];
+    const C: [f64; 6] = [-7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838, -2.549732539343734, 4.374664141464968, 2.938163982698783];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416];
+    const LOWER_TAIL: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        let q = (-2.0 * q.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if probability < LOWER_TAIL {
+        tail(probability)
+    } else if probability > 1.0 - LOWER_TAIL {
+        -tail(1.0 - probability)
+    } else {
+        let q = probability - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
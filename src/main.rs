@@ -1,16 +1,45 @@
 use std::collections::HashMap;
-use std::env;
-use std::time::Instant;
 use rand::Rng;
 use rand_mt::Mt64;
 use spin_chain::SpinChain;
-use rand::prelude::ThreadRng;
+use site::{Site, Spin};
+use cli::{Command, EnumerateArgs, ExactArgs, InspectArgs};
+use config::{ExcitedBondConfig, RunConfig};
+use rng_utils::TrialRng;
+use simulation::run_trial;
 use file_utils::RunData;
-use log::{info, LevelFilter};
+use log::LevelFilter;
 mod spin_chain;
-mod calculation_utils;
+mod site;
+mod site_placer;
 mod file_utils;
 mod data_utils;
+mod cli;
+mod config;
+mod rng_utils;
+mod simulation;
+mod n_fold_way;
+mod gillespie;
+mod update_scheme;
+mod trajectory;
+mod recorder;
+mod observables;
+mod survival;
+mod streaming_statistics;
+mod scaling_fit;
+mod lifetime_estimates;
+mod lifetime_distribution;
+mod data_collapse;
+mod svg_plot;
+mod run_merge;
+mod exact_solver;
+mod enumeration;
+#[cfg(test)]
+mod sampler_tests;
+#[cfg(test)]
+mod dynamics_tests;
+#[cfg(test)]
+mod analysis_tests;
 
 const CHAIN_SIZE:usize = 42;
 
@@ -18,187 +47,227 @@ const CHAIN_SIZE:usize = 42;
 
 fn main() {
 
-    // Args: storage directory, # of trials, min chain size, max chain size, min spin sector, max spin sector
-    // Local storage directory: ./data/runs
-    let args: Vec<String> = env::args().collect();
+    let cli = cli::parse();
 
-    let storage_directory: &String = args.get(1).unwrap();
-
-    let start = Instant::now();
+    simple_logging::log_to_file("fredkin_logs.log", LevelFilter::Info).unwrap();
 
-    println!("start time: {start:?}");
+    match cli.command {
+        Command::Simulate(args) => {
+            if args.resume {
+                let checkpoint = cli::resume_checkpoint(&args);
+                simulation::run_simulation(&checkpoint.config.clone(), Some(checkpoint));
+            } else {
+                simulation::run_simulation(&cli::simulate_config(&args), None);
+            }
+        }
+        Command::Analyze(args) => data_utils::print_lifetime_summary(&args),
+        Command::Inspect(args) => inspect_chain(&args),
+        Command::Enumerate(args) => enumerate_chains(&args),
+        Command::Exact(args) => print_exact_lifetimes(&args),
+        Command::FitZ(args) => data_utils::fit_dynamical_exponents(&args),
+        Command::Collapse(args) => data_utils::collapse_survival_curves(&args),
+        Command::Plot(args) => data_utils::plot_lifetimes(&args),
+        Command::Merge(args) => data_utils::merge_run_files(&args),
+    }
+}
 
-    simple_logging::log_to_file("fredkin_logs.log", LevelFilter::Info).unwrap();
+/// Generates a single excited chain of the requested size and spin sector and prints it.
+/// When a master seed is given the chain is the one generated for that trial of a simulate run,
+/// and --evolve replays the trial to report its lifetime.
+fn inspect_chain(args: &InspectArgs) {
+    let excited_bond_map = ExcitedBondConfig { up_cant: args.spin_sector, down_cant: args.down_cant, mismatch: args.mismatch }.to_map();
+    let seed = match args.seed {
+        Some(master_seed) => rng_utils::trial_seed(master_seed, args.spin_sector, args.chain_size, args.trial),
+        None => rand::thread_rng().gen(),
+    };
+    let mut rng = TrialRng::new(args.rng, seed);
 
-    let mut excited_bond_map:HashMap<usize, usize> = HashMap::<usize, usize>::new();
-    excited_bond_map.insert(0, 1);
-    excited_bond_map.insert(1,0);
-    excited_bond_map.insert(2,0);
+    if args.evolve {
+        let config = RunConfig { site_placer: args.site_placer, dynamics: args.dynamics, update_scheme: args.update_scheme, ..RunConfig::default() };
+        let lifetime = run_trial(&config, &excited_bond_map, args.chain_size, &mut rng);
+        // Regenerate the starting chain from the same stream so it can be printed alongside the lifetime
+        rng = TrialRng::new(args.rng, seed);
+        let spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited_with_placer(&excited_bond_map, args.chain_size, &args.site_placer, &mut rng);
+        print_chain(&spin_chain.chain);
+        match lifetime.time {
+            Some(time) => println!("moves: {}, time: {} sweeps", lifetime.step_count, time),
+            None if lifetime.immortal => println!("never dies, returns to its starting spins after {} steps", lifetime.step_count),
+            None => println!("step count: {}", lifetime.step_count),
+        }
+    } else {
+        let spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited_with_placer(&excited_bond_map, args.chain_size, &args.site_placer, &mut rng);
+        println!("{:?}", spin_chain.chain.iter().map(|site| i8::from(*site)).collect::<Vec<i8>>());
+        print_chain(&spin_chain.chain);
+    }
+}
 
-    let mut spin_sector = 0;
+/// Lists every configuration of the requested chain along with the probability the chosen site placer generates it with.
+/// With --samples the chains are also drawn with the chosen site placer and the number of times each configuration was
+/// generated is printed next to it.
+fn enumerate_chains(args: &EnumerateArgs) {
+    let excited_bonds = ExcitedBondConfig { up_cant: args.spin_sector, down_cant: args.down_cant, mismatch: args.mismatch };
+    let configurations = enumeration::excited_configurations(&excited_bonds, args.chain_size);
+    println!("{} configurations, expected {}", configurations.len(), enumeration::number_of_excited_configurations(&excited_bonds, args.chain_size));
 
-    for entry in &excited_bond_map {
-        spin_sector += entry.1;
+    if args.spin_sector + args.down_cant + args.mismatch == 0 {
+        for configuration in &configurations {
+            print_chain(configuration);
+        }
+        return;
     }
 
-        
-    let number_of_trials: usize = args.get(2).unwrap().parse().unwrap();
-    let min_chain_size: usize = args.get(3).unwrap_or(&"0".to_string()).parse().unwrap();
-    let max_size: usize = args.get(4).unwrap().parse().unwrap();
-    
-    let spin_sector_min: usize = args.get(5).unwrap().parse().unwrap();
-    let spin_sector_max: usize = args.get(6).unwrap().parse().unwrap(); 
-    
-    println!("Running chains from {min_chain_size} to size {max_size} with each chain size running {number_of_trials} times and spin sector from {spin_sector_min} to {spin_sector_max}");
-    let mut rng_seed: ThreadRng = rand::thread_rng();
-    let mut rng = Mt64::new(rng_seed.gen());
-    for current_spin_sector in spin_sector_min..=spin_sector_max {
-        let mut run_data: RunData = RunData::new();
-        info!("spin sector: {current_spin_sector}");
-        println!("spin sector: {current_spin_sector}");
-        excited_bond_map.insert(0, current_spin_sector);
-        let mut current_size: usize;
-        let min_chain_size_label: usize;
-        let hard_limit = (2 * current_spin_sector) + 2;
-        if min_chain_size < hard_limit {
-            current_size = hard_limit;
-            min_chain_size_label = hard_limit;
-        } else {
-            current_size = min_chain_size;
-            min_chain_size_label = min_chain_size;
+    let excited_bond_map = excited_bonds.to_map();
+    // placers whose distribution is not known in closed form print "unknown"
+    let sampler_probabilities: Option<HashMap<Vec<Site>, f64>> = SpinChain::<CHAIN_SIZE>::excited_chain_distribution(&excited_bond_map, args.chain_size, args.site_placer)
+        .map(|distribution| distribution.into_iter().collect());
+
+    let mut generated_counts: HashMap<u64, u64> = HashMap::new();
+    if let Some(samples) = args.samples {
+        let mut rng = Mt64::new(rand::thread_rng().gen());
+        for _i in 0..samples {
+            let spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited_with_placer(&excited_bond_map, args.chain_size, &args.site_placer, &mut rng);
+            *generated_counts.entry(spin_chain.chain_hash).or_insert(0) += 1;
         }
-        
-        while current_size <= max_size {
-            for _j in 0..number_of_trials {
-                let mut is_alive = true;
-                // info!("generating spin chain");
-                let mut spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited(&excited_bond_map, current_size, &mut rng);
-                //print_chain(&spin_chain.chain);
-            
-                let mut step_count = 0;
-            
-                while is_alive {
-                    let random_index = rng.gen_range(0..current_size - 2);
-                    is_alive = evolve_chain(&mut spin_chain.chain, random_index, current_size);
-                    step_count += 1;
-                }
-                update_run_data(&mut run_data, current_size, step_count); 
+        println!("sampler probability, uniform probability, generated count: chain");
+    } else {
+        println!("sampler probability, uniform probability: chain");
+    }
+
+    let uniform_probability = 1.0 / configurations.len() as f64;
+    for configuration in &configurations {
+        let sampler_probability = sampler_probabilities.as_ref()
+            .map_or("unknown".to_string(), |probabilities| probabilities.get(configuration).copied().unwrap_or(0.0).to_string());
+        match args.samples {
+            Some(_) => {
+                let generated_count = generated_counts.remove(&spin_chain::chain_hash(configuration)).unwrap_or(0);
+                print!("{}, {}, {}: ", sampler_probability, uniform_probability, generated_count);
             }
-            println!("completed spin chain of size {current_size}");
-            info!("completed spin chain of size {current_size}");
-            current_size+=2;
+            None => print!("{}, {}: ", sampler_probability, uniform_probability),
         }
-        let directory_string = format!("{}/run_ss_{}_cs_{}_{}.json", storage_directory, current_spin_sector, min_chain_size_label, max_size);
-        file_utils::save_data(directory_string, &run_data);
+        print_chain(configuration);
     }
-}
-
-// fn print_chain(chain: &Vec<i8>) {
-
-//     for spin in chain {
-//         if *spin == 1 {
-//             print!("(")
-//         } else if *spin == 2 {
-//             print!("[")
-//         } else if *spin == -1 {
-//             print!(")")
-//         } else {
-//             print!("?")
-//         }
-//     }
-//     println!();
 
-// }
+    if !generated_counts.is_empty() {
+        println!("{} chains were generated that are not valid configurations", generated_counts.values().sum::<u64>());
+    }
+}
 
-fn update_run_data(run_data: &mut RunData, chain_size: usize, steps: u128) {
-    let contains_chain_size = run_data.runs.contains_key(&chain_size);
-    if contains_chain_size {
-        run_data.runs.get_mut(&chain_size).unwrap().push(steps);
+/// Solves for the exact mean lifetime of every chain size in the requested range. When a run file is given the
+/// simulated mean lifetime of each chain size is printed next to it along with how many standard errors apart they are.
+/// Runs of the gillespie dynamics are compared in their time unit. Run files made with another update scheme, site
+/// placer or excited bonds than the exact solution assumes are refused.
+fn print_exact_lifetimes(args: &ExactArgs) {
+    let excited_bonds = ExcitedBondConfig { up_cant: args.spin_sector, down_cant: args.down_cant, mismatch: args.mismatch };
+    let run_data: Option<RunData> = args.run_file.clone().map(file_utils::load_data);
+    if let Some(run_data) = &run_data {
+        let mut mismatches = match &run_data.config {
+            Some(config) => exact_solver::run_mismatches(config, &excited_bonds, args.site_placer),
+            None => {
+                println!("the run file records no config, it is assumed to be a random-sequential run of {:?} chains", args.site_placer);
+                Vec::new()
+            }
+        };
+        if let Some(spin_sector) = run_data.spin_sector.filter(|spin_sector| *spin_sector != args.spin_sector) {
+            mismatches.push(format!("spin sector {} instead of {}", spin_sector, args.spin_sector));
+        }
+        if !mismatches.is_empty() {
+            cli::exit_with_error("--run-file", &format!("was run with {}, its lifetimes do not follow the exact solution", mismatches.join(", ")));
+        }
+    }
 
+    if run_data.is_some() {
+        println!("chain size, initial configurations, transient states, exact mean lifetime, simulated mean lifetime, standard error, deviation");
     } else {
-        let new_run_vec: Vec<u128> = vec![steps];
-        run_data.runs.insert(chain_size, new_run_vec);
+        println!("chain size, initial configurations, transient states, exact mean lifetime");
     }
-}
 
-/// A function that will print the spins in a chain. (Probably not necessary since I can use {:?} formatter for arrays)
-pub fn print_chains(spin_chain_vec: &Vec<SpinChain<CHAIN_SIZE>>) {
-    for spin_chain in spin_chain_vec {
-        for spin in &spin_chain.chain {
-            print!("{}, ",*spin);
+    let mut chain_size = args.min_chain_size.max(excited_bonds.minimum_chain_size());
+    while chain_size <= args.max_chain_size {
+        let exact_lifetime = match exact_solver::exact_mean_lifetime(&excited_bonds, chain_size, args.site_placer) {
+            Ok(exact_lifetime) => exact_lifetime,
+            Err((argument, message)) => cli::exit_with_error(&format!("--{}", argument.replace('_', "-")), &message),
+        };
+        // Continuous-time runs are compared in time, every triple fires move_rate times per unit of time
+        let exact_mean = match &run_data {
+            Some(run_data) if run_data.is_continuous_time() => {
+                let move_rate = run_data.config.as_ref().map(|config| config.continuous_time.move_rate()).unwrap_or(1.0);
+                exact_lifetime.mean_lifetime / ((chain_size - 2) as f64 * move_rate)
+            }
+            _ => exact_lifetime.mean_lifetime,
+        };
+        print!("{}, {}, {}, {}", chain_size, exact_lifetime.initial_configurations, exact_lifetime.transient_states, exact_mean);
+
+        match run_data.as_ref().and_then(|run_data| run_data.mean_lifetime(chain_size)) {
+            Some((mean, standard_error)) => {
+                println!(", {}, {}, {}", mean, standard_error, (mean - exact_mean) / standard_error);
+            }
+            _ if run_data.is_some() => println!(", , , "),
+            _ => println!(),
         }
+        chain_size += 2;
     }
 }
 
-/// A function that iterates through the hash_chain_map and prints the relevant information
-/// for degenerate chain creation.
-pub fn print_degen_counts(hash_chain_map: &HashMap<u64, (u128,[i8;CHAIN_SIZE],[char;CHAIN_SIZE])>) {
-    for key_value_pair in hash_chain_map {
-        for character in key_value_pair.1.2 {
-            print!("{}", character)
-        }
+/// Prints the chain as brackets, "(" and ")" for a regular up or down spin, "[" for an up-canted site,
+/// "]" for a down-canted site and "}" "{" for the down and up half of a mismatch bond.
+fn print_chain(chain: &[Site]) {
+
+    for site in chain {
+        print!("{}", site);
     }
+    println!();
+
 }
 
 /// A function that evolves the fredkin chain. It chooses the sites i, i+1, and i+2 and attempts to perform the fredkin swap.
 /// * chain: the spin chain that is to be evolved
-/// If you are trying to interpret the chain at every state then this code is bugged.
-/// Suppose you have something like 2 2 1 -1 and you select the 2 1 -1 to be swapped.
-/// The code will correctly evolve the chain since 2 and 1 are equivalent, but
-/// if you interpret the chain state then you will have 2 2 -1 1 which would imply
-/// you have a mismatch bond. However the correct interpretation would be 2 1 -1 2
+///
+/// Only the spin of a site matters to the dynamics, excited sites move exactly like regular sites of the same spin.
+/// The chain dies when an unmatched up spin reaches the right edge as (() or when an unmatched down spin
+/// reaches the left edge as ()). The left edge mirrors the right one: )() at the left edge swaps to ()) like ()(
+/// at the right edge swaps to ((), which is why the left_spin_index != 0 guard of the up-canted-only dynamics is
+/// gone. Up-canted-only chains never have a down spin at the left edge, so their lifetimes are unchanged.
+///
+/// If you are trying to interpret the chain at every state then this code is bugged unless the excited sites
+/// are re-labelled after the step with SpinChain::relabel_excited_sites (the track_bond_identity option).
+/// Suppose you have something like [ [ ( ) and you select the [ ( ) to be swapped.
+/// The code will correctly evolve the chain since [ and ( are equivalent, but
+/// if you interpret the chain state then you will have [ [ ) ( which would imply
+/// you have a mismatch bond. However the correct interpretation would be [ ( ) [
 /// where you have a proper Dyck word nested withing your excited sites.
-pub fn evolve_chain(chain: &mut Vec<i8>, random_index: usize, chain_size: usize) -> bool {
+pub fn evolve_chain(chain: &mut [Site], random_index: usize, chain_size: usize) -> bool {
 
     let mut is_chain_alive = true;
     let left_spin_index = random_index;
     let middle_spin_index = random_index + 1;
     let right_spin_index = random_index + 2;
 
-    let left_spin = chain[left_spin_index];
-    let middle_spin = chain[middle_spin_index];
-    let right_spin = chain[right_spin_index];
+    let left_spin = chain[left_spin_index].spin();
+    let middle_spin = chain[middle_spin_index].spin();
+    let right_spin = chain[right_spin_index].spin();
 
-    if left_spin == 1 || left_spin == 2 {
-        let temp_value = chain[middle_spin_index];
-        if (middle_spin == 1 || middle_spin == 2) && right_spin == -1 {
-            if right_spin_index == (chain_size - 1) && (middle_spin == 2 || middle_spin ==1) {
+    match (left_spin, middle_spin, right_spin) {
+        // (() <-> ()( unless the unmatched up spin is at the right edge
+        (Spin::Up, Spin::Up, Spin::Down) => {
+            if right_spin_index == chain_size - 1 {
                 is_chain_alive = false;
-            } else if right_spin_index != chain_size -1 {
-                chain[middle_spin_index] = chain[right_spin_index];
-                chain[right_spin_index] = temp_value;
+            } else {
+                chain.swap(middle_spin_index, right_spin_index);
             }
-        } else if middle_spin == -1 && (right_spin == 1 || right_spin == 2){
-            chain[middle_spin_index] = chain[right_spin_index];
-            chain[right_spin_index] = temp_value;
-        } else if (middle_spin == -1 && right_spin == -1) && left_spin_index != 0 {
-            chain[middle_spin_index] = chain[left_spin_index];
-            chain[left_spin_index] = temp_value;
         }
-    } else {
-        if (middle_spin == 1 || middle_spin == 2) && right_spin == -1 && left_spin_index != 0{
-            print!("I did this");
-            let temp_value = chain[middle_spin_index];
-            chain[middle_spin_index] = chain[left_spin_index];
-            chain[left_spin_index] = temp_value;
+        (Spin::Up, Spin::Down, Spin::Up) => chain.swap(middle_spin_index, right_spin_index),
+        // ()) <-> )() unless the unmatched down spin is at the left edge
+        (Spin::Up, Spin::Down, Spin::Down) => {
+            if left_spin_index == 0 {
+                is_chain_alive = false;
+            } else {
+                chain.swap(middle_spin_index, left_spin_index);
+            }
         }
+        (Spin::Down, Spin::Up, Spin::Down) => chain.swap(middle_spin_index, left_spin_index),
+        (Spin::Up, Spin::Up, Spin::Up) | (Spin::Down, Spin::Down, _) | (Spin::Down, Spin::Up, Spin::Up) => {}
     }
 
     is_chain_alive
 
 }
-
-/// A method that iterates through the collection of generated spin chains.
-/// It sums spins at the same site in each chain to see what the "net" spin is.
-/// Say the chain is of length 20 and this method returns that for index i there is a 20,
-/// this means that every chain generated had an up spin at this position.
-pub fn accumulate_spins_in_chain(spin_chain_vec: &Vec<SpinChain<CHAIN_SIZE>>) {
-    let mut spin_accum_array = [0;CHAIN_SIZE];
-
-    for i in 0..spin_chain_vec.len() {
-        let spin_chain = &spin_chain_vec[i].chain;
-        for j in 0..spin_chain.len(){
-            spin_accum_array[j] += spin_chain[j];
-        }
-    }
-}
\ No newline at end of file
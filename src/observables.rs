@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ObservableConfig;
+use crate::n_fold_way;
+use crate::recorder::TrialRecorder;
+use crate::site::Site;
+
+/// A quantity measured on the chain while it evolves. New quantities only need a unit struct implementing this
+/// trait and a variant of ObservableKind, the dynamics never look at them.
+pub trait Observable {
+    /// Appends the values of the quantity for the chain. Every chain of a given size and set of excited bonds must
+    /// give the same number of values.
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>);
+}
+
+/// The height of the chain after each of its sites, starting from height 0 in front of the first site
+pub struct HeightProfile;
+
+/// The positions of the excited sites from left to right
+pub struct ExcitedSitePositions;
+
+/// The number of triples whose Fredkin move would change the chain or kill it
+pub struct ActiveMoveCount;
+
+/// The number of sites between the rightmost excited site and the right edge of the chain, the chain size when there
+/// are no excited sites
+pub struct RightmostExcitedSiteDistance;
+
+impl Observable for HeightProfile {
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>) {
+        let mut height: i64 = 0;
+        values.push(0.0);
+        for site in chain {
+            height += site.spin().value() as i64;
+            values.push(height as f64);
+        }
+    }
+}
+
+impl Observable for ExcitedSitePositions {
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>) {
+        values.extend(chain.iter().enumerate().filter(|(_, site)| site.bond_kind().is_some()).map(|(index, _)| index as f64));
+    }
+}
+
+impl Observable for ActiveMoveCount {
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>) {
+        let active_moves = (0..chain.len() - 2).filter(|index| n_fold_way::is_active_move(chain, *index)).count();
+        values.push(active_moves as f64);
+    }
+}
+
+impl Observable for RightmostExcitedSiteDistance {
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>) {
+        let distance = match chain.iter().rposition(|site| site.bond_kind().is_some()) {
+            Some(index) => chain.len() - 1 - index,
+            None => chain.len(),
+        };
+        values.push(distance as f64);
+    }
+}
+
+/// The observables a run can measure
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservableKind {
+    /// Height of the chain after each site, chain size + 1 values (HeightProfile)
+    HeightProfile,
+    /// Position of every excited site from left to right, needs track_bond_identity (ExcitedSitePositions)
+    ExcitedSitePositions,
+    /// Number of triples whose move changes the chain (ActiveMoveCount)
+    ActiveMoves,
+    /// Distance of the rightmost excited site from the right edge, needs track_bond_identity (RightmostExcitedSiteDistance)
+    RightmostExcitedDistance,
+}
+
+impl ObservableKind {
+    /// Whether the observable reads which sites are excited from their labels. The moves only swap spins, so the labels
+    /// are only kept on the excited sites when the chain is re-labelled after every move (track_bond_identity).
+    pub fn reads_bond_labels(self) -> bool {
+        matches!(self, ObservableKind::ExcitedSitePositions | ObservableKind::RightmostExcitedDistance)
+    }
+}
+
+impl Observable for ObservableKind {
+    fn measure(&self, chain: &[Site], values: &mut Vec<f64>) {
+        match self {
+            ObservableKind::HeightProfile => HeightProfile.measure(chain, values),
+            ObservableKind::ExcitedSitePositions => ExcitedSitePositions.measure(chain, values),
+            ObservableKind::ActiveMoves => ActiveMoveCount.measure(chain, values),
+            ObservableKind::RightmostExcitedDistance => RightmostExcitedSiteDistance.measure(chain, values),
+        }
+    }
+}
+
+/// The values of every observable, in the order of the config, at every observation time a trial lived through
+pub type Measurements = Vec<Vec<Vec<f64>>>;
+
+/// Measures the observables of a single trial at the observation times. The chain at time t is the chain after
+/// floor(t) steps for the discrete dynamics and the chain after the last move before t for the gillespie dynamics.
+/// Times the chain does not live to are not measured.
+pub struct ObservableSampler {
+    observables: Vec<ObservableKind>,
+    times: Vec<f64>,
+    pub measurements: Measurements,
+}
+
+impl ObservableSampler {
+    pub fn new(observable_config: &ObservableConfig) -> Self {
+        ObservableSampler { observables: observable_config.quantities.clone(), times: observable_config.times.clone(), measurements: Vec::new() }
+    }
+
+    /// A sampler that measures nothing
+    pub fn disabled() -> Self {
+        ObservableSampler::new(&ObservableConfig::default())
+    }
+
+    /// Measures the chain at every observation time before end_time that has not been measured yet
+    fn measure_until(&mut self, end_time: f64, chain: &[Site]) {
+        while self.measurements.len() < self.times.len() && self.times[self.measurements.len()] < end_time {
+            let values = self.observables.iter().map(|observable| {
+                let mut values = Vec::new();
+                observable.measure(chain, &mut values);
+                values
+            }).collect();
+            self.measurements.push(values);
+        }
+    }
+}
+
+impl TrialRecorder for ObservableSampler {
+    fn is_recording(&self) -> bool {
+        !self.observables.is_empty()
+    }
+
+    fn record(&mut self, step: u128, _moves: u128, time: Option<f64>, chain: &[Site]) {
+        // the gillespie dynamics report the time the chain held still for before each move instead
+        if time.is_none() {
+            self.measure_until(step as f64 + 1.0, chain);
+        }
+    }
+
+    fn record_idle_steps(&mut self, _first_step: u128, end_step: u128, _moves: u128, chain: &[Site]) {
+        self.measure_until(end_step as f64, chain);
+    }
+
+    fn record_idle_time(&mut self, _start_time: f64, end_time: f64, chain: &[Site]) {
+        self.measure_until(end_time, chain);
+    }
+}
+
+/// The observables of every trial of a chain size, summed up per observation time over the trials alive at that time.
+/// Sums rather than means are kept so checkpoints and run files can be combined.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ObservableCurves {
+    pub times: Vec<f64>,
+    // Number of trials alive at each observation time, the number of values in the sums
+    pub survivors: Vec<u64>,
+    pub sums: BTreeMap<ObservableKind, Vec<Vec<f64>>>,
+    pub sums_of_squares: BTreeMap<ObservableKind, Vec<Vec<f64>>>,
+}
+
+impl ObservableCurves {
+    pub fn new(observable_config: &ObservableConfig) -> Self {
+        let empty_curves: BTreeMap<ObservableKind, Vec<Vec<f64>>> = observable_config.quantities.iter()
+            .map(|observable| (*observable, vec![Vec::new(); observable_config.times.len()]))
+            .collect();
+        ObservableCurves {
+            times: observable_config.times.clone(),
+            survivors: vec![0; observable_config.times.len()],
+            sums: empty_curves.clone(),
+            sums_of_squares: empty_curves,
+        }
+    }
+
+    /// Adds the measurements of one trial, in the observable order of the config the curves were made with
+    pub fn add_trial(&mut self, observables: &[ObservableKind], measurements: &Measurements) {
+        for (time_index, observable_values) in measurements.iter().enumerate() {
+            self.survivors[time_index] += 1;
+            for (observable, values) in observables.iter().zip(observable_values) {
+                add_values(&mut self.sums.get_mut(observable).unwrap()[time_index], values.iter().copied());
+                add_values(&mut self.sums_of_squares.get_mut(observable).unwrap()[time_index], values.iter().map(|value| value * value));
+            }
+        }
+    }
+
+    /// Adds the sums of curves measured with the same observables at the same times
+    pub fn merge(&mut self, other: &ObservableCurves) {
+        assert_eq!(self.times, other.times, "curves measured at different times cannot be merged");
+        for (survivors, other_survivors) in self.survivors.iter_mut().zip(&other.survivors) {
+            *survivors += other_survivors;
+        }
+        for (sums, other_sums) in [(&mut self.sums, &other.sums), (&mut self.sums_of_squares, &other.sums_of_squares)] {
+            for (observable, other_curves) in other_sums {
+                let curves = sums.entry(*observable).or_insert_with(|| vec![Vec::new(); other_curves.len()]);
+                for (curve, other_curve) in curves.iter_mut().zip(other_curves) {
+                    add_values(curve, other_curve.iter().copied());
+                }
+            }
+        }
+    }
+
+    /// The mean of every value of the observable at every observation time, empty where no trial was alive
+    pub fn means(&self, observable: ObservableKind) -> Vec<Vec<f64>> {
+        self.sums[&observable].iter().zip(&self.survivors)
+            .map(|(sums, survivors)| sums.iter().map(|sum| sum / *survivors as f64).collect())
+            .collect()
+    }
+
+    /// The standard error of every mean, NaN where fewer than two trials were alive
+    pub fn standard_errors(&self, observable: ObservableKind) -> Vec<Vec<f64>> {
+        self.sums[&observable].iter().zip(&self.sums_of_squares[&observable]).zip(&self.survivors)
+            .map(|((sums, sums_of_squares), survivors)| {
+                let count = *survivors as f64;
+                if *survivors < 2 {
+                    return vec![f64::NAN; sums.len()];
+                }
+                sums.iter().zip(sums_of_squares).map(|(sum, sum_of_squares)| {
+                    let variance = (sum_of_squares - sum * sum / count) / (count - 1.0);
+                    (variance.max(0.0) / count).sqrt()
+                }).collect()
+            })
+            .collect()
+    }
+}
+
+fn add_values<I: Iterator<Item = f64>>(sums: &mut Vec<f64>, values: I) {
+    for (index, value) in values.enumerate() {
+        match sums.get_mut(index) {
+            Some(sum) => *sum += value,
+            None => sums.push(value),
+        }
+    }
+}
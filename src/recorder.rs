@@ -0,0 +1,62 @@
+use crate::observables::ObservableSampler;
+use crate::site::Site;
+use crate::trajectory::TrajectoryRecorder;
+
+/// Follows a single trial while the dynamics evolve its chain. The dynamics report every chain the trial passes
+/// through, the starting chain being step 0, and only call the recorder at all when is_recording is true.
+pub trait TrialRecorder {
+    fn is_recording(&self) -> bool;
+
+    /// The chain after the given number of steps and moves, at the given time for the gillespie dynamics
+    fn record(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]);
+
+    /// The chain stayed the same from step first_step up to, but not including, step end_step.
+    /// Reported by the n-fold way dynamics, which skips over these steps.
+    fn record_idle_steps(&mut self, _first_step: u128, _end_step: u128, _moves: u128, _chain: &[Site]) {}
+
+    /// The chain stayed the same from start_time up to, but not including, end_time.
+    /// Reported by the gillespie dynamics before every move.
+    fn record_idle_time(&mut self, _start_time: f64, _end_time: f64, _chain: &[Site]) {}
+
+    /// The last chain before the move that killed it, step being the lifetime, or the chain at the step cap of the run
+    fn finish(&mut self, _step: u128, _moves: u128, _time: Option<f64>, _chain: &[Site]) {}
+}
+
+/// Everything recorded about a single trial besides its lifetime
+pub struct TrialRecording {
+    pub trajectory: TrajectoryRecorder,
+    pub observables: ObservableSampler,
+}
+
+impl TrialRecording {
+    /// A recording that keeps nothing, for trials that only need their lifetime
+    pub fn disabled() -> Self {
+        TrialRecording { trajectory: TrajectoryRecorder::disabled(), observables: ObservableSampler::disabled() }
+    }
+}
+
+impl TrialRecorder for TrialRecording {
+    fn is_recording(&self) -> bool {
+        self.trajectory.is_recording() || self.observables.is_recording()
+    }
+
+    fn record(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]) {
+        self.trajectory.record(step, moves, time, chain);
+        self.observables.record(step, moves, time, chain);
+    }
+
+    fn record_idle_steps(&mut self, first_step: u128, end_step: u128, moves: u128, chain: &[Site]) {
+        self.trajectory.record_idle_steps(first_step, end_step, moves, chain);
+        self.observables.record_idle_steps(first_step, end_step, moves, chain);
+    }
+
+    fn record_idle_time(&mut self, start_time: f64, end_time: f64, chain: &[Site]) {
+        self.trajectory.record_idle_time(start_time, end_time, chain);
+        self.observables.record_idle_time(start_time, end_time, chain);
+    }
+
+    fn finish(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]) {
+        self.trajectory.finish(step, moves, time, chain);
+        self.observables.finish(step, moves, time, chain);
+    }
+}
@@ -0,0 +1,75 @@
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rand_mt::{Mt, Mt64};
+
+use crate::config::RngKind;
+
+/// A random number generator of the kind selected by the run configuration.
+/// Lets every trial own its own generator without the simulation being generic over the kind.
+pub enum TrialRng {
+    Mt64(Mt64),
+    Mt(Mt),
+    Std(StdRng),
+}
+
+impl TrialRng {
+    pub fn new(kind: RngKind, seed: u64) -> TrialRng {
+        match kind {
+            RngKind::Mt64 => TrialRng::Mt64(Mt64::new(seed)),
+            RngKind::Mt => TrialRng::Mt(Mt::seed_from_u64(seed)),
+            RngKind::Std => TrialRng::Std(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngCore for TrialRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            TrialRng::Mt64(rng) => rng.next_u32(),
+            TrialRng::Mt(rng) => rng.next_u32(),
+            TrialRng::Std(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            TrialRng::Mt64(rng) => rng.next_u64(),
+            TrialRng::Mt(rng) => rng.next_u64(),
+            TrialRng::Std(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            TrialRng::Mt64(rng) => rng.fill_bytes(dest),
+            TrialRng::Mt(rng) => rng.fill_bytes(dest),
+            TrialRng::Std(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            TrialRng::Mt64(rng) => rng.try_fill_bytes(dest),
+            TrialRng::Mt(rng) => rng.try_fill_bytes(dest),
+            TrialRng::Std(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+/// Derives the seed of a single trial from the master seed of the run.
+/// Every (spin sector, chain size, trial) gets its own stream, so a trial can be replayed on its own
+/// and does not depend on how many trials were run before it.
+pub fn trial_seed(master_seed: u64, spin_sector: usize, chain_size: usize, trial: u64) -> u64 {
+    let mut seed = splitmix64(master_seed);
+    seed = splitmix64(seed ^ spin_sector as u64);
+    seed = splitmix64(seed ^ chain_size as u64);
+    splitmix64(seed ^ trial)
+}
+
+/// The SplitMix64 finalizer. Spreads nearby inputs (trial 1, trial 2, ...) over unrelated seeds.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use crate::config::{LifetimeStorage, RunConfig};
+use crate::file_utils::{RunData, RunSource};
+use crate::simulation::Lifetime;
+
+/// The config parameters that change the distribution of the lifetimes a run samples, as a description of every one
+/// that differs between the configs. The rest, like the number of trials, the chain sizes or the seed, only change
+/// which samples were drawn, so runs differing in them can be merged. up_cant is left out as it is the spin sector.
+fn config_mismatches(first: &RunConfig, other: &RunConfig) -> Vec<String> {
+    let parameters = |config: &RunConfig| -> Vec<(&str, String)> {
+        vec![
+            ("down_cant", config.excited_bond_map.down_cant.to_string()),
+            ("mismatch", config.excited_bond_map.mismatch.to_string()),
+            ("site_placer", format!("{:?}", config.site_placer)),
+            ("dynamics", format!("{:?}", config.dynamics)),
+            ("continuous_time", format!("{:?}", config.continuous_time)),
+            ("update_scheme", format!("{:?}", config.update_scheme)),
+            ("max_steps", config.max_steps.to_string()),
+            ("observables", format!("{:?}", config.observables)),
+        ]
+    };
+    parameters(first).into_iter().zip(parameters(other))
+        .filter(|((_, first_value), (_, other_value))| first_value != other_value)
+        .map(|((name, first_value), (_, other_value))| format!("{name} {other_value} instead of {first_value}"))
+        .collect()
+}
+
+/// Merges run files of the same spin sector into one, the lifetimes of every chain size being the lifetimes of all of
+/// them. When any file keeps summaries the lifetimes of the others are folded into summaries too. The merged file
+/// keeps the config of the first file with the chain size range of all of them, and a source for every file merged,
+/// the sources of files that were merged before included.
+///
+/// Files of another spin sector or clock are refused. Files whose config parameters differ from the first file in a
+/// way that changes the lifetimes, or that repeat a master seed for a chain size and so the same trials, are refused
+/// unless allow_mismatch is set. They are then merged and the differences recorded in their source, with their
+/// observables left out when those were measured differently. Returns the merged run data and a warning for every
+/// mismatch merged.
+/// * fallback_spin_sector: spin sector of files older than the spin sector field of the run data
+pub fn merge_runs(inputs: &[(String, RunData)], allow_mismatch: bool, fallback_spin_sector: Option<usize>) -> Result<(RunData, Vec<String>), String> {
+    let Some((first_file, first)) = inputs.first() else {
+        return Err("no run files to merge".to_string());
+    };
+    let spin_sector_of = |file_name: &str, run_data: &RunData| run_data.spin_sector.or(fallback_spin_sector)
+        .ok_or(format!("{file_name} does not record its spin sector, pass it with --spin-sector"));
+    let spin_sector = spin_sector_of(first_file, first)?;
+    let mut config = first.config.clone().unwrap_or_default();
+    let summary = inputs.iter().any(|(_, run_data)| !run_data.summaries.is_empty());
+
+    let mut merged = RunData::new(&config, spin_sector);
+    merged.master_seed = None;
+    let mut warnings = Vec::new();
+    for (file_name, run_data) in inputs {
+        let file_spin_sector = spin_sector_of(file_name, run_data)?;
+        if file_spin_sector != spin_sector {
+            return Err(format!("{file_name} is in spin sector {file_spin_sector}, {first_file} in spin sector {spin_sector}"));
+        }
+        if run_data.is_continuous_time() != first.is_continuous_time() {
+            return Err(format!("{file_name} and {first_file} do not both measure lifetimes in steps or both in time"));
+        }
+
+        // files merged before bring the sources they were merged from
+        let mut sources = if run_data.sources.is_empty() {
+            vec![RunSource { file_name: file_name.clone(), master_seed: run_data.master_seed, trials: run_data.trial_counts(), mismatches: Vec::new() }]
+        } else {
+            run_data.sources.clone()
+        };
+        let mut mismatches = match (&first.config, &run_data.config) {
+            (Some(first_config), Some(file_config)) => config_mismatches(first_config, file_config),
+            _ if first.config == run_data.config => Vec::new(),
+            _ => vec!["no config to compare".to_string()],
+        };
+        // the trial seeds are derived from the chain size too, so a master seed only repeats trials of the chain sizes
+        // both files ran
+        for source in &sources {
+            let repeats_trials = |merged_source: &&RunSource| source.master_seed.is_some() && merged_source.master_seed == source.master_seed
+                && source.trials.keys().any(|chain_size| merged_source.trials.contains_key(chain_size));
+            if let Some(repeated) = merged.sources.iter().find(repeats_trials) {
+                mismatches.push(format!("master seed {} repeats the trials of {}", source.master_seed.unwrap(), repeated.file_name));
+            }
+        }
+        if !mismatches.is_empty() {
+            if !allow_mismatch {
+                return Err(format!("{file_name} does not match {first_file}: {}. Pass --allow-mismatch to merge it anyway", mismatches.join(", ")));
+            }
+            warnings.extend(mismatches.iter().map(|mismatch| format!("{file_name}: {mismatch}")));
+            for source in &mut sources {
+                source.mismatches.extend(mismatches.iter().cloned());
+            }
+        }
+
+        add_lifetimes(&mut merged, run_data, summary);
+        let same_observables = run_data.config.as_ref().map(|config| &config.observables) == first.config.as_ref().map(|config| &config.observables);
+        if same_observables {
+            for (chain_size, curves) in &run_data.observables {
+                match merged.observables.get_mut(chain_size) {
+                    Some(merged_curves) => merged_curves.merge(curves),
+                    None => {
+                        merged.observables.insert(*chain_size, curves.clone());
+                    }
+                }
+            }
+        } else if !run_data.observables.is_empty() {
+            warnings.push(format!("{file_name}: observables measured differently from {first_file} are left out"));
+        }
+        merged.sources.extend(sources);
+    }
+
+    let chain_sizes = merged.chain_sizes();
+    if let (Some(min_chain_size), Some(max_chain_size)) = (chain_sizes.first(), chain_sizes.last()) {
+        config.min_chain_size = *min_chain_size;
+        config.max_chain_size = *max_chain_size;
+    }
+    if summary {
+        config.lifetime_storage = LifetimeStorage::Summary;
+    }
+    merged.config = first.config.is_some().then_some(config);
+    Ok((merged, warnings))
+}
+
+/// Adds the lifetimes of a run file to the merged ones, folding them into summaries when the merged file keeps those
+fn add_lifetimes(merged: &mut RunData, run_data: &RunData, summary: bool) {
+    for (chain_size, immortal_trials) in &run_data.immortal_trials {
+        *merged.immortal_trials.entry(*chain_size).or_insert(0) += immortal_trials;
+    }
+    for (chain_size, file_summary) in &run_data.summaries {
+        merged.summaries.entry(*chain_size).or_default().merge(file_summary);
+    }
+    if !summary {
+        extend(&mut merged.runs, &run_data.runs);
+        extend(&mut merged.times, &run_data.times);
+        extend(&mut merged.censored_runs, &run_data.censored_runs);
+        extend(&mut merged.censored_times, &run_data.censored_times);
+        return;
+    }
+    for (runs, times, censored) in [(&run_data.runs, &run_data.times, false), (&run_data.censored_runs, &run_data.censored_times, true)] {
+        for (chain_size, step_counts) in runs {
+            let merged_summary = merged.summaries.entry(*chain_size).or_default();
+            for (trial, step_count) in step_counts.iter().enumerate() {
+                let time = times.get(chain_size).map(|times| times[trial]);
+                merged_summary.add(&Lifetime { step_count: *step_count, time, immortal: false, censored });
+            }
+        }
+    }
+}
+
+fn extend<T: Clone>(merged: &mut BTreeMap<usize, Vec<T>>, lifetimes: &BTreeMap<usize, Vec<T>>) {
+    for (chain_size, values) in lifetimes {
+        merged.entry(*chain_size).or_default().extend(values.iter().cloned());
+    }
+}
@@ -0,0 +1,335 @@
+//! Statistical checks of the probability SpinChain::new_excited_with_placer draws every configuration of a chain with.
+//! The sampled frequencies are compared with a chi-square and a Kolmogorov-Smirnov test against the Catalan and ballot
+//! counts of the exact enumeration: uniform over the configurations for the uniform placer, and uniform over the Dyck
+//! word fillings of every placement for k_beach.
+//! Every test runs from a fixed seed, so a passing sampler always passes and a biased one fails.
+//! The site placers are checked on their own for the even/odd alternation of the excited sites.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rand::Rng;
+use rand_mt::Mt64;
+
+use crate::config::ExcitedBondConfig;
+use crate::enumeration;
+use crate::site::Site;
+use crate::site_placer::{self, SitePlacer, SitePlacerKind};
+use crate::spin_chain::{self, SpinChain};
+use crate::CHAIN_SIZE;
+
+/// Chi-square z score above which the frequencies are rejected, a one sided p value of 0.001
+const CHI_SQUARE_CRITICAL_Z: f64 = 3.09;
+/// Kolmogorov-Smirnov constant for a p value of 0.001, the critical distance is this over the square root of the samples
+const KS_CRITICAL_CONSTANT: f64 = 1.95;
+
+/// Draws chains from the sampler and counts how often each configuration of the enumeration was drawn.
+/// Panics when the sampler draws a chain that is not a valid configuration.
+fn sampled_counts<F: FnMut(&mut Mt64) -> Vec<Site>>(configurations: &[Vec<Site>], samples: u64, seed: u64, mut sampler: F) -> Vec<u64> {
+    let configuration_indices: HashMap<&Vec<Site>, usize> = configurations.iter().enumerate().map(|(index, configuration)| (configuration, index)).collect();
+    let mut counts = vec![0; configurations.len()];
+    let mut rng = Mt64::new(seed);
+
+    for _ in 0..samples {
+        let chain = sampler(&mut rng);
+        match configuration_indices.get(&chain) {
+            Some(index) => counts[*index] += 1,
+            None => panic!("the sampler drew {} which is not a valid configuration", chain.iter().map(|site| site.symbol()).collect::<String>()),
+        }
+    }
+    counts
+}
+
+/// The chi-square statistic of the counts against the probabilities of the configurations, converted to a standard normal
+/// z score with the Wilson-Hilferty approximation.
+fn chi_square_z_score(counts: &[u64], probabilities: &[f64]) -> f64 {
+    let samples: u64 = counts.iter().sum();
+    let chi_square: f64 = counts.iter().zip(probabilities)
+        .map(|(count, probability)| {
+            let expected = samples as f64 * probability;
+            (*count as f64 - expected).powi(2) / expected
+        })
+        .sum();
+    wilson_hilferty_z_score(chi_square, (counts.len() - 1) as f64)
+}
+
+fn wilson_hilferty_z_score(chi_square: f64, degrees_of_freedom: f64) -> f64 {
+    let variance = 2.0 / (9.0 * degrees_of_freedom);
+    ((chi_square / degrees_of_freedom).cbrt() - (1.0 - variance)) / variance.sqrt()
+}
+
+/// The Kolmogorov-Smirnov distance between the empirical distribution of the counts and the probabilities of the
+/// configurations, in enumeration order.
+fn ks_distance(counts: &[u64], probabilities: &[f64]) -> f64 {
+    let samples: u64 = counts.iter().sum();
+    let mut empirical_cdf = 0.0;
+    let mut exact_cdf = 0.0;
+    let mut largest_distance: f64 = 0.0;
+    for (count, probability) in counts.iter().zip(probabilities) {
+        empirical_cdf += *count as f64 / samples as f64;
+        exact_cdf += probability;
+        largest_distance = largest_distance.max((empirical_cdf - exact_cdf).abs());
+    }
+    largest_distance
+}
+
+/// Whether the counts pass both the chi-square and the Kolmogorov-Smirnov test against the probabilities
+fn follows_distribution(counts: &[u64], probabilities: &[f64]) -> bool {
+    let samples: u64 = counts.iter().sum();
+    chi_square_z_score(counts, probabilities) < CHI_SQUARE_CRITICAL_Z && ks_distance(counts, probabilities) < KS_CRITICAL_CONSTANT / (samples as f64).sqrt()
+}
+
+fn uniform_probabilities(counts: &[u64]) -> Vec<f64> {
+    vec![1.0 / counts.len() as f64; counts.len()]
+}
+
+/// Whether the counts pass both the chi-square and the Kolmogorov-Smirnov test of uniformity
+fn is_uniform(counts: &[u64]) -> bool {
+    follows_distribution(counts, &uniform_probabilities(counts))
+}
+
+/// Draws chains from new_excited_with_placer, at least 200 expected draws of every configuration on average
+fn new_excited_counts(site_placer: SitePlacerKind, excited_bonds: ExcitedBondConfig, chain_size: usize, seed: u64) -> (Vec<Vec<Site>>, Vec<u64>) {
+    let configurations = enumeration::excited_configurations(&excited_bonds, chain_size);
+    let samples = 200 * configurations.len() as u64;
+    let excited_bond_map = excited_bonds.to_map();
+    let counts = sampled_counts(&configurations, samples, seed, |rng| SpinChain::<CHAIN_SIZE>::new_excited_with_placer(&excited_bond_map, chain_size, &site_placer, rng).chain);
+    (configurations, counts)
+}
+
+/// The excited sites of a configuration and the number of ways to fill the intervals around them with Dyck words,
+/// the product of the Catalan numbers of the intervals
+fn placement_and_fillings(configuration: &[Site]) -> (Vec<usize>, u128) {
+    let placement: Vec<usize> = (0..configuration.len()).filter(|index| configuration[*index].bond_kind().is_some()).collect();
+    let mut interval_start = 0;
+    let mut fillings = 1;
+    for interval_end in placement.iter().copied().chain([configuration.len()]) {
+        fillings *= enumeration::catalan_number((interval_end - interval_start) / 2);
+        interval_start = interval_end + 1;
+    }
+    (placement, fillings)
+}
+
+/// k_beach does not weigh its placements by their fillings, but every placement it draws is filled with uniformly drawn
+/// Dyck words. Given the placement, every configuration with those excited sites is expected 1 / fillings of the draws
+/// of the placement, a chi-square test with as many degrees of freedom as configurations minus placements.
+#[test]
+fn k_beach_fills_the_intervals_with_uniform_dyck_words() {
+    for (seed, (excited_bonds, chain_size)) in [
+        (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 10),
+        (ExcitedBondConfig { up_cant: 2, down_cant: 0, mismatch: 0 }, 12),
+        (ExcitedBondConfig { up_cant: 0, down_cant: 1, mismatch: 1 }, 10),
+        (ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 1 }, 14),
+    ].into_iter().enumerate() {
+        let (configurations, counts) = new_excited_counts(SitePlacerKind::KBeach, excited_bonds, chain_size, seed as u64);
+        let placements: Vec<(Vec<usize>, u128)> = configurations.iter().map(|configuration| placement_and_fillings(configuration)).collect();
+        let mut placement_counts: HashMap<&Vec<usize>, u64> = HashMap::new();
+        for ((placement, _), count) in placements.iter().zip(&counts) {
+            *placement_counts.entry(placement).or_insert(0) += count;
+        }
+
+        let mut chi_square = 0.0;
+        let mut degrees_of_freedom = 0;
+        for ((placement, fillings), count) in placements.iter().zip(&counts) {
+            let placement_count = placement_counts[placement];
+            if placement_count == 0 {
+                continue;
+            }
+            let expected = placement_count as f64 / *fillings as f64;
+            chi_square += (*count as f64 - expected).powi(2) / expected;
+            degrees_of_freedom += 1;
+        }
+        degrees_of_freedom -= placement_counts.values().filter(|count| **count > 0).count();
+        let z_score = wilson_hilferty_z_score(chi_square, degrees_of_freedom as f64);
+        assert!(z_score < CHI_SQUARE_CRITICAL_Z, "{:?} with chain size {}: chi-square z = {}", excited_bonds, chain_size, z_score);
+    }
+}
+
+/// The uniform placer draws a placement as often as the fraction of all configurations, counted with ballot numbers,
+/// that have their excited sites there: the product of the Catalan numbers of its intervals over the number of configurations
+#[test]
+fn uniform_placer_weighs_placements_by_their_dyck_word_fillings() {
+    for (seed, (excited_bonds, chain_size)) in [
+        (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 16),
+        (ExcitedBondConfig { up_cant: 2, down_cant: 1, mismatch: 0 }, 16),
+        (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 2 }, 18),
+    ].into_iter().enumerate() {
+        let number_of_configurations = enumeration::number_of_excited_configurations(&excited_bonds, chain_size) as f64;
+        let placements = enumeration::excited_site_placements(&excited_bonds, chain_size);
+        let probabilities: Vec<f64> = placements.iter()
+            .map(|placement| {
+                let mut configuration = vec![Site::Up; chain_size];
+                for index in placement {
+                    configuration[*index] = Site::UpCant;
+                }
+                placement_and_fillings(&configuration).1 as f64 / number_of_configurations
+            })
+            .collect();
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let placement_indices: HashMap<&Vec<usize>, usize> = placements.iter().enumerate().map(|(index, placement)| (placement, index)).collect();
+        let mut counts = vec![0; placements.len()];
+        for placement in draw_placements(SitePlacerKind::Uniform, excited_bonds, chain_size, 200 * placements.len() as u64, 20 + seed as u64) {
+            counts[placement_indices[&placement]] += 1;
+        }
+        assert!(follows_distribution(&counts, &probabilities), "{:?} with chain size {}: chi-square z = {}, KS distance = {}",
+            excited_bonds, chain_size, chi_square_z_score(&counts, &probabilities), ks_distance(&counts, &probabilities));
+    }
+}
+
+fn assert_new_excited_is_uniform(excited_bonds: ExcitedBondConfig, chain_size: usize, seed: u64) {
+    let (_, counts) = new_excited_counts(SitePlacerKind::Uniform, excited_bonds, chain_size, seed);
+    let probabilities = uniform_probabilities(&counts);
+    assert!(is_uniform(&counts), "{:?} with chain size {} is not sampled uniformly: chi-square z = {}, KS distance = {} over {} samples",
+        excited_bonds, chain_size, chi_square_z_score(&counts, &probabilities), ks_distance(&counts, &probabilities), counts.iter().sum::<u64>());
+}
+
+#[test]
+fn up_cant_chains_are_sampled_uniformly() {
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 6, 1);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 10, 2);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 2, down_cant: 0, mismatch: 0 }, 12, 3);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 3, down_cant: 0, mismatch: 0 }, 12, 4);
+}
+
+#[test]
+fn down_cant_and_mismatch_chains_are_sampled_uniformly() {
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 0, down_cant: 1, mismatch: 0 }, 10, 5);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 0, down_cant: 0, mismatch: 1 }, 10, 6);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 }, 12, 7);
+    assert_new_excited_is_uniform(ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 1 }, 14, 8);
+}
+
+#[test]
+fn k_beach_chains_are_not_sampled_uniformly() {
+    let (_, counts) = new_excited_counts(SitePlacerKind::KBeach, ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 10, 11);
+    assert!(!is_uniform(&counts));
+}
+
+/// The distribution the uniform placer is listed with by enumerate and used with by the exact solver gives every
+/// configuration one over its ballot count
+#[test]
+fn exact_uniform_sampler_distribution_is_uniform() {
+    for (excited_bonds, chain_size) in [
+        (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 14),
+        (ExcitedBondConfig { up_cant: 2, down_cant: 1, mismatch: 0 }, 14),
+        (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 2 }, 16),
+    ] {
+        let distribution = SpinChain::<CHAIN_SIZE>::excited_chain_distribution(&excited_bonds.to_map(), chain_size, SitePlacerKind::Uniform).unwrap();
+        let number_of_configurations = enumeration::number_of_excited_configurations(&excited_bonds, chain_size);
+        assert_eq!(distribution.len() as u128, number_of_configurations);
+        for (chain, probability) in distribution {
+            assert!((probability * number_of_configurations as f64 - 1.0).abs() < 1e-9, "{:?} has probability {}", chain, probability);
+        }
+    }
+}
+
+#[test]
+fn biased_sampler_is_rejected() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 };
+    let chain_size = 10;
+    let configurations = enumeration::excited_configurations(&excited_bonds, chain_size);
+    let samples = 200 * configurations.len() as u64;
+    let excited_bond_map = excited_bonds.to_map();
+    let draw = |rng: &mut Mt64| SpinChain::<CHAIN_SIZE>::new_excited_with_placer(&excited_bond_map, chain_size, &SitePlacerKind::Uniform, rng).chain;
+
+    // redraw the configurations in the first half of the enumeration a fifth of the time, which leaves them 10% under-represented
+    let first_half = &configurations[..configurations.len() / 2];
+    let counts = sampled_counts(&configurations, samples, 9, |rng| {
+        let chain = draw(rng);
+        if first_half.contains(&chain) && rng.gen_bool(0.2) { draw(rng) } else { chain }
+    });
+
+    assert!(!is_uniform(&counts));
+}
+
+#[test]
+fn configuration_counts_match_ballot_numbers() {
+    assert_eq!(enumeration::catalan_number(5), 42);
+    for chain_size in (2..=14).step_by(2) {
+        assert_eq!(enumeration::dyck_words(chain_size).len() as u128, enumeration::catalan_number(chain_size / 2));
+    }
+    for (up_cant, down_cant, mismatch) in [(1, 0, 0), (2, 0, 0), (0, 1, 0), (0, 0, 1), (1, 1, 1), (0, 2, 1)] {
+        let excited_bonds = ExcitedBondConfig { up_cant, down_cant, mismatch };
+        for chain_size in (excited_bonds.minimum_chain_size()..=16).step_by(2) {
+            assert_eq!(enumeration::excited_configurations(&excited_bonds, chain_size).len() as u128, enumeration::number_of_excited_configurations(&excited_bonds, chain_size));
+        }
+    }
+}
+
+const SITE_PLACERS: [SitePlacerKind; 4] = [SitePlacerKind::KBeach, SitePlacerKind::Uniform, SitePlacerKind::Rejection, SitePlacerKind::IntervalSplitting];
+
+/// Draws placements from the site placer for the given bonds, checking every one with validate_site_index_map
+fn draw_placements(site_placer: SitePlacerKind, excited_bonds: ExcitedBondConfig, chain_size: usize, samples: u64, seed: u64) -> Vec<Vec<usize>> {
+    let number_of_bonds = excited_bonds.up_cant + excited_bonds.down_cant + excited_bonds.mismatch;
+    let (left_reserved, right_reserved) = spin_chain::reserved_edge_sites(excited_bonds.up_cant, excited_bonds.down_cant, excited_bonds.mismatch);
+    let mut rng = Mt64::new(seed);
+
+    (0..samples).map(|_| {
+        let mut excited_site_indices = BTreeMap::<usize, Site>::new();
+        site_placer.place_excited_sites(&mut excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, &mut rng);
+
+        assert!(site_placer::validate_site_index_map(&excited_site_indices), "{:?} placed {:?} which does not alternate between even and odd sites", site_placer, excited_site_indices.keys());
+        assert_eq!(excited_site_indices.len(), 2 * number_of_bonds, "{:?} placed {:?}", site_placer, excited_site_indices.keys());
+        assert!(excited_site_indices.keys().all(|index| *index >= left_reserved && *index < chain_size - right_reserved), "{:?} placed {:?} in the reserved edge sites", site_placer, excited_site_indices.keys());
+        excited_site_indices.into_keys().collect()
+    }).collect()
+}
+
+#[test]
+fn validate_site_index_map_requires_alternating_sites() {
+    let alternating: BTreeMap<usize, Site> = [(2, Site::UpCant), (5, Site::UpCant), (6, Site::UpCant), (9, Site::UpCant)].into_iter().collect();
+    let two_even_sites: BTreeMap<usize, Site> = [(2, Site::UpCant), (4, Site::UpCant), (5, Site::UpCant), (9, Site::UpCant)].into_iter().collect();
+    let starts_odd: BTreeMap<usize, Site> = [(1, Site::UpCant), (2, Site::UpCant)].into_iter().collect();
+
+    assert!(site_placer::validate_site_index_map(&alternating));
+    assert!(!site_placer::validate_site_index_map(&two_even_sites));
+    assert!(!site_placer::validate_site_index_map(&starts_odd));
+}
+
+#[test]
+fn every_site_placer_alternates_even_and_odd_sites() {
+    for (seed, site_placer) in SITE_PLACERS.into_iter().enumerate() {
+        for (excited_bonds, chain_size) in [
+            (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 4),
+            (ExcitedBondConfig { up_cant: 1, down_cant: 0, mismatch: 0 }, 12),
+            (ExcitedBondConfig { up_cant: 3, down_cant: 0, mismatch: 0 }, 8),
+            (ExcitedBondConfig { up_cant: 2, down_cant: 0, mismatch: 0 }, 20),
+            (ExcitedBondConfig { up_cant: 0, down_cant: 2, mismatch: 0 }, 12),
+            (ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 1 }, 10),
+            (ExcitedBondConfig { up_cant: 2, down_cant: 1, mismatch: 2 }, 24),
+        ] {
+            draw_placements(site_placer, excited_bonds, chain_size, 2000, seed as u64);
+        }
+    }
+}
+
+#[test]
+fn k_beach_uniform_and_rejection_placers_reach_every_placement() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 1, down_cant: 1, mismatch: 0 };
+    let chain_size = 12;
+    let placements = enumeration::excited_site_placements(&excited_bonds, chain_size);
+
+    // interval splitting stops splitting once there are as many pieces as bonds, so it never places e.g. 2 3 6 7
+    for (seed, site_placer) in [SitePlacerKind::KBeach, SitePlacerKind::Uniform, SitePlacerKind::Rejection].into_iter().enumerate() {
+        let mut drawn_placements = draw_placements(site_placer, excited_bonds, chain_size, 200 * placements.len() as u64, seed as u64);
+        drawn_placements.sort();
+        drawn_placements.dedup();
+        assert_eq!(drawn_placements, placements, "{:?} does not reach every placement", site_placer);
+    }
+}
+
+#[test]
+fn rejection_placer_draws_every_placement_equally_often() {
+    let excited_bonds = ExcitedBondConfig { up_cant: 2, down_cant: 0, mismatch: 0 };
+    let chain_size = 14;
+    let placements = enumeration::excited_site_placements(&excited_bonds, chain_size);
+    let placement_indices: HashMap<&Vec<usize>, usize> = placements.iter().enumerate().map(|(index, placement)| (placement, index)).collect();
+
+    let mut counts = vec![0; placements.len()];
+    for placement in draw_placements(SitePlacerKind::Rejection, excited_bonds, chain_size, 200 * placements.len() as u64, 10) {
+        counts[placement_indices[&placement]] += 1;
+    }
+
+    let probabilities = uniform_probabilities(&counts);
+    assert!(is_uniform(&counts), "chi-square z = {}, KS distance = {}", chi_square_z_score(&counts, &probabilities), ks_distance(&counts, &probabilities));
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use crate::file_utils::RunData;
+
+/// The mean lifetime of one chain size, in sweeps of chain_size - 2 attempted moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalingPoint {
+    pub chain_size: usize,
+    pub mean_lifetime: f64,
+    pub standard_error: f64,
+}
+
+/// A fit of the mean lifetime to tau = A N^z, or to tau = A N^z exp(b N^-omega) with corrections to scaling.
+/// The correction term is the first order of the usual (1 + b N^-omega), which keeps the fit linear in ln tau.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ZFit {
+    pub z: f64,
+    // Standard error of z from the covariance of the weighted least squares fit
+    pub standard_error: f64,
+    // Delete-one jackknife error of z over the chain sizes, None when too few chain sizes are left to refit
+    pub jackknife_error: Option<f64>,
+    pub amplitude: f64,
+    // omega and b of the correction to scaling, None for the pure power law
+    pub correction_exponent: Option<f64>,
+    pub correction_amplitude: Option<f64>,
+    // Chi squared of the fit per degree of freedom, None without degrees of freedom
+    pub reduced_chi_squared: Option<f64>,
+    pub min_chain_size: usize,
+    pub max_chain_size: usize,
+    pub chain_sizes: usize,
+}
+
+/// The mean lifetimes of the chain sizes of a run file within the given range, converted to sweeps. Gillespie lifetimes
+/// are used in their own time unit, which only changes the amplitude of the fit. Chain sizes whose lifetimes are all
+/// the same have no standard error to weight them by in the fit and are left out.
+pub fn scaling_points(run_data: &RunData, min_chain_size: usize, max_chain_size: usize) -> Vec<ScalingPoint> {
+    let steps_per_sweep = |chain_size: usize| if run_data.is_continuous_time() { 1.0 } else { (chain_size - 2) as f64 };
+    run_data.chain_sizes().into_iter()
+        .filter(|chain_size| (min_chain_size..=max_chain_size).contains(chain_size))
+        .filter_map(|chain_size| {
+            let (mean_lifetime, standard_error) = run_data.mean_lifetime(chain_size)?;
+            let steps_per_sweep = steps_per_sweep(chain_size);
+            Some(ScalingPoint { chain_size, mean_lifetime: mean_lifetime / steps_per_sweep, standard_error: standard_error / steps_per_sweep })
+        })
+        .filter(|point| point.mean_lifetime > 0.0 && point.standard_error > 0.0 && (point.mean_lifetime / point.standard_error).is_finite())
+        .collect()
+}
+
+/// The two estimates of z the z data file has always held: the slope of ln tau against ln N between the smallest and
+/// the largest chain size, and the mean of the slopes between neighbouring chain sizes. Like the original estimates the
+/// slopes are taken of the mean lifetime in steps and lowered by 1 to turn steps into sweeps, gillespie lifetimes
+/// are already per sweep. None with fewer than two chain sizes.
+pub fn slope_estimates(run_data: &RunData, min_chain_size: usize, max_chain_size: usize) -> Option<(f64, f64)> {
+    let log_points: Vec<(f64, f64)> = run_data.chain_sizes().into_iter()
+        .filter(|chain_size| (min_chain_size..=max_chain_size).contains(chain_size))
+        .filter_map(|chain_size| run_data.mean_lifetime(chain_size).map(|(mean_lifetime, _)| ((chain_size as f64).ln(), mean_lifetime.ln())))
+        .filter(|(_, log_mean_lifetime)| log_mean_lifetime.is_finite())
+        .collect();
+    if log_points.len() < 2 {
+        return None;
+    }
+    let slope = |(x_1, y_1): (f64, f64), (x_2, y_2): (f64, f64)| (y_2 - y_1) / (x_2 - x_1);
+    let steps_per_sweep_exponent = if run_data.is_continuous_time() { 0.0 } else { 1.0 };
+    let endpoint_z = slope(log_points[0], log_points[log_points.len() - 1]) - steps_per_sweep_exponent;
+    let average_z = log_points.windows(2).map(|pair| slope(pair[0], pair[1])).sum::<f64>() / (log_points.len() - 1) as f64 - steps_per_sweep_exponent;
+    Some((endpoint_z, average_z))
+}
+
+/// Fits ln tau = ln A + z ln N (+ b N^-omega) by weighted least squares, every point weighted by the inverse variance of
+/// ln tau. None when there are fewer chain sizes than fit parameters.
+/// * correction_exponent: omega of the correction to scaling, None fits the pure power law
+pub fn fit_z(points: &[ScalingPoint], correction_exponent: Option<f64>) -> Option<ZFit> {
+    let (parameters, covariance, chi_squared) = fit_log_lifetimes(points, correction_exponent)?;
+    let number_of_parameters = parameters.len();
+    let degrees_of_freedom = points.len() - number_of_parameters;
+
+    // delete-one jackknife over the chain sizes
+    let jackknife_zs: Vec<f64> = (0..points.len())
+        .filter_map(|left_out| {
+            let remaining: Vec<ScalingPoint> = points.iter().enumerate().filter(|(index, _)| *index != left_out).map(|(_, point)| *point).collect();
+            fit_log_lifetimes(&remaining, correction_exponent).map(|(parameters, _, _)| parameters[1])
+        })
+        .collect();
+    let jackknife_error = (jackknife_zs.len() == points.len() && degrees_of_freedom > 0).then(|| {
+        let count = jackknife_zs.len() as f64;
+        let mean = jackknife_zs.iter().sum::<f64>() / count;
+        ((count - 1.0) / count * jackknife_zs.iter().map(|z| (z - mean).powi(2)).sum::<f64>()).sqrt()
+    });
+
+    Some(ZFit {
+        z: parameters[1],
+        standard_error: covariance[1][1].sqrt(),
+        jackknife_error,
+        amplitude: parameters[0].exp(),
+        correction_exponent,
+        correction_amplitude: correction_exponent.map(|_| parameters[2]),
+        reduced_chi_squared: (degrees_of_freedom > 0).then(|| chi_squared / degrees_of_freedom as f64),
+        min_chain_size: points.iter().map(|point| point.chain_size).min()?,
+        max_chain_size: points.iter().map(|point| point.chain_size).max()?,
+        chain_sizes: points.len(),
+    })
+}
+
+/// The parameters (ln A, z, b), their covariance and the chi squared of the weighted fit of ln tau
+fn fit_log_lifetimes(points: &[ScalingPoint], correction_exponent: Option<f64>) -> Option<(Vec<f64>, Vec<Vec<f64>>, f64)> {
+    let basis = |chain_size: usize| -> Vec<f64> {
+        let log_chain_size = (chain_size as f64).ln();
+        match correction_exponent {
+            Some(omega) => vec![1.0, log_chain_size, (chain_size as f64).powf(-omega)],
+            None => vec![1.0, log_chain_size],
+        }
+    };
+    let number_of_parameters = if correction_exponent.is_some() { 3 } else { 2 };
+    if points.len() < number_of_parameters {
+        return None;
+    }
+
+    // normal equations (X^T W X) p = X^T W y, with the error of ln tau being the relative error of tau
+    let mut normal_matrix = vec![vec![0.0; number_of_parameters]; number_of_parameters];
+    let mut right_hand_side = vec![0.0; number_of_parameters];
+    for point in points {
+        let weight = (point.mean_lifetime / point.standard_error).powi(2);
+        let row = basis(point.chain_size);
+        for i in 0..number_of_parameters {
+            right_hand_side[i] += weight * row[i] * point.mean_lifetime.ln();
+            for j in 0..number_of_parameters {
+                normal_matrix[i][j] += weight * row[i] * row[j];
+            }
+        }
+    }
+    let covariance = invert(normal_matrix)?;
+    let parameters: Vec<f64> = covariance.iter().map(|row| row.iter().zip(&right_hand_side).map(|(a, b)| a * b).sum()).collect();
+
+    let chi_squared = points.iter().map(|point| {
+        let prediction: f64 = basis(point.chain_size).iter().zip(&parameters).map(|(x, p)| x * p).sum();
+        ((point.mean_lifetime.ln() - prediction) * point.mean_lifetime / point.standard_error).powi(2)
+    }).sum();
+    Some((parameters, covariance, chi_squared))
+}
+
+/// Inverts a small matrix by Gauss-Jordan elimination with partial pivoting, None when it is singular
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let size = matrix.len();
+    let mut inverse: Vec<Vec<f64>> = (0..size).map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+    for column in 0..size {
+        let pivot = (column..size).max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))?;
+        if matrix[pivot][column] == 0.0 || !matrix[pivot][column].is_finite() {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let pivot_value = matrix[column][column];
+        for j in 0..size {
+            matrix[column][j] /= pivot_value;
+            inverse[column][j] /= pivot_value;
+        }
+        for row in 0..size {
+            if row != column {
+                let factor = matrix[row][column];
+                for j in 0..size {
+                    matrix[row][j] -= factor * matrix[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+use log::info;
+use rand::Rng;
+use rand::prelude::ThreadRng;
+
+use crate::config::{Dynamics, LifetimeStorage, RunConfig, SnapshotTrigger};
+use crate::file_utils::{self, Checkpoint, RunData};
+use crate::gillespie;
+use crate::n_fold_way;
+use crate::observables::{Measurements, ObservableCurves, ObservableSampler};
+use crate::recorder::{TrialRecorder, TrialRecording};
+use crate::rng_utils::{self, TrialRng};
+use crate::site::{BondKind, Spin};
+use crate::spin_chain::SpinChain;
+use crate::trajectory::{TrajectoryRecorder, TrajectoryWriter};
+use crate::update_scheme::UpdateSchedule;
+use crate::{evolve_chain, CHAIN_SIZE};
+
+/// The worker threads are handed the trials between checkpoints in batches of at most this many, which bounds the memory
+/// the lifetimes of the batch take before they are added to the run data
+const TRIAL_BATCH_SIZE: u64 = 100_000;
+
+/// Runs the lifetime simulation for every spin sector and chain size of the run configuration
+/// and saves one RunData file per spin sector.
+/// Every trial is driven by its own random number generator seeded from the master seed of the run.
+/// Progress is checkpointed to the storage directory, and passing the last checkpoint continues
+/// the sweep from where it stopped.
+pub fn run_simulation(config: &RunConfig, checkpoint: Option<Checkpoint>) {
+
+    // Local storage directory: ./data/runs
+    let storage_directory = &config.storage_directory;
+
+    let start = Instant::now();
+
+    println!("start time: {start:?}");
+
+    // Record the master seed in the config so the echoed config reproduces the run exactly
+    let mut config = config.clone();
+    let master_seed: u64 = match config.rng.seed {
+        Some(seed) => seed,
+        None => {
+            let mut rng_seed: ThreadRng = rand::thread_rng();
+            rng_seed.gen()
+        }
+    };
+    config.rng.seed = Some(master_seed);
+
+    let number_of_trials = config.trials;
+    let min_chain_size = config.min_chain_size;
+    let max_size = config.max_chain_size;
+
+    let spin_sector_min = match &checkpoint {
+        Some(checkpoint) => checkpoint.spin_sector,
+        None => config.min_spin_sector,
+    };
+    let spin_sector_max = config.max_spin_sector;
+
+    println!("Running chains from {min_chain_size} to size {max_size} with each chain size running {number_of_trials} times and spin sector from {spin_sector_min} to {spin_sector_max}");
+    let worker_threads = config.worker_threads();
+    println!("master seed: {master_seed}, worker threads: {worker_threads}");
+    info!("master seed: {master_seed}, worker threads: {worker_threads}");
+    let checkpoint_file = file_utils::checkpoint_file_name(storage_directory);
+    let mut resume_point = checkpoint;
+    for current_spin_sector in spin_sector_min..=spin_sector_max {
+        info!("spin sector: {current_spin_sector}");
+        println!("spin sector: {current_spin_sector}");
+        let excited_bonds = config.excited_bond_map.with_spin_sector(current_spin_sector);
+        let excited_bond_map = excited_bonds.to_map();
+        let mut current_size: usize;
+        let min_chain_size_label: usize;
+        let hard_limit = excited_bonds.minimum_chain_size();
+        if min_chain_size < hard_limit {
+            current_size = hard_limit;
+            min_chain_size_label = hard_limit;
+        } else {
+            current_size = min_chain_size;
+            min_chain_size_label = min_chain_size;
+        }
+
+        let mut run_data: RunData = RunData::new(&config, current_spin_sector);
+        let mut first_trial = 0;
+        let mut trajectory_length = None;
+        if let Some(checkpoint) = resume_point.take() {
+            println!("resuming at chain size {} trial {}", checkpoint.next_chain_size, checkpoint.next_trial);
+            info!("resuming at chain size {} trial {}", checkpoint.next_chain_size, checkpoint.next_trial);
+            run_data = checkpoint.run_data;
+            current_size = checkpoint.next_chain_size;
+            first_trial = checkpoint.next_trial;
+            trajectory_length = checkpoint.trajectory_length;
+        }
+
+        while current_size <= max_size {
+            let checkpoint_interval = if config.checkpoint_interval == 0 { number_of_trials } else { config.checkpoint_interval };
+            let trajectory_writer: Option<Mutex<TrajectoryWriter>> = match config.trajectory.snapshot {
+                SnapshotTrigger::Off => None,
+                _ => Some(Mutex::new(TrajectoryWriter::new(&config.trajectory, storage_directory, current_spin_sector, current_size, first_trial, trajectory_length.take()))),
+            };
+            while first_trial < number_of_trials {
+                let last_trial = number_of_trials.min(first_trial + checkpoint_interval);
+                for batch_start in (first_trial..last_trial).step_by(TRIAL_BATCH_SIZE as usize) {
+                    let batch_end = last_trial.min(batch_start + TRIAL_BATCH_SIZE);
+                    let trial_results = run_trials(&config, master_seed, current_spin_sector, &excited_bond_map, current_size, batch_start..batch_end, worker_threads, trajectory_writer.as_ref());
+                    for (lifetime, measurements) in trial_results {
+                        update_run_data(&mut run_data, &config, current_size, lifetime, &measurements);
+                    }
+                }
+                first_trial = last_trial;
+                if first_trial < number_of_trials {
+                    let trajectory_length = trajectory_writer.as_ref().map(|trajectory_writer| trajectory_writer.lock().unwrap().length());
+                    let checkpoint = Checkpoint { config: config.clone(), spin_sector: current_spin_sector, next_chain_size: current_size, next_trial: first_trial, run_data: run_data.clone(), trajectory_length };
+                    file_utils::save_checkpoint(&checkpoint_file, &checkpoint);
+                }
+            }
+            println!("completed spin chain of size {current_size}");
+            info!("completed spin chain of size {current_size}");
+            current_size += config.chain_size_step;
+            first_trial = 0;
+            let checkpoint = Checkpoint { config: config.clone(), spin_sector: current_spin_sector, next_chain_size: current_size, next_trial: 0, run_data: run_data.clone(), trajectory_length: None };
+            file_utils::save_checkpoint(&checkpoint_file, &checkpoint);
+        }
+        let directory_string = format!("{}/run_ss_{}_cs_{}_{}.json", storage_directory, current_spin_sector, min_chain_size_label, max_size);
+        file_utils::save_data_formatted(directory_string, &run_data, config.output_format);
+
+        // The finished sector is safely on disk, so a resume starts at the beginning of the next one
+        if current_spin_sector < spin_sector_max {
+            let next_sector_bonds = config.excited_bond_map.with_spin_sector(current_spin_sector + 1);
+            let next_chain_size = min_chain_size.max(next_sector_bonds.minimum_chain_size());
+            let checkpoint = Checkpoint { config: config.clone(), spin_sector: current_spin_sector + 1, next_chain_size, next_trial: 0, run_data: RunData::new(&config, current_spin_sector + 1), trajectory_length: None };
+            file_utils::save_checkpoint(&checkpoint_file, &checkpoint);
+        }
+    }
+    file_utils::remove_checkpoint(&checkpoint_file);
+}
+
+/// Runs a range of trials of a single chain size, spreading the trials over the worker threads.
+/// Each trial is seeded from its own index, so the lifetimes and observable measurements are returned in trial order
+/// and are identical no matter how many threads ran them.
+/// Trajectories are written to the trajectory writer as soon as their trial finishes, so they are only in trial order with a
+/// single worker thread.
+#[allow(clippy::too_many_arguments)]
+fn run_trials(config: &RunConfig, master_seed: u64, spin_sector: usize, excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, trials: Range<u64>, worker_threads: usize, trajectory_writer: Option<&Mutex<TrajectoryWriter>>) -> Vec<(Lifetime, Measurements)> {
+    let next_trial = AtomicU64::new(trials.start);
+    let mut trial_results: Vec<(Lifetime, Measurements)> = vec![(Lifetime { step_count: 0, time: None, immortal: false, censored: false }, Vec::new()); (trials.end - trials.start) as usize];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..worker_threads).map(|_| {
+            scope.spawn(|| {
+                let mut completed_trials: Vec<(u64, Lifetime, Measurements)> = Vec::new();
+                loop {
+                    let trial = next_trial.fetch_add(1, Ordering::Relaxed);
+                    if trial >= trials.end {
+                        break;
+                    }
+                    let seed = rng_utils::trial_seed(master_seed, spin_sector, chain_size, trial);
+                    let mut rng = TrialRng::new(config.rng.kind, seed);
+                    let mut recording = TrialRecording { trajectory: TrajectoryRecorder::new(&config.trajectory, trial, seed), observables: ObservableSampler::new(&config.observables) };
+                    let lifetime = run_recorded_trial(config, excited_bond_map, chain_size, &mut recording, &mut rng);
+                    if let Some(trajectory_writer) = &trajectory_writer {
+                        trajectory_writer.lock().unwrap().write_trajectory(&recording.trajectory.snapshots);
+                    }
+                    completed_trials.push((trial, lifetime, recording.observables.measurements));
+                }
+                completed_trials
+            })
+        }).collect();
+
+        for worker in workers {
+            for (trial, lifetime, measurements) in worker.join().unwrap() {
+                trial_results[(trial - trials.start) as usize] = (lifetime, measurements);
+            }
+        }
+    });
+
+    trial_results
+}
+
+/// How long a single chain lived for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lifetime {
+    // Number of steps of the discrete dynamics, or the number of moves that fired for the gillespie dynamics
+    pub step_count: u128,
+    // Time of death in the configured time unit, only measured by the gillespie dynamics
+    pub time: Option<f64>,
+    // The deterministic update scheme cycles through the same chains forever without killing the chain.
+    // step_count is then the number of steps until the chain first returned to its starting spins
+    pub immortal: bool,
+    // The chain was still alive when it reached the step cap of the run, so it lives for longer than step_count and time
+    pub censored: bool,
+}
+
+/// Generates an excited chain and evolves it until it dies.
+/// Returns how long the chain lived for.
+pub fn run_trial<R: Rng>(config: &RunConfig, excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, rng: &mut R) -> Lifetime {
+    run_recorded_trial(config, excited_bond_map, chain_size, &mut TrialRecording::disabled(), rng)
+}
+
+/// run_trial, reporting the evolution of the chain to the recorder
+pub fn run_recorded_trial<R: Rng>(config: &RunConfig, excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, recorder: &mut impl TrialRecorder, rng: &mut R) -> Lifetime {
+    let mut is_alive = true;
+    // info!("generating spin chain");
+    let mut spin_chain: SpinChain<CHAIN_SIZE> = SpinChain::new_excited_with_placer(excited_bond_map, chain_size, &config.site_placer, rng);
+    //print_chain(&spin_chain.chain);
+
+    match config.dynamics {
+        Dynamics::RandomIndex => {}
+        Dynamics::NFoldWay => {
+            return n_fold_way::evolve_until_death(&mut spin_chain, chain_size, config.step_cap(), config.track_bond_identity, recorder, rng);
+        }
+        Dynamics::Gillespie => {
+            return gillespie::evolve_until_death(&mut spin_chain, chain_size, config.continuous_time.move_rate(), config.step_cap(), config.track_bond_identity, recorder, rng);
+        }
+    }
+
+    let mut update_schedule = UpdateSchedule::new(config.update_scheme, chain_size);
+    let max_steps = config.step_cap();
+    let is_recording = recorder.is_recording();
+    let initial_spins: Vec<Spin> = spin_chain.chain[..chain_size].iter().map(|site| site.spin()).collect();
+    let mut step_count = 0;
+    let mut move_count = 0;
+    recorder.record(0, 0, None, &spin_chain.chain);
+
+    while is_alive {
+        let random_index = update_schedule.next_index(chain_size, rng);
+        // only the recorder needs to know whether the step moved anything
+        let is_move = is_recording && n_fold_way::is_active_move(&spin_chain.chain, random_index);
+        is_alive = evolve_chain(&mut spin_chain.chain, random_index, chain_size);
+        if config.track_bond_identity {
+            spin_chain.relabel_excited_sites();
+        }
+        step_count += 1;
+        if is_alive && is_recording {
+            if is_move {
+                move_count += 1;
+            }
+            recorder.record(step_count, move_count, None, &spin_chain.chain);
+        }
+        if is_alive && update_schedule.completed_deterministic_sweep() && spin_chain.chain[..chain_size].iter().map(|site| site.spin()).eq(initial_spins.iter().copied()) {
+            recorder.finish(step_count, move_count, None, &spin_chain.chain);
+            return Lifetime { step_count, time: None, immortal: true, censored: false };
+        }
+        if is_alive && max_steps == Some(step_count) {
+            recorder.finish(step_count, move_count, None, &spin_chain.chain);
+            return Lifetime { step_count, time: None, immortal: false, censored: true };
+        }
+    }
+    recorder.finish(step_count, move_count, None, &spin_chain.chain);
+    Lifetime { step_count, time: None, immortal: false, censored: false }
+}
+
+fn update_run_data(run_data: &mut RunData, config: &RunConfig, chain_size: usize, lifetime: Lifetime, measurements: &Measurements) {
+    // immortal chains stop being measured once they cycle, so they are left out of the observables as well
+    if lifetime.immortal {
+        *run_data.immortal_trials.entry(chain_size).or_insert(0) += 1;
+        return;
+    }
+    if !config.observables.quantities.is_empty() {
+        run_data.observables.entry(chain_size)
+            .or_insert_with(|| ObservableCurves::new(&config.observables))
+            .add_trial(&config.observables.quantities, measurements);
+    }
+    if config.lifetime_storage == LifetimeStorage::Summary {
+        run_data.summaries.entry(chain_size).or_default().add(&lifetime);
+        return;
+    }
+    if lifetime.censored {
+        run_data.censored_runs.entry(chain_size).or_default().push(lifetime.step_count);
+        if let Some(time) = lifetime.time {
+            run_data.censored_times.entry(chain_size).or_default().push(time);
+        }
+        return;
+    }
+    let contains_chain_size = run_data.runs.contains_key(&chain_size);
+    if contains_chain_size {
+        run_data.runs.get_mut(&chain_size).unwrap().push(lifetime.step_count);
+
+    } else {
+        let new_run_vec: Vec<u128> = vec![lifetime.step_count];
+        run_data.runs.insert(chain_size, new_run_vec);
+    }
+    if let Some(time) = lifetime.time {
+        run_data.times.entry(chain_size).or_default().push(time);
+    }
+}
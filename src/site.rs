@@ -0,0 +1,127 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The spin of a site. The Fredkin dynamics only ever look at spins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Spin {
+    Up,
+    Down,
+}
+
+impl Spin {
+    /// +1 for an up spin and -1 for a down spin
+    pub fn value(self) -> i8 {
+        match self {
+            Spin::Up => 1,
+            Spin::Down => -1,
+        }
+    }
+}
+
+/// The excitation types a chain can hold. Each bond occupies 2 sites of the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BondKind {
+    /// A pair of unmatched up spins [[
+    UpCant,
+    /// A pair of unmatched down spins ]]
+    DownCant,
+    /// An unmatched down spin followed by an unmatched up spin }{
+    Mismatch,
+}
+
+/// A single site of the chain. Regular sites belong to the Dyck words of the chain, the others are
+/// endpoints of excited bonds. Serialized as the site codes 1, -1, 2, -3, -4 and 4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "i8", try_from = "i8")]
+pub enum Site {
+    Up,
+    Down,
+    /// An endpoint of an up-canted bond
+    UpCant,
+    /// An endpoint of a down-canted bond
+    DownCant,
+    /// The left half of a mismatch bond
+    MismatchDown,
+    /// The right half of a mismatch bond
+    MismatchUp,
+}
+
+impl Site {
+    pub fn spin(self) -> Spin {
+        match self {
+            Site::Up | Site::UpCant | Site::MismatchUp => Spin::Up,
+            Site::Down | Site::DownCant | Site::MismatchDown => Spin::Down,
+        }
+    }
+
+    /// The excited bond this site is an endpoint of, None for regular sites
+    pub fn bond_kind(self) -> Option<BondKind> {
+        match self {
+            Site::Up | Site::Down => None,
+            Site::UpCant => Some(BondKind::UpCant),
+            Site::DownCant => Some(BondKind::DownCant),
+            Site::MismatchDown | Site::MismatchUp => Some(BondKind::Mismatch),
+        }
+    }
+
+    /// The bracket the site is printed as
+    pub fn symbol(self) -> char {
+        match self {
+            Site::Up => '(',
+            Site::Down => ')',
+            Site::UpCant => '[',
+            Site::DownCant => ']',
+            Site::MismatchDown => '}',
+            Site::MismatchUp => '{',
+        }
+    }
+}
+
+impl From<Spin> for Site {
+    fn from(spin: Spin) -> Site {
+        match spin {
+            Spin::Up => Site::Up,
+            Spin::Down => Site::Down,
+        }
+    }
+}
+
+// up = 1, down = -1, up_cant = 2, down_cant = -3, mismatch = -4 (down half) and 4 (up half).
+// The sign of a site code is its spin. The i8 chains only planned down_cant = 3 and mismatch = 4, but then the spin of
+// a down-canted site and of either half of a mismatch bond could not be told from its code, so those carry a sign.
+impl From<Site> for i8 {
+    fn from(site: Site) -> i8 {
+        match site {
+            Site::Up => 1,
+            Site::Down => -1,
+            Site::UpCant => 2,
+            Site::DownCant => -3,
+            Site::MismatchDown => -4,
+            Site::MismatchUp => 4,
+        }
+    }
+}
+
+impl TryFrom<i8> for Site {
+    type Error = String;
+
+    fn try_from(code: i8) -> Result<Site, String> {
+        match code {
+            1 => Ok(Site::Up),
+            -1 => Ok(Site::Down),
+            2 => Ok(Site::UpCant),
+            -3 => Ok(Site::DownCant),
+            -4 => Ok(Site::MismatchDown),
+            4 => Ok(Site::MismatchUp),
+            _ => Err(format!("{} is not a site code", code)),
+        }
+    }
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
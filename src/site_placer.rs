@@ -0,0 +1,471 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration;
+use crate::site::Site;
+
+/// Chooses the sites of the chain that hold the endpoints of the excited bonds.
+/// Every placer returns 2 * number_of_bonds sites that alternate between even and odd sites, starting on an even site,
+/// outside of the sites reserved at the edges of the chain. The sites are labelled with their bond type afterwards.
+pub trait SitePlacer {
+    /// * excited_site_indices: An empty map that will be populated with the excited sites
+    /// * number_of_bonds: The number of bonds that one wishes to generate
+    /// * left_reserved, right_reserved: the sites at the edges of the chain that cannot be excited, see reserved_edge_sites
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R);
+}
+
+/// Draws random sites until there are enough even and odd ones, starting over when they do not alternate.
+/// Every placement of the sites is equally likely, but placements are not weighted by the number of Dyck words around them.
+pub struct RejectionPlacer;
+
+/// Splits the chain into even-to-odd pieces and uses the endpoints of randomly chosen pieces.
+/// The chain stops being split once there are as many pieces as bonds, so some placements can never be drawn.
+pub struct IntervalSplittingPlacer;
+
+/// Places the sites from left to right with sequential probabilities that only depend on the number of sites left to place.
+/// Placements are not weighted by the number of Dyck words around them, so configurations are not equally likely.
+pub struct KBeachPlacer;
+
+/// Places the sites from left to right with probabilities weighted by the number of configurations each choice leaves,
+/// so every configuration of the chain is equally likely
+pub struct UniformPlacer;
+
+impl SitePlacer for RejectionPlacer {
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+        let mut is_valid_map = false;
+        while !is_valid_map {
+            excited_site_indices.clear();
+            is_valid_map = populate_up_cant_site_index_map(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng);
+        }
+    }
+}
+
+impl SitePlacer for IntervalSplittingPlacer {
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+        populate_up_cant_site_index_map_v2(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng);
+    }
+}
+
+impl SitePlacer for KBeachPlacer {
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+        populate_up_cant_site_index_map_k_beach(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng);
+    }
+}
+
+impl SitePlacer for UniformPlacer {
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+        populate_up_cant_site_index_map_uniform(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng);
+    }
+}
+
+/// The site placers a run can be configured with
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SitePlacerKind {
+    /// Sequential probabilities, configurations are not equally likely (populate_up_cant_site_index_map_k_beach)
+    #[default]
+    KBeach,
+    /// Sequential probabilities weighted by ballot numbers, every configuration is equally likely (populate_up_cant_site_index_map_uniform)
+    Uniform,
+    /// Rejection sampling of the sites (populate_up_cant_site_index_map)
+    Rejection,
+    /// Interval splitting (populate_up_cant_site_index_map_v2)
+    IntervalSplitting,
+}
+
+impl SitePlacer for SitePlacerKind {
+    fn place_excited_sites<R: Rng>(&self, excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+        match self {
+            SitePlacerKind::KBeach => KBeachPlacer.place_excited_sites(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng),
+            SitePlacerKind::Uniform => UniformPlacer.place_excited_sites(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng),
+            SitePlacerKind::Rejection => RejectionPlacer.place_excited_sites(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng),
+            SitePlacerKind::IntervalSplitting => IntervalSplittingPlacer.place_excited_sites(excited_site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng),
+        }
+    }
+}
+
+impl SitePlacerKind {
+    /// The probability the placer places the excited sites at the given placement, None for the placers whose distribution
+    /// is not known in closed form
+    /// * placement: the excited sites in increasing order
+    pub fn placement_probability(&self, placement: &[usize], number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize) -> Option<f64> {
+        match self {
+            SitePlacerKind::KBeach => Some(k_beach_placement_probability(placement, number_of_bonds, chain_size, left_reserved, right_reserved)),
+            SitePlacerKind::Uniform => Some(uniform_placement_probability(placement, number_of_bonds, chain_size, left_reserved, right_reserved)),
+            SitePlacerKind::Rejection | SitePlacerKind::IntervalSplitting => None,
+        }
+    }
+}
+
+/// A function that will generate indices that will have an excited bond
+/// * excited_site_indices: An empty map that will be populated with the index for an excited bond as the key and the excitation type for the bond
+/// * number_of_bonds: The number of bonds that one wishes to generate
+/// * left_reserved, right_reserved: the sites at the edges of the chain that cannot be excited, see reserved_edge_sites
+///
+/// Returns whether the drawn sites alternate between even and odd, the map has to be cleared and drawn again when they do not.
+pub fn populate_up_cant_site_index_map<R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) -> bool {
+    let mut odd_number_counter = 0;
+    let mut even_number_counter = 0;
+
+    // Here we generate n even numbers and n odd numbers that will be paired with eachother as bond sites
+    while odd_number_counter < number_of_bonds || even_number_counter < number_of_bonds {
+        let random_number = rng.gen_range(left_reserved..chain_size-right_reserved);
+        if random_number.is_multiple_of(2) && even_number_counter < number_of_bonds && !excited_site_indices.contains_key(&random_number) {
+            even_number_counter += 1;
+            excited_site_indices.insert(random_number, Site::UpCant);
+        } else if !random_number.is_multiple_of(2) && odd_number_counter < number_of_bonds && !excited_site_indices.contains_key(&random_number) {
+            odd_number_counter += 1;
+            excited_site_indices.insert(random_number, Site::UpCant);
+        }
+    }
+
+    validate_site_index_map(excited_site_indices)
+
+}
+
+/// A function that splits the chain into pieces that start on an even site and end on an odd site, and uses the endpoints of
+/// randomly chosen pieces as the excited sites
+/// * excited_site_indices: An empty map that will be populated with the index for an excited bond as the key and the excitation type for the bond
+/// * number_of_bonds: The number of bonds that one wishes to generate
+/// * left_reserved, right_reserved: the sites at the edges of the chain that cannot be excited, see reserved_edge_sites
+pub fn populate_up_cant_site_index_map_v2<R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size:usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+
+    // the pieces are split by their position in available_sites, which has the same parity as the site since left_reserved is even
+    let mut available_sites: Vec<usize> = Vec::new();
+
+    for integer in left_reserved..chain_size-right_reserved {
+
+        available_sites.push(integer);
+
+    }
+
+    let maximum_index = available_sites.len();
+
+    //i_e_i
+    let mut initial_even_index: usize = 0;
+    let mut initial_even_index_determined = false;
+    //i_o_i
+    let mut initial_odd_index: usize = maximum_index;
+    let mut initial_odd_index_determined = false;
+    let mut number_of_subcovers: usize;
+
+    let mut are_sites_populated = false;
+
+    while !initial_even_index_determined {
+        initial_even_index = rng.gen_range(0..maximum_index);
+        if initial_even_index.is_multiple_of(2) {
+            initial_even_index_determined = true;
+        }
+    }
+    while !initial_odd_index_determined {
+        initial_odd_index = rng.gen_range(initial_even_index..maximum_index);
+        if !initial_odd_index.is_multiple_of(2) {
+            initial_odd_index_determined = true;
+        }
+    }
+
+    // we can now split the chain into multiple logical pieces
+    // 0 1 2 .. N - m N - m + 1.. N-3 N-2 N-1 N
+    // There are three possible configurations after the initial split:
+    // one like [0..inital_even_index - 1][initial_even_index..initial_odd_index][initial_odd_index + 1.. N-2]
+    // or one like [0..initial_odd_index][initial_odd_index...N-2]
+    // or [0...N-2]
+    let mut chain_pieces: Vec<&[usize]> = Vec::new();
+    if initial_even_index == 0 {     
+        // If we have i_e_i = 0, then we will only need to split the chain once maximum. If we also have i_o_i = maximum index we don't need to do
+        // any splitting since both endpoints will now be part of a bond.
+        if initial_odd_index == maximum_index - 1 {
+            chain_pieces.push(&available_sites);
+            number_of_subcovers = 1;
+        } else {
+            let (left, right) =  available_sites.split_at(initial_odd_index+1);
+            chain_pieces.push(left);
+            chain_pieces.push(right);
+            number_of_subcovers = 2;
+        }
+    } else {
+        // if we have i_o_i == chain_size -3 we will only need to split once. Otherwise we will have split the chain into 3 pieces
+        if initial_odd_index == maximum_index - 1 {
+            let (left, right) = available_sites.split_at(initial_even_index);
+            chain_pieces.push(left);
+            chain_pieces.push(right);
+            number_of_subcovers = 2;
+        } else {
+            let (left, right) = available_sites.split_at(initial_even_index);
+            chain_pieces.push(left);
+            // the split has created a new logical chain called "right". We need to offset the indexing by the length of the chain
+            // called "left" since the original chain structure was permanently modified
+            let (middle, right) = right.split_at(initial_odd_index+1 - left.len());
+            chain_pieces.push(middle);
+            chain_pieces.push(right);     
+            number_of_subcovers = 3;
+        }
+    }
+
+    let mut two_entry_vecs: Vec<&[usize]>= Vec::new();
+    if number_of_subcovers < number_of_bonds {
+
+        while !are_sites_populated {
+            let random_piece = rng.gen_range(0..chain_pieces.len());
+            let chain_to_split = chain_pieces.remove(random_piece);
+            let random_odd = rng.gen_range(0..chain_to_split.len());
+            // we do not want to attempt to split chains that only have 2 elements
+            // nor do we want to split a chain at its endpoint since it will give us an
+            // empty slice.
+            // Because we remove the "chain to split" from the "chain pieces"
+            // we need to be sure that we re-insert them if they are not modified at all
+            // otherwise we will lose access to them once the loop continues.
+            if random_odd % 2 != 0 && random_odd != chain_to_split.len() - 1{
+                if chain_to_split.len() > 2 {
+                    let (left, right) = chain_to_split.split_at(random_odd+1);
+                    if left.len() <= 2 && right.len() <= 2{
+                        two_entry_vecs.push(left);
+                        two_entry_vecs.push(right);
+                    } else if left.len() <= 2 && right.len() > 2 {
+                        two_entry_vecs.push(left);
+                        chain_pieces.push(right);
+                    } else if left.len() > 2 && right. len() <= 2 {
+                        chain_pieces.push(left);
+                        two_entry_vecs.push(right);
+                    } else {
+                        chain_pieces.push(left);
+                        chain_pieces.push(right);
+                    }
+                    number_of_subcovers+=1;
+                } else {
+                    two_entry_vecs.push(chain_to_split);
+                }
+                if number_of_subcovers >= number_of_bonds {
+                    are_sites_populated = true;
+
+                }  
+            } else {
+                chain_pieces.push(chain_to_split);
+            }
+        }
+    }
+
+   chain_pieces.append(&mut two_entry_vecs);
+
+    for _i in 0..number_of_bonds {
+        let random_index = rng.gen_range(0..chain_pieces.len());
+        let random_endpoints = chain_pieces.remove(random_index);
+        excited_site_indices.insert(*random_endpoints.first().unwrap(), Site::UpCant);
+        excited_site_indices.insert(*random_endpoints.last().unwrap(), Site::UpCant);
+    }
+}
+
+/// A function that places the excited sites one at a time from left to right
+/// * excited_site_indices: An empty map that will be populated with the indices of the excited sites
+/// * number_of_bonds: The number of bonds that one wishes to generate
+/// * left_reserved, right_reserved: the sites at the edges of the chain that cannot be excited, see reserved_edge_sites
+///
+/// Walking along the chain, every candidate site is either taken with the probability of k_beach_site_probability
+/// or skipped along with the site after it, so the next candidate has the same parity.
+pub fn populate_up_cant_site_index_map_k_beach<R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+
+    let mut mo:usize = 0;
+    let mut me:usize = 0;
+
+    let mut i:usize = left_reserved;
+
+    while me != number_of_bonds || mo != number_of_bonds {
+
+        let rand: f64 = rng.gen_range(0.0f64..1f64);
+        let p = k_beach_site_probability(i, me, mo, number_of_bonds, chain_size, left_reserved, right_reserved);
+
+        if rand < p {
+            excited_site_indices.insert(i, Site::UpCant);
+            if i.is_multiple_of(2) {
+                me += 1;
+            } else {
+                mo += 1;
+            }
+            i += 1;
+        } else {
+            i += 2;
+        }
+    }
+}
+
+/// The probability that k_beach places an excited site at candidate i
+/// * i: the candidate site
+/// * me, mo: the number of even and odd excited sites placed so far
+///
+/// The sites are drawn on the part of the chain that is not reserved, extended by 2 sites since k_beach always keeps
+/// the 2 rightmost sites of the chain it is given free. With size the length of that chain and j = i - left_reserved,
+/// an even candidate is taken with probability 2 (number_of_bonds - me) / (size - 2 - j) and an odd one with
+/// 2 (number_of_bonds - mo) / (size - 1 - j). These do not weigh a placement by the number of Dyck words that fit
+/// around it, so the chains are not all equally likely.
+#[allow(clippy::too_many_arguments)]
+pub fn k_beach_site_probability(i: usize, me: usize, mo: usize, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize) -> f64 {
+    let size = chain_size + 2 - left_reserved - right_reserved;
+    // left_reserved is even, so j has the parity of i
+    let j = i - left_reserved;
+    let p = if j.is_multiple_of(2) {
+        (2 * (number_of_bonds - me)) as f64 / (size - 2 - j) as f64
+    } else {
+        (2 * (number_of_bonds - mo)) as f64 / (size - 1 - j) as f64
+    };
+    p.min(1.0)
+}
+
+/// The probability that populate_up_cant_site_index_map_k_beach places the excited sites at the given placement, 0 when it never does.
+/// Follows the candidates of the sampler and multiplies in the probability of skipping every candidate in front of the next site
+/// and of taking the candidate at it.
+/// * placement: the excited sites in increasing order
+pub fn k_beach_placement_probability(placement: &[usize], number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize) -> f64 {
+    let mut mo:usize = 0;
+    let mut me:usize = 0;
+
+    let mut i:usize = left_reserved;
+    let mut probability = 1.0;
+    for site in placement {
+        while i < *site {
+            probability *= 1.0 - k_beach_site_probability(i, me, mo, number_of_bonds, chain_size, left_reserved, right_reserved);
+            i += 2;
+        }
+        if i != *site {
+            return 0.0;
+        }
+        probability *= k_beach_site_probability(i, me, mo, number_of_bonds, chain_size, left_reserved, right_reserved);
+        if i.is_multiple_of(2) {
+            me += 1;
+        } else {
+            mo += 1;
+        }
+        i += 1;
+    }
+    probability
+}
+
+/// A function that places the excited sites one at a time from left to right, so that every configuration of the chain is equally likely.
+/// * excited_site_indices: An empty map that will be populated with the indices of the excited sites
+/// * number_of_bonds: The number of bonds that one wishes to generate
+/// * left_reserved, right_reserved: the sites at the edges of the chain that cannot be excited, see reserved_edge_sites
+///
+/// Every excited site is drawn among the candidates after the previous one, of the right parity, with the weights of
+/// uniform_site_weights. Costs one pass over the candidates per excited site.
+pub fn populate_up_cant_site_index_map_uniform<R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize, rng: &mut R) {
+    with_log_factorials(chain_size + 1, |log_factorials| {
+        let mut first_candidate = left_reserved;
+        // the first site of the Dyck word the next excited site closes
+        let mut gap_start = 0;
+        for placed_sites in 0..2 * number_of_bonds {
+            let weights = uniform_site_weights(log_factorials, first_candidate, gap_start, 2 * number_of_bonds - placed_sites, chain_size, right_reserved);
+            let mut rand: f64 = rng.gen_range(0.0f64..1f64);
+            // falls back on the last candidate with a weight should rounding leave rand above the last cumulative weight
+            let mut chosen = weights.iter().rposition(|weight| *weight > 0.0).unwrap();
+            for (candidate, weight) in weights.iter().enumerate() {
+                if rand < *weight {
+                    chosen = candidate;
+                    break;
+                }
+                rand -= weight;
+            }
+            let site = first_candidate + 2 * chosen;
+            excited_site_indices.insert(site, Site::UpCant);
+            gap_start = site + 1;
+            first_candidate = site + 1;
+        }
+    });
+}
+
+/// The probability that populate_up_cant_site_index_map_uniform places the next excited site at each of the candidates
+/// first_candidate, first_candidate + 2, ... that leave room for the other excited sites in front of the reserved right edge.
+/// * log_factorials: the table from enumeration::log_factorials
+/// * gap_start: the first site after the previous excited site, 0 when no site has been placed yet
+/// * remaining_sites: the number of excited sites still to be placed, including this one
+///
+/// A uniformly drawn configuration has its next excited site at candidate j with the number of configurations that have the
+/// site there over the number of configurations. Placing the site at candidate j leaves a Dyck word on [gap_start, j) and
+/// the rest of the chain after j to be filled with remaining_sites - 1 excited sites and Dyck words. The configurations of
+/// the rest are counted by ballot numbers, minus the ones whose last excited site falls into the reserved right edge, so
+/// candidate j is weighted by catalan((j - gap_start)/2) * completions(j + 1).
+pub fn uniform_site_weights(log_factorials: &[f64], first_candidate: usize, gap_start: usize, remaining_sites: usize, chain_size: usize, right_reserved: usize) -> Vec<f64> {
+    let log_weight = |candidate: usize| {
+        let log_dyck_words = enumeration::log_ballot_number(log_factorials, candidate - gap_start, 0);
+        let rest_length = chain_size - candidate - 1;
+        let rest_sites = remaining_sites - 1;
+        let log_completions = if right_reserved == 0 {
+            enumeration::log_ballot_number(log_factorials, rest_length, rest_sites)
+        } else if rest_sites == 0 {
+            // the Dyck word behind the last excited site may not be empty
+            if rest_length == 0 { f64::NEG_INFINITY } else { enumeration::log_ballot_number(log_factorials, rest_length, 0) }
+        } else {
+            // every configuration of the rest minus the ones ending in an excited site
+            let all = enumeration::log_ballot_number(log_factorials, rest_length, rest_sites);
+            let ending_excited = enumeration::log_ballot_number(log_factorials, rest_length - 1, rest_sites - 1);
+            all + (1.0 - (ending_excited - all).exp()).max(0.0).ln()
+        };
+        log_dyck_words + log_completions
+    };
+
+    let last_candidate = chain_size - right_reserved - remaining_sites;
+    let log_weights: Vec<f64> = (first_candidate..=last_candidate).step_by(2).map(log_weight).collect();
+    let largest_log_weight = log_weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = log_weights.iter().map(|log_weight| (log_weight - largest_log_weight).exp()).collect();
+    let total_weight: f64 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total_weight).collect()
+}
+
+/// The probability that populate_up_cant_site_index_map_uniform places the excited sites at the given placement, 0 when it never does
+/// * placement: the excited sites in increasing order
+pub fn uniform_placement_probability(placement: &[usize], number_of_bonds: usize, chain_size: usize, left_reserved: usize, right_reserved: usize) -> f64 {
+    with_log_factorials(chain_size + 1, |log_factorials| {
+        let mut first_candidate = left_reserved;
+        let mut gap_start = 0;
+        let mut probability = 1.0;
+        for (placed_sites, site) in placement.iter().enumerate() {
+            if *site < first_candidate || !(site - first_candidate).is_multiple_of(2) {
+                return 0.0;
+            }
+            let weights = uniform_site_weights(log_factorials, first_candidate, gap_start, 2 * number_of_bonds - placed_sites, chain_size, right_reserved);
+            probability *= weights.get((site - first_candidate) / 2).copied().unwrap_or(0.0);
+            gap_start = site + 1;
+            first_candidate = site + 1;
+        }
+        probability
+    })
+}
+
+thread_local! {
+    /// The ln(n!) table of the uniform placer, built once per thread and only rebuilt when a longer chain comes along
+    static LOG_FACTORIALS: RefCell<Vec<f64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Calls the function with a table of ln(n!) covering at least n = 0..=size
+fn with_log_factorials<T>(size: usize, function: impl FnOnce(&[f64]) -> T) -> T {
+    LOG_FACTORIALS.with(|log_factorials| {
+        let mut log_factorials = log_factorials.borrow_mut();
+        if log_factorials.len() <= size {
+            *log_factorials = enumeration::log_factorials(size);
+        }
+        function(&log_factorials)
+    })
+}
+
+/// A function that ensures the excited site indices are in even, odd, even, odd, even,... order
+/// * excited_site_indices: A map that contains the endpoints for excited bonds and the bond type at that index
+pub fn validate_site_index_map(excited_site_indices: &BTreeMap<usize, Site>) -> bool {
+    let mut is_even = true;
+    let mut is_valid_map = true;
+
+    for key in excited_site_indices.keys() {
+        if is_even && *key % 2 != 0 {
+            // println!("This number is odd and shouldn't be: {}", key);
+            is_valid_map = false;
+        } else if !is_even && *key % 2 == 0 {
+            // println!("This number is even and shouldn't be: {}", key);
+
+            is_valid_map = false;
+        }
+        is_even = !is_even;
+    }
+
+    is_valid_map
+
+}
@@ -2,15 +2,21 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::i8;
+use std::iter;
 use rand::Rng;
-use rand_mt::Mt19937GenRand64;
+
+use crate::config::ExcitedBondConfig;
+use crate::enumeration;
+use crate::site::{BondKind, Site, Spin};
+use crate::site_placer::{SitePlacer, SitePlacerKind};
+
 // Spin chain struct
 #[derive(Clone)]
 pub struct SpinChain<const N: usize> {
-    pub chain: Vec<i8>,
+    pub chain: Vec<Site>,
     pub chain_hash: u64,
-    pub spin_sector: usize
+    // needed to tell mismatch sites from canted sites when the excited sites are re-labelled
+    pub number_of_mismatch_bonds: usize
 }
 
 /// Creates a Spin Chain based on "height above horizon".
@@ -21,49 +27,47 @@ pub struct SpinChain<const N: usize> {
 //    Memory Access: Accessing elements in an array is very efficient because the compiler knows the size and can perform bounds checking efficiently.
 impl<const N: usize> SpinChain<N> {
 
-    /// A function for generating a spin chain with excited up-cant bonds
+    /// A function for generating a spin chain with excited up-cant, down-cant and mismatch bonds
     /// 
-    /// * 'excited_bond_map': A hashmap that contains 3 key-value pairs in the form (bond type, number of bonds). The keys are 0,1,2 for up-canted, down-canted, and mismatch bond types. 
+    /// * 'excited_bond_map': A hashmap that contains key-value pairs in the form (bond type, number of bonds). Bond types missing from the map have no bonds.
+    ///
+    /// Every excited site is an unmatched spin. Up-canted bonds are a pair of unmatched up spins [[, down-canted bonds a pair
+    /// of unmatched down spins ]] and mismatch bonds an unmatched down spin followed by an unmatched up spin }{.
+    /// Since an unmatched down spin to the right of an unmatched up spin would pair up with it, all of the unmatched down spins
+    /// come first: ]] ... }} ... {{ ... [[ with Dyck words filling the space in between.
+    #[cfg(test)]
+    pub fn new_excited<R: Rng>(excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, rng: &mut R) -> Self {
+        SpinChain::<N>::new_excited_with_placer(excited_bond_map, chain_size, &SitePlacerKind::KBeach, rng)
+    }
 
-    pub fn new_excited(excited_bond_map: &HashMap<usize, usize>, chain_size: usize, rng: &mut Mt19937GenRand64) -> Self {
+    /// A function for generating an excited spin chain whose excited sites are chosen by the given site placer
+    /// * 'excited_bond_map': A hashmap that contains key-value pairs in the form (bond type, number of bonds).
+    /// * site_placer: the strategy that chooses the sites of the excited bonds, see site_placer
+    pub fn new_excited_with_placer<P: SitePlacer, R: Rng>(excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, site_placer: &P, rng: &mut R) -> Self {
 
         // println!("Making new excited chain");
 
-        if excited_bond_map.len() < 3 {
-            panic!("Not enough arguments supplied! Must have entries with keys 0, 1, 2");
-        }
-
-        if excited_bond_map.len() > 3 {
-            panic!("Too many arguments supplied! Must only have entries with keys 0, 1, 2");
-        }
-
-        let spin_sector = SpinChain::<N>::validate_excited_sites(excited_bond_map, chain_size);
+        SpinChain::<N>::validate_excited_sites(excited_bond_map, chain_size);
 
         // Validation should have been successful, now we choose where to place the bonds
         // In the S_tot^z = 1 sector we have that the bonds should have the form
         // (...)[_i (...) ]_j (...)
 
-        let number_of_up_cant_bonds = *excited_bond_map.get(&0).unwrap();
-        // let number_of_down_cant_bonds = *excited_bond_map.get(&1).unwrap();
-        // let number_of_mismatch_sites = *excited_bond_map.get(&2).unwrap();
+        let number_of_up_cant_bonds = number_of_bonds(excited_bond_map, BondKind::UpCant);
+        let number_of_down_cant_bonds = number_of_bonds(excited_bond_map, BondKind::DownCant);
+        let number_of_mismatch_bonds = number_of_bonds(excited_bond_map, BondKind::Mismatch);
 
 
         // Nice property of BTreeMap is that it will keep keys in a specific order
         // example: doing insert(10, 20) followed by insert (2, 15) will have the
         // entries stored in the order (2, 15), (10, 20).
-        let mut excited_site_indices = BTreeMap::<usize, i8>::new();
+        let mut excited_site_indices = BTreeMap::<usize, Site>::new();
 
-        // First, we populate the up_cant sites. This is fairly straightforward since all indices come in pairs meaning that
-        // by default they will not be embedded within another up-canted bond.
+        // First, we populate the excited sites. This is fairly straightforward since all indices come in pairs meaning that
+        // by default they will not be embedded within another excited bond.
         // println!("populating map with indices");
-        let mut is_valid_map = false;
-
-        // while !is_valid_map {
-        //     excited_site_indices.clear();
-        //     is_valid_map = SpinChain::<N>::populate_up_cant_site_index_map(&mut excited_site_indices, number_of_up_cant_bonds, chain_size, rng);
-        // }
 
-        SpinChain::<N>::populate_up_cant_site_index_map_k_beach(&mut excited_site_indices, number_of_up_cant_bonds, chain_size, rng);
+        SpinChain::<N>::populate_excited_site_index_map(&mut excited_site_indices, number_of_up_cant_bonds, number_of_down_cant_bonds, number_of_mismatch_bonds, chain_size, site_placer, rng);
         
 
         // println!("excited site indices: {excited_site_indices:?}");
@@ -73,210 +77,117 @@ impl<const N: usize> SpinChain<N> {
         let chain = SpinChain::<N>::construct_excited_chain(&mut excited_site_indices, chain_size, rng);
 
 
-        let mut hasher = DefaultHasher::new();
-        chain.hash(&mut hasher);
-        let chain_hash = hasher.finish();
-
-
-
-        SpinChain { chain, chain_hash, spin_sector}
-    }
-
-    /// A function that will generate indices that will have an excited bond
-    /// * excited_site_indices: An empty map that will be populated with the index for an excited bond as the key and the excitation type for the bond
-    /// * number_of_bonds: The number of bonds that one wishes to generate
-    fn populate_up_cant_site_index_map(excited_site_indices: &mut BTreeMap<usize, i8>, number_of_bonds: usize, chain_size: usize, rng: &mut Mt19937GenRand64) -> bool {
-        let mut odd_number_counter = 0;
-        let mut even_number_counter = 0;
-
-        // Here we generate n even numbers and n odd numbers that will be paired with eachother as bond sites
-        while odd_number_counter < number_of_bonds || even_number_counter < number_of_bonds {
-            let random_number = rng.gen_range(0..chain_size-2);
-            if random_number %2 == 0 && even_number_counter < number_of_bonds && !excited_site_indices.contains_key(&random_number) {
-                even_number_counter += 1;
-                excited_site_indices.insert(random_number, 2);
-            } else if random_number %2 !=0 && odd_number_counter < number_of_bonds && !excited_site_indices.contains_key(&random_number) {
-                odd_number_counter += 1;
-                excited_site_indices.insert(random_number, 2);
-            }
-        }
-
-        let is_valid_map = validate_site_index_map(excited_site_indices);
-        is_valid_map
+        let chain_hash = chain_hash(&chain);
 
+        SpinChain { chain, chain_hash, number_of_mismatch_bonds}
     }
 
-    fn populate_up_cant_site_index_map_v2(excited_site_indices: &mut BTreeMap<usize, i8>, number_of_bonds: usize, chain_size:usize, rng: &mut Mt19937GenRand64) {
-
-        let mut available_sites: Vec<usize> = Vec::new();
-
-        for integer in 0..chain_size-2 {
-
-            available_sites.push(integer);
-
-        }
-
-        let maximum_index = available_sites.len();
-
-        //i_e_i
-        let mut initial_even_index: usize = 0;
-        let mut initial_even_index_determined = false;
-        //i_o_i
-        let mut initial_odd_index: usize = maximum_index;
-        let mut initial_odd_index_determined = false;
-        let mut number_of_subcovers: usize;
-
-        let mut are_sites_populated = false;
-
-        while !initial_even_index_determined {
-            initial_even_index = rng.gen_range(0..maximum_index);
-            if initial_even_index % 2 == 0 {
-                initial_even_index_determined = true;
-            }
-        }
-        while !initial_odd_index_determined {
-            initial_odd_index = rng.gen_range(initial_even_index..maximum_index);
-            if initial_odd_index % 2 != 0 {
-                initial_odd_index_determined = true;
-            }
-        }
-
-        // we can now split the chain into multiple logical pieces
-        // 0 1 2 .. N - m N - m + 1.. N-3 N-2 N-1 N
-        // There are three possible configurations after the initial split:
-        // one like [0..inital_even_index - 1][initial_even_index..initial_odd_index][initial_odd_index + 1.. N-2]
-        // or one like [0..initial_odd_index][initial_odd_index...N-2]
-        // or [0...N-2]
-        let mut chain_pieces: Vec<&[usize]> = Vec::new();
-        if initial_even_index == 0 {     
-            // If we have i_e_i = 0, then we will only need to split the chain once maximum. If we also have i_o_i = maximum index we don't need to do
-            // any splitting since both endpoints will now be part of a bond.
-            if initial_odd_index == maximum_index - 1 {
-                chain_pieces.push(&available_sites);
-                number_of_subcovers = 1;
-            } else {
-                let (left, right) =  available_sites.split_at(initial_odd_index+1);
-                chain_pieces.push(left);
-                chain_pieces.push(right);
-                number_of_subcovers = 2;
-            }
-        } else {
-            // if we have i_o_i == chain_size -3 we will only need to split once. Otherwise we will have split the chain into 3 pieces
-            if initial_odd_index == maximum_index - 1 {
-                let (left, right) = available_sites.split_at(initial_even_index);
-                chain_pieces.push(left);
-                chain_pieces.push(right);
-                number_of_subcovers = 2;
-            } else {
-                let (left, right) = available_sites.split_at(initial_even_index);
-                chain_pieces.push(left);
-                // the split has created a new logical chain called "right". We need to offset the indexing by the length of the chain
-                // called "left" since the original chain structure was permanently modified
-                let (middle, right) = right.split_at(initial_odd_index+1 - left.len());
-                chain_pieces.push(middle);
-                chain_pieces.push(right);     
-                number_of_subcovers = 3;
-            }
-        }
-
-        let mut two_entry_vecs: Vec<&[usize]>= Vec::new();
-        if number_of_subcovers < number_of_bonds {
-
-            while !are_sites_populated {
-                let random_piece = rng.gen_range(0..chain_pieces.len());
-                let chain_to_split = chain_pieces.remove(random_piece);
-                let random_odd = rng.gen_range(0..chain_to_split.len());
-                // we do not want to attempt to split chains that only have 2 elements
-                // nor do we want to split a chain at its endpoint since it will give us an
-                // empty slice.
-                // Because we remove the "chain to split" from the "chain pieces"
-                // we need to be sure that we re-insert them if they are not modified at all
-                // otherwise we will lose access to them once the loop continues.
-                if random_odd % 2 != 0 && random_odd != chain_to_split.len() - 1{
-                    if chain_to_split.len() > 2 {
-                        let (left, right) = chain_to_split.split_at(random_odd+1);
-                        if left.len() <= 2 && right.len() <= 2{
-                            two_entry_vecs.push(left);
-                            two_entry_vecs.push(right);
-                        } else if left.len() <= 2 && right.len() > 2 {
-                            two_entry_vecs.push(left);
-                            chain_pieces.push(right);
-                        } else if left.len() > 2 && right. len() <= 2 {
-                            chain_pieces.push(left);
-                            two_entry_vecs.push(right);
-                        } else {
-                            chain_pieces.push(left);
-                            chain_pieces.push(right);
-                        }
-                        number_of_subcovers+=1;
+    /// A function that re-labels every site of the chain from its spin so the chain is a valid decorated Dyck word.
+    ///
+    /// The Fredkin moves only look at spins, so swapping an excited site with a regular site of the same spin
+    /// keeps the dynamics correct but leaves the labels pointing at the wrong sites (see evolve_chain).
+    /// Matching every up spin with the first free down spin to its right recovers the regular bonds, and the
+    /// spins left unmatched are the excited sites. The unmatched down spins come first, the rightmost of them
+    /// belong to mismatch bonds, and the leftmost unmatched up spins are the other halves of the mismatch bonds.
+    pub fn relabel_excited_sites(&mut self) {
+        let mut unmatched_up_spins: Vec<usize> = Vec::new();
+        let mut unmatched_down_spins: Vec<usize> = Vec::new();
+
+        for index in 0..self.chain.len() {
+            match self.chain[index].spin() {
+                Spin::Up => unmatched_up_spins.push(index),
+                Spin::Down => {
+                    if let Some(partner_index) = unmatched_up_spins.pop() {
+                        self.chain[partner_index] = Site::Up;
+                        self.chain[index] = Site::Down;
                     } else {
-                        two_entry_vecs.push(chain_to_split);
+                        unmatched_down_spins.push(index);
                     }
-                    if number_of_subcovers >= number_of_bonds {
-                        are_sites_populated = true;
-                
-                    }  
-                } else {
-                    chain_pieces.push(chain_to_split);
                 }
             }
         }
 
-       chain_pieces.append(&mut two_entry_vecs);
-
-        for _i in 0..number_of_bonds {
-            let random_index = rng.gen_range(0..chain_pieces.len());
-            let random_endpoints = chain_pieces.remove(random_index);
-            excited_site_indices.insert(*random_endpoints.first().unwrap(), 2);
-            excited_site_indices.insert(*random_endpoints.last().unwrap(), 2);
+        let number_of_down_cant_sites = unmatched_down_spins.len() - self.number_of_mismatch_bonds;
+        for (position, index) in unmatched_down_spins.into_iter().enumerate() {
+            self.chain[index] = if position < number_of_down_cant_sites { Site::DownCant } else { Site::MismatchDown };
+        }
+        for (position, index) in unmatched_up_spins.into_iter().enumerate() {
+            self.chain[index] = if position < self.number_of_mismatch_bonds { Site::MismatchUp } else { Site::UpCant };
         }
     }
 
-    fn populate_up_cant_site_index_map_k_beach(excited_site_indices: &mut BTreeMap<usize, i8>, number_of_bonds: usize, chain_size:usize, rng: &mut Mt19937GenRand64) {
-
-        let mut mo:usize = 0;
-        let mut me:usize = 0;
-        
-        let mut i:usize = 0;
-
-        while me != number_of_bonds || mo != number_of_bonds {
-
-            let rand: f64 = rng.gen_range(0.0f64..1f64);
-
-            if i%2 == 0 { // even case
-                let num = (2 * (number_of_bonds - me)) as f64;
-                let den = (chain_size - 2 - i) as f64;
-                let p = num/den;
-                if rand < p {
-                    excited_site_indices.insert(i, 2);
-                    me+=1;
-                    i += 1;
-                } else {
-                    i += 2;
-                }
-           } else { // odd case
-                let num = (2* (number_of_bonds - mo)) as f64;
-                let den = (chain_size - 1 - i) as f64;
-                let p = num/den;
-                if rand < p {
-                   excited_site_indices.insert(i, 2);
-                   mo+=1;
-                   i += 1;
-                } else {
-
-                  i += 2;
-                }
-           }
+    /// A function that places the endpoints of every excited bond and labels them with their site code
+    /// * excited_site_indices: An empty map that will be populated with the index for an excited bond as the key and the site for the bond
+    ///
+    /// * site_placer: the strategy that chooses the sites
+    ///
+    /// The sites are chosen by the site placer outside of the sites reserved by reserved_edge_sites
+    /// and are then labelled from left to right: down-cant, mismatch down half, mismatch up half, up-cant.
+    #[allow(clippy::too_many_arguments)]
+    fn populate_excited_site_index_map<P: SitePlacer, R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, number_of_up_cant_bonds: usize, number_of_down_cant_bonds: usize, number_of_mismatch_bonds: usize, chain_size: usize, site_placer: &P, rng: &mut R) {
+        let number_of_bonds = number_of_up_cant_bonds + number_of_down_cant_bonds + number_of_mismatch_bonds;
+        let (left_reserved, right_reserved) = reserved_edge_sites(number_of_up_cant_bonds, number_of_down_cant_bonds, number_of_mismatch_bonds);
+
+        let mut site_indices = BTreeMap::<usize, Site>::new();
+        site_placer.place_excited_sites(&mut site_indices, number_of_bonds, chain_size, left_reserved, right_reserved, rng);
+
+        let sites = iter::repeat_n(Site::DownCant, 2 * number_of_down_cant_bonds)
+            .chain(iter::repeat_n(Site::MismatchDown, number_of_mismatch_bonds))
+            .chain(iter::repeat_n(Site::MismatchUp, number_of_mismatch_bonds))
+            .chain(iter::repeat_n(Site::UpCant, 2 * number_of_up_cant_bonds));
+
+        for (index, site) in site_indices.keys().zip(sites) {
+            excited_site_indices.insert(*index, site);
         }
     }
 
-    // up_cant = 2, down_cant = 3, mismatch = 4
+    /// A function that lists every chain new_excited_with_placer can generate along with the probability it is generated with.
+    /// None when the distribution of the site placer is not known, see SitePlacerKind::placement_probability.
+    /// * excited_bond_map: A hashmap that contains key-value pairs in the form (bond type, number of bonds)
+    /// * site_placer: the strategy that chooses the sites of the excited bonds
+    ///
+    /// The chains are the configurations of enumeration::excited_configurations. new_excited_with_placer places the excited
+    /// sites with the site placer and fills every interval around them with a uniformly drawn Dyck word, so a chain is
+    /// generated with the probability of its excited sites over the number of ways to fill the intervals. Only practical for
+    /// small chains.
+    pub fn excited_chain_distribution(excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize, site_placer: SitePlacerKind) -> Option<Vec<(Vec<Site>, f64)>> {
+        SpinChain::<N>::validate_excited_sites(excited_bond_map, chain_size);
+
+        let excited_bonds = ExcitedBondConfig {
+            up_cant: number_of_bonds(excited_bond_map, BondKind::UpCant),
+            down_cant: number_of_bonds(excited_bond_map, BondKind::DownCant),
+            mismatch: number_of_bonds(excited_bond_map, BondKind::Mismatch),
+        };
+        let number_of_bonds = excited_bonds.up_cant + excited_bonds.down_cant + excited_bonds.mismatch;
+        let (left_reserved, right_reserved) = reserved_edge_sites(excited_bonds.up_cant, excited_bonds.down_cant, excited_bonds.mismatch);
+
+        let mut placement_probabilities: HashMap<Vec<usize>, Option<f64>> = HashMap::new();
+        enumeration::excited_configurations(&excited_bonds, chain_size).into_iter()
+            .map(|chain| {
+                let placement: Vec<usize> = (0..chain_size).filter(|index| chain[*index].bond_kind().is_some()).collect();
+
+                // The Dyck word intervals are [0, first excited site), the gaps between excited sites and (last excited site, chain_size)
+                let mut interval_starts = vec![0];
+                interval_starts.extend(placement.iter().map(|index| index + 1));
+                let interval_ends = placement.iter().copied().chain(iter::once(chain_size));
+                let fillings: f64 = interval_starts.into_iter().zip(interval_ends)
+                    .map(|(start, end)| enumeration::catalan_number((end - start) / 2) as f64)
+                    .product();
+
+                let placement_probability = *placement_probabilities.entry(placement)
+                    .or_insert_with_key(|placement| site_placer.placement_probability(placement, number_of_bonds, chain_size, left_reserved, right_reserved));
+                placement_probability.map(|placement_probability| (chain, placement_probability / fillings))
+            })
+            .filter(|entry| entry.as_ref().is_none_or(|(_, probability)| *probability > 0.0))
+            .collect()
+    }
+
     /// A function that will construct the entire excited chain
     /// * excited_site_indices: a map that contains the sites that will have an excited bond endpoint
-    fn construct_excited_chain(excited_site_indices: &mut BTreeMap<usize, i8>, chain_size: usize, rng: &mut Mt19937GenRand64) -> Vec<i8> {
-        let mut chain = vec![0;chain_size];
-        chain[0] = 1;
-        chain[chain_size-1] = -1;
+    fn construct_excited_chain<R: Rng>(excited_site_indices: &mut BTreeMap<usize, Site>, chain_size: usize, rng: &mut R) -> Vec<Site> {
+        // the chain starts out as up spins and every site after the first is overwritten below
+        let mut chain = vec![Site::Up;chain_size];
+        chain[chain_size-1] = Site::Down;
         
         // for excited chains, height above/below the horizon no longer matters. We just need to make 
         // proper Dyck words in between the excited sites.
@@ -292,16 +203,16 @@ impl<const N: usize> SpinChain<N> {
 
         while index < excited_indices_vec.len() {
             let left_bound = *excited_indices_vec.get(index-1).unwrap();
-            let left_bound_index = *left_bound as usize;
+            let left_bound_index = *left_bound;
             let right_bound = *excited_indices_vec.get(index).unwrap();
-            let right_bound_index = *right_bound as usize;
+            let right_bound_index = *right_bound;
             let inner_length =  (right_bound_index - left_bound_index - 1) as u32;
             SpinChain::<N>::generate_arbitrary_dyck_words(&mut chain, left_bound_index+1, right_bound_index, inner_length, rng);           
             index += 1;
         }
 
         
-        let last_excited_bond_position = *excited_site_indices.last_key_value().unwrap().0 as usize;
+        let last_excited_bond_position = *excited_site_indices.last_key_value().unwrap().0;
 
         let right_side_length = (chain_size - last_excited_bond_position -1) as u32;
         SpinChain::<N>::generate_arbitrary_dyck_words(&mut chain, last_excited_bond_position+1, chain_size, right_side_length, rng);
@@ -315,7 +226,7 @@ impl<const N: usize> SpinChain<N> {
     /// * left_bound: the first spin that will be included in the Dyck word state
     /// * right_bound: the spin after the last spin that will be included in the Dyck word state.
     /// * length: the size of then interval
-    fn generate_arbitrary_dyck_words(chain: &mut Vec<i8>, left_bound: usize, right_bound: usize, length: u32, rng: &mut Mt19937GenRand64) {
+    fn generate_arbitrary_dyck_words<R: Rng>(chain: &mut [Site], left_bound: usize, right_bound: usize, length: u32, rng: &mut R) {
 
         // offset index to keep probability calulations correct
         let mut current_index = 1;
@@ -324,24 +235,24 @@ impl<const N: usize> SpinChain<N> {
         if length == 0 {
             return;
         }
-        else if length == 2 {
-            chain[left_bound] = 1;
-            chain [right_bound - 1] = -1;
-            return;
+
+        if length == 2 {
+            chain[left_bound] = Site::Up;
+            chain [right_bound - 1] = Site::Down;
         } else {
-            chain[left_bound] = 1;
-            chain[right_bound - 1] = -1;
-            for i in left_bound+1..right_bound {
+            chain[left_bound] = Site::Up;
+            chain[right_bound - 1] = Site::Down;
+            for spin in &mut chain[left_bound+1..right_bound] {
                 let prob_up = calculate_next_spin_prob(length, current_index, height);
                 let random_num:f64 = rng.gen_range(0f64..=1f64);
                 if random_num <= prob_up {
-                    chain[i] = 1;
+                    *spin = Site::Up;
                     height += 1;
                 } else {
-                    chain[i] = -1;
+                    *spin = Site::Down;
                     height -= 1;
                 }
-                current_index = current_index+1;
+                current_index += 1;
             }
         }
 
@@ -354,9 +265,9 @@ impl<const N: usize> SpinChain<N> {
     /// A preprocessing function that fills in the bonds before Dyck Word generation is performed
     /// * excited_bond_positions: A map that contains the bond positions and the type of bond
     /// * chain: an array representing the spin chain
-    fn populate_excited_sites_of_chain(excited_bond_positions: &mut BTreeMap<usize, i8>, chain: &mut Vec<i8>) {
+    fn populate_excited_sites_of_chain(excited_bond_positions: &mut BTreeMap<usize, Site>, chain: &mut [Site]) {
         for entry  in excited_bond_positions {
-            let index = (*entry.0) as usize;
+            let index = *entry.0;
             let excitation_type = *entry.1;
             chain[index] = excitation_type;
         }
@@ -365,15 +276,15 @@ impl<const N: usize> SpinChain<N> {
     /// A function that handles the special case of populating in the left side of the chain
     /// * chain: an array that represents the spin chain
     /// * first_excited_bond_position: the position of the left most excited bond site
-    fn populate_left_side_of_chain(chain: &mut Vec<i8>, first_excited_bond_position: usize, rng: &mut Mt19937GenRand64) {
+    fn populate_left_side_of_chain<R: Rng>(chain: &mut [Site], first_excited_bond_position: usize, rng: &mut R) {
         if first_excited_bond_position == 0 {
             return;
         } else if first_excited_bond_position == 2 {
-            chain [1] = -1;
+            chain [1] = Site::Down;
             return;
         }
 
-        let right_bound = first_excited_bond_position as usize;
+        let right_bound = first_excited_bond_position;
         let length = right_bound as u32;
 
         SpinChain::<N>::generate_arbitrary_dyck_words(chain, 0, right_bound, length, rng);
@@ -382,55 +293,53 @@ impl<const N: usize> SpinChain<N> {
 
     /// A function that ensures a user does not pass in more bonds than there are sites
     /// * number_of_bonds: A map containing all bond types and the number of each bond
-    fn validate_excited_sites(number_of_bonds: &HashMap<usize, usize>, chain_size: usize) -> usize {
+    fn validate_excited_sites(excited_bond_map: &HashMap<BondKind, usize>, chain_size: usize) {
 
-        // the number of available sites is N-2 since the leftmost and rightmost
-        // sites cannot be changed
-        let available_sites = chain_size-2;
-        let mut spin_sector = 0;
+        // the number of available sites is N-2 for up-canted bonds since the rightmost sites
+        // cannot be changed, see reserved_edge_sites for the other bond types
+        let (left_reserved, right_reserved) = reserved_edge_sites(number_of_bonds(excited_bond_map, BondKind::UpCant), number_of_bonds(excited_bond_map, BondKind::DownCant), number_of_bonds(excited_bond_map, BondKind::Mismatch));
+        let available_sites = chain_size.saturating_sub(left_reserved + right_reserved);
         let mut total_number_of_excited_bonds:usize = 0;
-        for entry in number_of_bonds {
+        for entry in excited_bond_map {
 
-            total_number_of_excited_bonds += *entry.1 as usize;
-            spin_sector +=*entry.1;
+            total_number_of_excited_bonds += *entry.1;
         }
 
         // above we simply find out how many exicted bonds we want. We need to multiply this by 2 
         // since each bond occupies 2 sites.
         let num_of_excited_sites = 2 * total_number_of_excited_bonds;
 
+        if num_of_excited_sites == 0 {
+            panic!("An excited chain needs at least one excited bond");
+        }
+
         if num_of_excited_sites > available_sites {
             panic!("The number of sites needed for excited bonds exceeded the number of available sites in the chain. excited sites: {}, size of chain: {}", num_of_excited_sites, chain_size);
         }
 
-        spin_sector
-
     }
 }
 
-/// A function that ensures the excited site indices are in even, odd, even, odd, even,... order
-/// * excited_site_indices: A map that contains the endpoints for excited bonds and the bond type at that index
-fn validate_site_index_map(excited_site_indices: &mut BTreeMap<usize, i8>) -> bool {
-    let mut is_even = true;
-    let mut is_valid_map = true;
-
-    for key in excited_site_indices.keys() {
-        if is_even && *key % 2 != 0 {
-            // println!("This number is odd and shouldn't be: {}", key);
-            is_valid_map = false;
-        } else if !is_even && *key % 2 == 0 {
-            // println!("This number is even and shouldn't be: {}", key);
-
-            is_valid_map = false;
-        }
-        is_even = !is_even;
-    }
-
-    return is_valid_map;
-
+/// The hash a chain is identified by, equal chains always have the same hash
+pub fn chain_hash(chain: &[Site]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    chain.hash(&mut hasher);
+    hasher.finish()
 }
 
+/// The number of bonds of a kind in the excited_bond_map, 0 when the kind is missing
+fn number_of_bonds(excited_bond_map: &HashMap<BondKind, usize>, bond_kind: BondKind) -> usize {
+    excited_bond_map.get(&bond_kind).copied().unwrap_or(0)
+}
 
+/// The number of sites at the (left, right) edge of the chain that cannot hold an excited site.
+/// A chain dies when an unmatched up spin reaches the right edge as (() or an unmatched down spin reaches the
+/// left edge as ()), so unmatched up spins need a regular bond to their right and unmatched down spins one to their left.
+pub fn reserved_edge_sites(number_of_up_cant_bonds: usize, number_of_down_cant_bonds: usize, number_of_mismatch_bonds: usize) -> (usize, usize) {
+    let left_reserved = if number_of_down_cant_bonds + number_of_mismatch_bonds > 0 { 2 } else { 0 };
+    let right_reserved = if number_of_up_cant_bonds + number_of_mismatch_bonds > 0 { 2 } else { 0 };
+    (left_reserved, right_reserved)
+}
 
 /// Calculates the probability of next spin being up. Based on Eq. 10
 /// in arXiv:1805.00532
@@ -440,12 +349,10 @@ fn validate_site_index_map(excited_site_indices: &mut BTreeMap<usize, i8>) -> bo
 /// * height: how high above the horizon are you: up up -> height = 2 up down -> height = 0
 fn calculate_next_spin_prob(length: u32, current_index: u32, height: u32) -> f64 {
 
-    let numerator: f64 = ((height + 2) * (length - current_index - height)).try_into().expect("Could not turn u32 into f64 - numerator");
-
-    let denominator: f64 = (2 * (height + 1) * (length - current_index)).try_into().expect("Could not turn u32 into f64 - denominator");
+    let numerator: f64 = ((height + 2) * (length - current_index - height)).into();
 
-    let spin_up_prob = numerator/denominator;
+    let denominator: f64 = (2 * (height + 1) * (length - current_index)).into();
 
-    spin_up_prob
+    numerator/denominator
 }
 
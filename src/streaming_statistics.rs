@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::Lifetime;
+
+/// Relative error of the quantiles read off a QuantileSketch
+const QUANTILE_RELATIVE_ACCURACY: f64 = 0.01;
+/// Number of LogHistogram bins per factor of 10
+const HISTOGRAM_BINS_PER_DECADE: u32 = 10;
+
+/// Counts of values in logarithmic bins. Bin k holds the values in [10^(k/b), 10^((k+1)/b)) for b bins per decade
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LogHistogram {
+    pub bins_per_decade: u32,
+    pub counts: BTreeMap<i32, u64>,
+    // Values of 0 or below, which have no logarithm
+    pub non_positive: u64,
+}
+
+impl LogHistogram {
+    pub fn new(bins_per_decade: u32) -> Self {
+        LogHistogram { bins_per_decade, counts: BTreeMap::new(), non_positive: 0 }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if value <= 0.0 {
+            self.non_positive += 1;
+            return;
+        }
+        let bin = (value.log10() * self.bins_per_decade as f64).floor() as i32;
+        *self.counts.entry(bin).or_insert(0) += 1;
+    }
+
+    /// Adds the counts of a histogram with the same binning
+    pub fn merge(&mut self, other: &LogHistogram) {
+        assert_eq!(self.bins_per_decade, other.bins_per_decade, "histograms with different binnings cannot be merged");
+        for (bin, count) in &other.counts {
+            *self.counts.entry(*bin).or_insert(0) += count;
+        }
+        self.non_positive += other.non_positive;
+    }
+}
+
+/// A quantile sketch with logarithmic buckets (DDSketch). Every quantile it returns is within the relative accuracy of
+/// a value of the stream at that rank, using one bucket per factor of (1 + accuracy) / (1 - accuracy) the values span.
+/// Bucket k holds the values in (gamma^(k-1), gamma^k].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuantileSketch {
+    pub relative_accuracy: f64,
+    pub counts: BTreeMap<i32, u64>,
+    // Values of 0 or below, reported as 0
+    pub non_positive: u64,
+    pub count: u64,
+}
+
+impl QuantileSketch {
+    pub fn new(relative_accuracy: f64) -> Self {
+        QuantileSketch { relative_accuracy, counts: BTreeMap::new(), non_positive: 0, count: 0 }
+    }
+
+    fn gamma(&self) -> f64 {
+        (1.0 + self.relative_accuracy) / (1.0 - self.relative_accuracy)
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        if value <= 0.0 {
+            self.non_positive += 1;
+            return;
+        }
+        let bucket = (value.ln() / self.gamma().ln()).ceil() as i32;
+        *self.counts.entry(bucket).or_insert(0) += 1;
+    }
+
+    /// Adds the buckets of a sketch of the same accuracy, giving the sketch of both streams
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        assert_eq!(self.relative_accuracy, other.relative_accuracy, "sketches of different accuracies cannot be merged");
+        for (bucket, count) in &other.counts {
+            *self.counts.entry(*bucket).or_insert(0) += count;
+        }
+        self.non_positive += other.non_positive;
+        self.count += other.count;
+    }
+
+    /// The value at the given quantile, between 0 and 1, None for an empty sketch
+    pub fn quantile(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = quantile.clamp(0.0, 1.0) * (self.count - 1) as f64;
+        let mut cumulative_count = self.non_positive;
+        if cumulative_count as f64 > rank {
+            return Some(0.0);
+        }
+        let gamma = self.gamma();
+        for (bucket, count) in &self.counts {
+            cumulative_count += count;
+            if cumulative_count as f64 > rank {
+                return Some(2.0 * gamma.powi(*bucket) / (gamma + 1.0));
+            }
+        }
+        self.counts.keys().next_back().map(|bucket| 2.0 * gamma.powi(*bucket) / (gamma + 1.0))
+    }
+}
+
+/// Running statistics of a stream of values that take the same memory however many values there are
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StreamingStatistics {
+    pub count: u64,
+    pub mean: f64,
+    // Sum of the squared deviations from the mean, updated with Welford's algorithm
+    pub sum_of_squared_deviations: f64,
+    pub min: f64,
+    pub max: f64,
+    pub histogram: LogHistogram,
+    pub quantiles: QuantileSketch,
+}
+
+impl Default for StreamingStatistics {
+    fn default() -> Self {
+        StreamingStatistics {
+            count: 0,
+            mean: 0.0,
+            sum_of_squared_deviations: 0.0,
+            min: 0.0,
+            max: 0.0,
+            histogram: LogHistogram::new(HISTOGRAM_BINS_PER_DECADE),
+            quantiles: QuantileSketch::new(QUANTILE_RELATIVE_ACCURACY),
+        }
+    }
+}
+
+impl StreamingStatistics {
+    pub fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.count += 1;
+        let deviation = value - self.mean;
+        self.mean += deviation / self.count as f64;
+        self.sum_of_squared_deviations += deviation * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.histogram.add(value);
+        self.quantiles.add(value);
+    }
+
+    /// Combines the statistics of another stream with these, as if every value had been added here. The mean and the
+    /// sum of squared deviations follow the pairwise update of Chan, Golub and LeVeque.
+    pub fn merge(&mut self, other: &StreamingStatistics) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = other.clone();
+            return;
+        }
+        let count = (self.count + other.count) as f64;
+        let deviation = other.mean - self.mean;
+        self.mean += deviation * other.count as f64 / count;
+        self.sum_of_squared_deviations += other.sum_of_squared_deviations + deviation * deviation * self.count as f64 * other.count as f64 / count;
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.histogram.merge(&other.histogram);
+        self.quantiles.merge(&other.quantiles);
+    }
+
+    /// The sample variance, NaN with fewer than two values
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return f64::NAN;
+        }
+        self.sum_of_squared_deviations / (self.count - 1) as f64
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        (self.variance() / self.count as f64).sqrt()
+    }
+}
+
+/// The lifetimes of every trial of a chain size, kept as running statistics instead of one entry per trial
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct LifetimeSummary {
+    // Steps, or moves that fired for the gillespie dynamics
+    pub steps: StreamingStatistics,
+    // Continuous-time lifetimes, only measured by the gillespie dynamics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub times: Option<StreamingStatistics>,
+    // Trials stopped at the step cap. Only counted, so the statistics leave them out
+    pub censored_trials: u64,
+}
+
+impl LifetimeSummary {
+    pub fn add(&mut self, lifetime: &Lifetime) {
+        if lifetime.censored {
+            self.censored_trials += 1;
+            return;
+        }
+        self.steps.add(lifetime.step_count as f64);
+        if let Some(time) = lifetime.time {
+            self.times.get_or_insert_with(StreamingStatistics::default).add(time);
+        }
+    }
+
+    pub fn merge(&mut self, other: &LifetimeSummary) {
+        self.steps.merge(&other.steps);
+        if let Some(times) = &other.times {
+            self.times.get_or_insert_with(StreamingStatistics::default).merge(times);
+        }
+        self.censored_trials += other.censored_trials;
+    }
+
+    /// The statistics of the lifetimes, in time for the gillespie dynamics and in steps otherwise
+    pub fn lifetimes(&self) -> &StreamingStatistics {
+        self.times.as_ref().unwrap_or(&self.steps)
+    }
+}
@@ -0,0 +1,91 @@
+/// The Kaplan-Meier estimate of the survival function at one of the distinct observed lifetimes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurvivalPoint {
+    pub time: f64,
+    // Trials whose lifetime, censored or not, is at least time
+    pub at_risk: u64,
+    pub deaths: u64,
+    pub censored: u64,
+    // Estimated probability of living for longer than time
+    pub survival: f64,
+    // Greenwood's standard error of survival
+    pub standard_error: f64,
+}
+
+/// The mean lifetime read off a Kaplan-Meier curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CensoredMean {
+    pub mean: f64,
+    pub standard_error: f64,
+    // The longest lifetime was censored, so the curve does not reach 0 and the mean is only taken up to that lifetime.
+    // It is then a lower bound on the true mean.
+    pub restricted: bool,
+}
+
+/// The Kaplan-Meier estimate of the survival function of a set of lifetimes, some of them right-censored
+#[derive(Debug, Clone, PartialEq)]
+pub struct KaplanMeier {
+    pub points: Vec<SurvivalPoint>,
+}
+
+impl KaplanMeier {
+    /// * lifetimes: lifetimes of the trials that died
+    /// * censored_lifetimes: lifetimes of the trials that were stopped while still alive
+    pub fn new(lifetimes: &[f64], censored_lifetimes: &[f64]) -> Self {
+        let mut observations: Vec<(f64, bool)> = lifetimes.iter().map(|lifetime| (*lifetime, true))
+            .chain(censored_lifetimes.iter().map(|lifetime| (*lifetime, false)))
+            .collect();
+        observations.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut points = Vec::new();
+        let mut at_risk = observations.len() as u64;
+        let mut survival = 1.0;
+        let mut greenwood_sum = 0.0;
+        for group in observations.chunk_by(|a, b| a.0 == b.0) {
+            let deaths = group.iter().filter(|(_, died)| *died).count() as u64;
+            let censored = group.len() as u64 - deaths;
+            survival *= 1.0 - deaths as f64 / at_risk as f64;
+            if at_risk > deaths {
+                greenwood_sum += deaths as f64 / (at_risk as f64 * (at_risk - deaths) as f64);
+            }
+            points.push(SurvivalPoint { time: group[0].0, at_risk, deaths, censored, survival, standard_error: survival * greenwood_sum.sqrt() });
+            at_risk -= group.len() as u64;
+        }
+        KaplanMeier { points }
+    }
+
+    /// The probability of living to at least the given time, the curve just before it
+    pub fn survival_before(&self, time: f64) -> f64 {
+        self.points.iter().take_while(|point| point.time < time).last().map_or(1.0, |point| point.survival)
+    }
+
+    /// The first lifetime the curve drops to 1/2 or below at, None when more than half of the trials outlived the censoring
+    pub fn median(&self) -> Option<f64> {
+        self.points.iter().find(|point| point.survival <= 0.5).map(|point| point.time)
+    }
+
+    /// The area under the curve up to the longest lifetime, with the standard error of the restricted mean survival time
+    pub fn mean(&self) -> CensoredMean {
+        // area under the curve from 0 up to every point
+        let mut areas_before = Vec::with_capacity(self.points.len());
+        let mut area = 0.0;
+        let mut previous_time = 0.0;
+        let mut previous_survival = 1.0;
+        for point in &self.points {
+            area += previous_survival * (point.time - previous_time);
+            areas_before.push(area);
+            previous_time = point.time;
+            previous_survival = point.survival;
+        }
+
+        let variance: f64 = self.points.iter().zip(&areas_before)
+            .filter(|(point, _)| point.deaths > 0 && point.at_risk > point.deaths)
+            .map(|(point, area_before)| {
+                let area_after = area - area_before;
+                area_after * area_after * point.deaths as f64 / (point.at_risk as f64 * (point.at_risk - point.deaths) as f64)
+            })
+            .sum();
+        let restricted = self.points.last().is_some_and(|point| point.survival > 0.0);
+        CensoredMean { mean: area, standard_error: variance.sqrt(), restricted }
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt::Write as _;
+use std::fs;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 500.0;
+// Space around the plot area for the title, tick labels and axis labels
+const LEFT_MARGIN: f64 = 90.0;
+const RIGHT_MARGIN: f64 = 30.0;
+const TOP_MARGIN: f64 = 50.0;
+const BOTTOM_MARGIN: f64 = 60.0;
+const MARKER_RADIUS: f64 = 4.0;
+const ERROR_BAR_CAP: f64 = 4.0;
+/// Colours of the series in the order they are added, repeating after the last one
+const COLOURS: [&str; 6] = ["#1f5fa8", "#c0392b", "#2e8b57", "#8e44ad", "#d35400", "#555555"];
+
+/// How values are mapped onto an axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Linear,
+    /// Logarithmic, values of 0 or below are left out
+    Log,
+}
+
+/// How a series is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeriesStyle {
+    /// A circle at every point, with vertical error bars when the series has errors
+    Markers,
+    /// A line through the points in the order they were given
+    Line,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f64, f64)>,
+    // Standard error of the y value of every point, empty for a series without error bars
+    pub errors: Vec<f64>,
+    pub style: SeriesStyle,
+}
+
+impl Series {
+    /// * errors: the y error of every point, empty for no error bars
+    pub fn markers(label: &str, points: Vec<(f64, f64)>, errors: Vec<f64>) -> Self {
+        Series { label: label.to_string(), points, errors, style: SeriesStyle::Markers }
+    }
+
+    pub fn line(label: &str, points: Vec<(f64, f64)>) -> Self {
+        Series { label: label.to_string(), points, errors: Vec::new(), style: SeriesStyle::Line }
+    }
+
+    /// The lowest and highest y of every point, error bars included
+    fn y_extents(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.points.iter().enumerate().map(|(index, (_, y))| {
+            let error = self.errors.get(index).copied().filter(|error| error.is_finite()).unwrap_or(0.0);
+            (y - error, y + error)
+        })
+    }
+}
+
+/// A two dimensional plot drawn straight to SVG. Text is left to the viewer's sans-serif font, so writing a plot needs
+/// no fonts or graphics libraries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub x_scale: Scale,
+    pub y_scale: Scale,
+    pub series: Vec<Series>,
+}
+
+/// One axis of a plot, mapping values onto pixels between start and end
+struct Axis {
+    scale: Scale,
+    // Range of the axis, in log10 of the values for a logarithmic axis
+    min: f64,
+    max: f64,
+    start: f64,
+    end: f64,
+}
+
+impl Axis {
+    /// The axis spanning the values with a margin of 5% of the range on both sides
+    fn new(scale: Scale, values: impl Iterator<Item = f64>, start: f64, end: f64) -> Self {
+        let transformed: Vec<f64> = values.filter(|value| value.is_finite() && (scale == Scale::Linear || *value > 0.0))
+            .map(|value| if scale == Scale::Log { value.log10() } else { value })
+            .collect();
+        let (mut min, mut max) = transformed.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| (min.min(*value), max.max(*value)));
+        if transformed.is_empty() {
+            (min, max) = (0.0, 1.0);
+        } else if min == max {
+            (min, max) = (min - 0.5, max + 0.5);
+        }
+        let margin = 0.05 * (max - min);
+        Axis { scale, min: min - margin, max: max + margin, start, end }
+    }
+
+    /// The pixel position of a value, None for values a logarithmic axis cannot show
+    fn position(&self, value: f64) -> Option<f64> {
+        let value = match self.scale {
+            Scale::Linear => value,
+            Scale::Log if value > 0.0 => value.log10(),
+            Scale::Log => return None,
+        };
+        Some(self.start + (value - self.min) / (self.max - self.min) * (self.end - self.start))
+    }
+
+    /// The position of a value clamped to the ends of the axis, values a logarithmic axis cannot show going to its start
+    fn clamped_position(&self, value: f64) -> f64 {
+        let position = self.position(value).unwrap_or(self.start);
+        position.clamp(self.start.min(self.end), self.start.max(self.end))
+    }
+
+    /// The values the axis is labelled at, with their labels
+    fn ticks(&self) -> Vec<(f64, String)> {
+        match self.scale {
+            Scale::Linear => linear_ticks(self.min, self.max),
+            Scale::Log => {
+                let ticks = log_ticks(self.min, self.max);
+                if ticks.len() >= 3 {
+                    return ticks;
+                }
+                // less than a decade, label it like a linear axis
+                linear_ticks(10f64.powf(self.min), 10f64.powf(self.max)).into_iter().filter(|(value, _)| *value > 0.0).collect()
+            }
+        }
+    }
+}
+
+/// Ticks at multiples of 1, 2 or 5 times a power of 10, about 5 to 10 of them
+pub fn linear_ticks(min: f64, max: f64) -> Vec<(f64, String)> {
+    let rough_step = (max - min) / 6.0;
+    let magnitude = 10f64.powf(rough_step.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|factor| factor * magnitude).find(|step| *step >= rough_step).unwrap_or(10.0 * magnitude);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(|multiple| {
+        let value = multiple as f64 * step;
+        (value, format!("{:.*}", decimals, value))
+    }).collect()
+}
+
+/// Ticks at 1, 2 and 5 times every power of 10 between 10^min and 10^max. Only powers of 10 are kept when the axis
+/// spans more than three decades.
+pub fn log_ticks(min: f64, max: f64) -> Vec<(f64, String)> {
+    let factors: &[f64] = if max - min > 3.0 { &[1.0] } else { &[1.0, 2.0, 5.0] };
+    let mut ticks = Vec::new();
+    for decade in min.floor() as i32..=max.ceil() as i32 {
+        for factor in factors {
+            let value = factor * 10f64.powi(decade);
+            if (min..=max).contains(&value.log10()) {
+                let label = if (-3..5).contains(&decade) { format!("{:.*}", (-decade).max(0) as usize, value) } else { format!("{factor}e{decade}") };
+                ticks.push((value, label));
+            }
+        }
+    }
+    ticks
+}
+
+impl Plot {
+    pub fn new(title: &str, x_label: &str, y_label: &str, x_scale: Scale, y_scale: Scale) -> Self {
+        Plot { title: title.to_string(), x_label: x_label.to_string(), y_label: y_label.to_string(), x_scale, y_scale, series: Vec::new() }
+    }
+
+    pub fn add_series(&mut self, series: Series) {
+        self.series.push(series);
+    }
+
+    /// The SVG document of the plot
+    pub fn to_svg(&self) -> String {
+        let (left, right, top, bottom) = (LEFT_MARGIN, WIDTH - RIGHT_MARGIN, TOP_MARGIN, HEIGHT - BOTTOM_MARGIN);
+        let x_axis = Axis::new(self.x_scale, self.series.iter().flat_map(|series| series.points.iter().map(|(x, _)| *x)), left, right);
+        // error bars reaching 0 or below cannot set the range of a logarithmic axis, their points still do
+        let y_axis = Axis::new(self.y_scale, self.series.iter().flat_map(|series| series.y_extents().flat_map(|(low, high)| [low, high]))
+            .chain(self.series.iter().flat_map(|series| series.points.iter().map(|(_, y)| *y))), bottom, top);
+
+        let mut svg = String::new();
+        writeln!(svg, r#"<svg viewBox="0 0 {WIDTH} {HEIGHT}" width="{WIDTH}" height="{HEIGHT}" xmlns="http://www.w3.org/2000/svg" font-family="sans-serif">"#).unwrap();
+        writeln!(svg, r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}" font-size="18" text-anchor="middle">{}</text>"#, (left + right) / 2.0, TOP_MARGIN / 2.0 + 6.0, escape(&self.title)).unwrap();
+
+        // grid lines and tick labels
+        for (value, label) in x_axis.ticks() {
+            let x = x_axis.clamped_position(value);
+            writeln!(svg, r##"<line x1="{x:.2}" y1="{top}" x2="{x:.2}" y2="{bottom}" stroke="#dddddd"/>"##).unwrap();
+            writeln!(svg, r#"<text x="{x:.2}" y="{}" font-size="12" text-anchor="middle">{}</text>"#, bottom + 18.0, escape(&label)).unwrap();
+        }
+        for (value, label) in y_axis.ticks() {
+            let y = y_axis.clamped_position(value);
+            writeln!(svg, r##"<line x1="{left}" y1="{y:.2}" x2="{right}" y2="{y:.2}" stroke="#dddddd"/>"##).unwrap();
+            writeln!(svg, r#"<text x="{}" y="{:.2}" font-size="12" text-anchor="end">{}</text>"#, left - 6.0, y + 4.0, escape(&label)).unwrap();
+        }
+        writeln!(svg, r#"<rect x="{left}" y="{top}" width="{}" height="{}" fill="none" stroke="black"/>"#, right - left, bottom - top).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}" font-size="14" text-anchor="middle">{}</text>"#, (left + right) / 2.0, HEIGHT - 15.0, escape(&self.x_label)).unwrap();
+        writeln!(svg, r#"<text x="20" y="{0}" font-size="14" text-anchor="middle" transform="rotate(-90 20 {0})">{1}</text>"#, (top + bottom) / 2.0, escape(&self.y_label)).unwrap();
+
+        for (index, series) in self.series.iter().enumerate() {
+            let colour = COLOURS[index % COLOURS.len()];
+            let positions: Vec<(usize, f64, f64)> = series.points.iter().enumerate()
+                .filter_map(|(point, (x, y))| Some((point, x_axis.position(*x)?, y_axis.position(*y)?)))
+                .collect();
+            match series.style {
+                SeriesStyle::Markers => {
+                    for (point, x, y) in &positions {
+                        if let Some((low, high)) = series.y_extents().nth(*point).filter(|_| !series.errors.is_empty()) {
+                            let (low, high) = (y_axis.clamped_position(low), y_axis.clamped_position(high));
+                            writeln!(svg, r#"<path d="M{x:.2} {low:.2}V{high:.2}M{:.2} {low:.2}H{:.2}M{:.2} {high:.2}H{:.2}" stroke="{colour}" fill="none"/>"#,
+                                x - ERROR_BAR_CAP, x + ERROR_BAR_CAP, x - ERROR_BAR_CAP, x + ERROR_BAR_CAP).unwrap();
+                        }
+                        writeln!(svg, r#"<circle cx="{x:.2}" cy="{y:.2}" r="{MARKER_RADIUS}" fill="{colour}"/>"#).unwrap();
+                    }
+                }
+                SeriesStyle::Line => {
+                    let points: Vec<String> = positions.iter().map(|(_, x, y)| format!("{x:.2},{y:.2}")).collect();
+                    writeln!(svg, r#"<polyline points="{}" stroke="{colour}" stroke-width="2" fill="none"/>"#, points.join(" ")).unwrap();
+                }
+            }
+
+            // legend in the top left corner of the plot area
+            let legend_y = top + 20.0 + 20.0 * index as f64;
+            match series.style {
+                SeriesStyle::Markers => writeln!(svg, r#"<circle cx="{}" cy="{legend_y}" r="{MARKER_RADIUS}" fill="{colour}"/>"#, left + 20.0).unwrap(),
+                SeriesStyle::Line => writeln!(svg, r#"<line x1="{}" y1="{legend_y}" x2="{}" y2="{legend_y}" stroke="{colour}" stroke-width="2"/>"#, left + 10.0, left + 30.0).unwrap(),
+            }
+            writeln!(svg, r#"<text x="{}" y="{}" font-size="12">{}</text>"#, left + 38.0, legend_y + 4.0, escape(&series.label)).unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn save(&self, file_name: &str) {
+        fs::write(file_name, self.to_svg()).unwrap();
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,172 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use bson::doc;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{SnapshotTrigger, TrajectoryConfig, TrajectoryFormat};
+use crate::recorder::TrialRecorder;
+use crate::site::Site;
+
+/// The state of a chain at one point of its evolution
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrajectorySnapshot {
+    // The trial within its chain size, inspect --trial replays it
+    pub trial: u64,
+    // The seed of the trial's random number generator, derived from the master seed
+    pub seed: u64,
+    // Steps of the discrete dynamics taken so far, the number of moves for the gillespie dynamics
+    pub step: u64,
+    // Moves that changed the chain so far
+    pub moves: u64,
+    // Time in the configured time unit, only measured by the gillespie dynamics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    pub chain: Vec<Site>,
+}
+
+/// Collects the snapshots of a single trial. Keeps the chains the trajectory config asks for, along with the
+/// starting chain and the last chain before death.
+pub struct TrajectoryRecorder {
+    snapshot_trigger: SnapshotTrigger,
+    interval: u64,
+    trial: u64,
+    seed: u64,
+    last_recorded_moves: Option<u64>,
+    pub snapshots: Vec<TrajectorySnapshot>,
+}
+
+impl TrajectoryRecorder {
+    pub fn new(trajectory_config: &TrajectoryConfig, trial: u64, seed: u64) -> Self {
+        TrajectoryRecorder {
+            snapshot_trigger: trajectory_config.snapshot,
+            interval: trajectory_config.interval,
+            trial,
+            seed,
+            last_recorded_moves: None,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// A recorder that keeps nothing, for trials whose trajectory is not wanted
+    pub fn disabled() -> Self {
+        TrajectoryRecorder::new(&TrajectoryConfig::default(), 0, 0)
+    }
+
+    fn take_snapshot(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]) {
+        self.last_recorded_moves = Some(moves as u64);
+        self.snapshots.push(TrajectorySnapshot { trial: self.trial, seed: self.seed, step: step as u64, moves: moves as u64, time, chain: chain.to_vec() });
+    }
+}
+
+impl TrialRecorder for TrajectoryRecorder {
+    fn is_recording(&self) -> bool {
+        self.snapshot_trigger != SnapshotTrigger::Off
+    }
+
+    fn record(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]) {
+        let is_due = match self.snapshot_trigger {
+            SnapshotTrigger::Off => return,
+            SnapshotTrigger::Steps => step.is_multiple_of(self.interval as u128),
+            // only once per move, idle steps after it leave the move count unchanged
+            SnapshotTrigger::Moves => moves.is_multiple_of(self.interval as u128) && self.last_recorded_moves != Some(moves as u64),
+        };
+        if is_due || step == 0 {
+            self.take_snapshot(step, moves, time, chain);
+        }
+    }
+
+    fn record_idle_steps(&mut self, first_step: u128, end_step: u128, moves: u128, chain: &[Site]) {
+        if self.snapshot_trigger != SnapshotTrigger::Steps {
+            return;
+        }
+        let interval = self.interval as u128;
+        let mut step = first_step.div_ceil(interval) * interval;
+        while step < end_step {
+            self.take_snapshot(step, moves, None, chain);
+            step += interval;
+        }
+    }
+
+    fn finish(&mut self, step: u128, moves: u128, time: Option<f64>, chain: &[Site]) {
+        if self.is_recording() && self.snapshots.last().map(|snapshot| snapshot.step as u128) != Some(step) {
+            self.take_snapshot(step, moves, time, chain);
+        }
+    }
+}
+
+/// Writes the trajectories of one chain size to trajectories_ss_{spin sector}_cs_{chain size}.jsonl or .bson in the
+/// storage directory. A run that starts the chain size over truncates the file, and a resumed run cuts it back to its
+/// length at the checkpoint, so every trial is in the file once.
+pub struct TrajectoryWriter {
+    trajectory_format: TrajectoryFormat,
+    file: BufWriter<File>,
+}
+
+impl TrajectoryWriter {
+    /// * first_trial: the trial the run starts the chain size at
+    /// * resume_length: the length of the file at the checkpoint the run resumes from, see Checkpoint::trajectory_length
+    ///
+    /// A run resumed from a checkpoint that does not record the length of the file writes the trials from first_trial on
+    /// to a new segment, trajectories_ss_{spin sector}_cs_{chain size}_from_{first_trial}, and leaves the file alone.
+    pub fn new(trajectory_config: &TrajectoryConfig, storage_directory: &str, spin_sector: usize, chain_size: usize, first_trial: u64, resume_length: Option<u64>) -> Self {
+        let extension = match trajectory_config.format {
+            TrajectoryFormat::JsonLines => "jsonl",
+            TrajectoryFormat::Bson => "bson",
+        };
+        let file_name = format!("{}/trajectories_ss_{}_cs_{}.{}", storage_directory, spin_sector, chain_size, extension);
+        let file = match resume_length {
+            Some(length) if first_trial > 0 => {
+                let mut file = OpenOptions::new().write(true).create(true).truncate(false).open(&file_name).unwrap();
+                let file_length = file.metadata().unwrap().len();
+                if file_length < length {
+                    panic!("{} holds {} bytes, fewer than the {} it held at the checkpoint", file_name, file_length, length);
+                }
+                file.set_len(length).unwrap();
+                file.seek(SeekFrom::End(0)).unwrap();
+                file
+            }
+            None if first_trial > 0 => {
+                File::create(format!("{}/trajectories_ss_{}_cs_{}_from_{}.{}", storage_directory, spin_sector, chain_size, first_trial, extension)).unwrap()
+            }
+            _ => File::create(file_name).unwrap(),
+        };
+        TrajectoryWriter { trajectory_format: trajectory_config.format, file: BufWriter::new(file) }
+    }
+
+    /// The number of bytes written to the file so far
+    pub fn length(&mut self) -> u64 {
+        self.file.flush().unwrap();
+        self.file.get_mut().stream_position().unwrap()
+    }
+
+    /// Writes every snapshot of a trial, one JSON object per line or one BSON document after the other
+    pub fn write_trajectory(&mut self, snapshots: &[TrajectorySnapshot]) {
+        for snapshot in snapshots {
+            match self.trajectory_format {
+                TrajectoryFormat::JsonLines => {
+                    serde_json::to_writer(&mut self.file, snapshot).unwrap();
+                    self.file.write_all(b"\n").unwrap();
+                }
+                TrajectoryFormat::Bson => bson_document(snapshot).to_writer(&mut self.file).unwrap(),
+            }
+        }
+        self.file.flush().unwrap();
+    }
+}
+
+/// BSON only has signed integers, so the seed is stored with the same bits as an i64 and the chain as site codes
+fn bson_document(snapshot: &TrajectorySnapshot) -> bson::Document {
+    let chain: Vec<i32> = snapshot.chain.iter().map(|site| i8::from(*site) as i32).collect();
+    let mut document = doc! {
+        "trial": snapshot.trial as i64,
+        "seed": snapshot.seed as i64,
+        "step": snapshot.step as i64,
+        "moves": snapshot.moves as i64,
+        "chain": chain,
+    };
+    if let Some(time) = snapshot.time {
+        document.insert("time", time);
+    }
+    document
+}
@@ -0,0 +1,48 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::config::UpdateScheme;
+
+/// Hands out the triples the random-index dynamics attempts, one step at a time, in the order of an update scheme.
+/// Sweeps attempt every one of the chain_size - 2 triples once.
+pub struct UpdateSchedule {
+    update_scheme: UpdateScheme,
+    // The triples of the current sweep in the order they are attempted, unused by random sequential updates
+    sweep: Vec<usize>,
+    next_position: usize,
+}
+
+impl UpdateSchedule {
+    pub fn new(update_scheme: UpdateScheme, chain_size: usize) -> Self {
+        let number_of_triples = chain_size - 2;
+        let sweep: Vec<usize> = match update_scheme {
+            UpdateScheme::RandomSequential => Vec::new(),
+            UpdateScheme::OrderedSweep | UpdateScheme::RandomPermutation => (0..number_of_triples).collect(),
+            // Triples three sites apart do not overlap, so each sublattice can be updated in parallel
+            UpdateScheme::Checkerboard => (0..3).flat_map(|sublattice| (sublattice..number_of_triples).step_by(3)).collect(),
+        };
+        UpdateSchedule { update_scheme, sweep, next_position: 0 }
+    }
+
+    /// The starting index of the triple attempted in the next step
+    pub fn next_index<R: Rng>(&mut self, chain_size: usize, rng: &mut R) -> usize {
+        if self.update_scheme == UpdateScheme::RandomSequential {
+            return rng.gen_range(0..chain_size - 2);
+        }
+        if self.next_position == self.sweep.len() {
+            self.next_position = 0;
+        }
+        if self.next_position == 0 && self.update_scheme == UpdateScheme::RandomPermutation {
+            self.sweep.shuffle(rng);
+        }
+        self.next_position += 1;
+        self.sweep[self.next_position - 1]
+    }
+
+    /// Whether the last step completed a sweep of a deterministic scheme. Every Fredkin move that does not kill the chain
+    /// can be undone, so a sweep of a deterministic scheme is a one-to-one map between living chains. A chain it never
+    /// kills therefore returns to its starting spins after some number of sweeps and then cycles forever.
+    pub fn completed_deterministic_sweep(&self) -> bool {
+        matches!(self.update_scheme, UpdateScheme::OrderedSweep | UpdateScheme::Checkerboard) && self.next_position == self.sweep.len()
+    }
+}